@@ -0,0 +1,162 @@
+// Compares the symbol table backends behind Environment.
+// cargo bench --bench symbols
+// Lookups are also measured against the sorted layouts from runtime/rust/heap.rs,
+// Eytzinger order and interpolated search. Those are read-only, so they're not
+// compared for inserts.
+#[macro_use]
+extern crate criterion;
+extern crate avs;
+extern crate eytzinger;
+
+use criterion::{Criterion, BenchmarkId};
+use eytzinger::SliceExt;
+use avs::constants::{APP_SYMBOL_START, LOW32_MASK};
+use avs::environment::Environment;
+use avs::structs::{Atom, Identifier};
+use avs::symbols::{SymbolStore, HashSymbolStore, DenseSymbolStore};
+use avs::utils::create_pointer_symbol;
+
+const SIZES: [u64; 3] = [100, 10_000, 100_000];
+// Reads per entry. Evenly distributed, though real usage is biased towards recent symbols.
+const READ_MULTIPLIER: u64 = 10;
+
+fn fill(store: &mut dyn SymbolStore, size: u64) {
+    for i in 0..size {
+        let symbol = create_pointer_symbol(APP_SYMBOL_START + i);
+        store.insert(symbol, Identifier {
+            symbol: symbol,
+            name: None,
+            value: Some(Atom::NumericValue(i as f64))
+        });
+    }
+}
+
+fn read_all<'a>(get: impl Fn(u64) -> Option<&'a Identifier>, size: u64) -> u64 {
+    let mut found = 0;
+    for i in 0..(size * READ_MULTIPLIER) {
+        if get(create_pointer_symbol(APP_SYMBOL_START + (i % size))).is_some() {
+            found += 1;
+        }
+    }
+    return found
+}
+
+fn sorted_entries(size: u64) -> Vec<(u64, Identifier)> {
+    return (0..size).map(|i| {
+        let symbol = create_pointer_symbol(APP_SYMBOL_START + i);
+        (symbol, Identifier {
+            symbol: symbol,
+            name: None,
+            value: Some(Atom::NumericValue(i as f64))
+        })
+    }).collect()
+}
+
+// Breadth-first order of the sorted entries, so the first levels of the search share cache lines.
+fn eytzinger_entries(size: u64) -> Vec<(u64, Identifier)> {
+    let mut entries = sorted_entries(size);
+    entries.eytzingerize(&mut eytzinger::permutation::InplacePermutator);
+    return entries
+}
+
+fn eytzinger_get(entries: &[(u64, Identifier)], symbol: u64) -> Option<&Identifier> {
+    return entries.eytzinger_search_by(|entry| entry.0.cmp(&symbol)).map(|index| &entries[index].1)
+}
+
+// Guesses the position from the id, rather than bisecting. Ids are nearly sequential.
+fn interpolated_get(entries: &[(u64, Identifier)], symbol: u64) -> Option<&Identifier> {
+    let id = symbol & LOW32_MASK;
+    let mut low = 0;
+    let mut high = entries.len().checked_sub(1)?;
+    while low <= high {
+        let low_id = entries[low].0 & LOW32_MASK;
+        let high_id = entries[high].0 & LOW32_MASK;
+        if id < low_id || id > high_id {
+            return None
+        }
+        let mid = if high_id == low_id {
+            low
+        } else {
+            low + ((id - low_id) as usize * (high - low)) / (high_id - low_id) as usize
+        };
+        let mid_id = entries[mid].0 & LOW32_MASK;
+        if mid_id == id {
+            return if entries[mid].0 == symbol { Some(&entries[mid].1) } else { None }
+        } else if mid_id < id {
+            low = mid + 1;
+        } else if mid == 0 {
+            return None
+        } else {
+            high = mid - 1;
+        }
+    }
+    return None
+}
+
+fn bench_lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lookup");
+    for size in SIZES.iter() {
+        let mut hash = HashSymbolStore::new();
+        fill(&mut hash, *size);
+        let mut dense = DenseSymbolStore::new(APP_SYMBOL_START);
+        fill(&mut dense, *size);
+        let eytzinger = eytzinger_entries(*size);
+        let sorted = sorted_entries(*size);
+        group.bench_with_input(BenchmarkId::new("hash", size), size, |b, size| b.iter(|| read_all(|symbol| hash.get(symbol), *size)));
+        group.bench_with_input(BenchmarkId::new("dense", size), size, |b, size| b.iter(|| read_all(|symbol| dense.get(symbol), *size)));
+        group.bench_with_input(BenchmarkId::new("eytzinger", size), size, |b, size| b.iter(|| read_all(|symbol| eytzinger_get(&eytzinger, symbol), *size)));
+        group.bench_with_input(BenchmarkId::new("interpolated", size), size, |b, size| b.iter(|| read_all(|symbol| interpolated_get(&sorted, symbol), *size)));
+    }
+    group.finish();
+}
+
+fn bench_insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert");
+    for size in SIZES.iter() {
+        group.bench_with_input(BenchmarkId::new("hash", size), size, |b, size| b.iter(|| {
+            let mut store = HashSymbolStore::new();
+            fill(&mut store, *size);
+            store.len()
+        }));
+        group.bench_with_input(BenchmarkId::new("dense", size), size, |b, size| b.iter(|| {
+            let mut store = DenseSymbolStore::new(APP_SYMBOL_START);
+            fill(&mut store, *size);
+            store.len()
+        }));
+    }
+    group.finish();
+}
+
+// Chains of cells referencing the previous cell. i.e. A = 1, B = A, C = B...
+fn resolve_chain(env: &Environment, size: u64) -> u64 {
+    let last = create_pointer_symbol(APP_SYMBOL_START + size - 1);
+    return env.resolve_chain(last).map(|identifier| identifier.symbol).unwrap_or(0)
+}
+
+fn chained_env(store: Box<dyn SymbolStore>, size: u64) -> Environment {
+    let mut env = Environment::with_store(APP_SYMBOL_START, store);
+    env.budget.limits.max_depth = size as u32 + 1;
+    let first = env.define_identifier();
+    env.bind_value(first, Atom::NumericValue(1.0));
+    let mut previous = first;
+    for _ in 1..size {
+        let symbol = env.define_identifier();
+        env.bind_value(symbol, Atom::SymbolValue(previous));
+        previous = symbol;
+    }
+    return env
+}
+
+fn bench_resolve(c: &mut Criterion) {
+    let mut group = c.benchmark_group("resolve_chain");
+    for size in [10, 1_000].iter() {
+        let hash_env = chained_env(Box::new(HashSymbolStore::new()), *size);
+        let dense_env = chained_env(Box::new(DenseSymbolStore::new(APP_SYMBOL_START)), *size);
+        group.bench_with_input(BenchmarkId::new("hash", size), size, |b, size| b.iter(|| resolve_chain(&hash_env, *size)));
+        group.bench_with_input(BenchmarkId::new("dense", size), size, |b, size| b.iter(|| resolve_chain(&dense_env, *size)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_lookup, bench_insert, bench_resolve);
+criterion_main!(benches);
@@ -0,0 +1,234 @@
+use crate::environment::Environment;
+use crate::structs::Atom;
+use crate::constants::*;
+use crate::types::*;
+use crate::numeric::int_arg;
+use core::cmp::Ordering;
+use std::rc::Rc;
+use im_rc::{Vector, OrdMap};
+
+/*
+Persistent lists and maps. i.e. [1, 2, 3] and {"a": 1, "b": 2}
+Values are immutable, so updates return a new version which shares structure with the old one
+(RRB vector for lists, B-tree for maps). Cloning either is O(1), so reading one out of the
+environment doesn't copy its contents.
+Elements are stored as u64 values, like the expression stack. Boxed elements stay in the environment.
+Elements may be errors, like error cells in a spreadsheet range. They surface when the element is
+read, or per the policy of an aggregate (see stats.rs), rather than when the list is built.
+*/
+
+// Map keys are compared by value rather than by symbol, so equal strings find the same entry.
+#[derive(Debug, Clone)]
+pub enum MapKey {
+    Number(f64),
+    Text(Rc<str>)
+}
+
+impl PartialEq for MapKey {
+    fn eq(&self, other: &Self) -> bool {
+        return self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for MapKey {}
+
+impl PartialOrd for MapKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return Some(self.cmp(other))
+    }
+}
+
+// Numbers sort before text
+impl Ord for MapKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (MapKey::Number(a), MapKey::Number(b)) => a.total_cmp(b),
+            (MapKey::Number(_), MapKey::Text(_)) => Ordering::Less,
+            (MapKey::Text(_), MapKey::Number(_)) => Ordering::Greater,
+            (MapKey::Text(a), MapKey::Text(b)) => a.cmp(b)
+        }
+    }
+}
+
+// Only numbers and strings can be used as keys.
+pub fn map_key(env: &Environment, value: u64) -> Result<MapKey, u64> {
+    if is_error(value) {
+        return Err(value)
+    }
+    match resolve_atom!(env, value) {
+        // -0 and 0 are the same key
+        Atom::NumericValue(num) if !num.is_nan() => Ok(MapKey::Number(num + 0.0)),
+        Atom::StringValue(text) => Ok(MapKey::Text(text)),
+        _ => Err(RUNTIME_ERR_INVALID_ARG)
+    }
+}
+
+// Convert a key back into a value. Strings are re-allocated, since the key isn't an environment symbol.
+pub fn key_value(env: &mut Environment, key: &MapKey) -> u64 {
+    match key {
+        MapKey::Number(num) => num.to_bits(),
+        MapKey::Text(text) => env.init_string(text.to_string())
+    }
+}
+
+pub fn build_list(env: &mut Environment, values: Vec<u64>) -> u64 {
+    return env.init_value(Atom::ListValue(values.into_iter().collect()))
+}
+
+// Values alternate between keys and values. Later keys replace earlier ones.
+pub fn build_map(env: &mut Environment, values: Vec<u64>) -> u64 {
+    if values.len() % 2 != 0 {
+        return RUNTIME_ERR_INVALID_ARG
+    }
+    let mut map: OrdMap<MapKey, u64> = OrdMap::new();
+    for pair in values.chunks(2) {
+        match map_key(env, pair[0]) {
+            Ok(key) => { map.insert(key, pair[1]); },
+            Err(err) => return err
+        }
+    }
+    return env.init_value(Atom::MapValue(map))
+}
+
+// Negative indexes count back from the end. i.e. -1 is the last element
+fn list_index(list: &Vector<u64>, index: u64) -> Result<usize, u64> {
+    let index = int_arg(index)?;
+    let position = if index < 0 { list.len() as i64 + index } else { index };
+    if position < 0 || position >= list.len() as i64 {
+        return Err(RUNTIME_ERR_INVALID_ARG)
+    }
+    return Ok(position as usize)
+}
+
+// get(collection, key)
+pub fn __av_get(env: &mut Environment, collection: u64, key: u64) -> u64 {
+    if is_error(collection) {
+        return collection
+    }
+    match resolve_atom!(env, collection) {
+        Atom::ListValue(list) => {
+            match list_index(&list, key) {
+                Ok(index) => list[index],
+                Err(err) => err
+            }
+        },
+        Atom::MapValue(map) => {
+            match map_key(env, key) {
+                Ok(map_key) => *map.get(&map_key).unwrap_or(&RUNTIME_ERR_UNK_FIELD),
+                Err(err) => err
+            }
+        },
+        _ => RUNTIME_ERR_INVALID_TYPE
+    }
+}
+
+// push(list, value). Returns a new list with the value appended.
+pub fn __av_push(env: &mut Environment, collection: u64, value: u64) -> u64 {
+    if is_error(collection) {
+        return collection
+    }
+    match resolve_atom!(env, collection) {
+        Atom::ListValue(mut list) => {
+            list.push_back(value);
+            env.init_value(Atom::ListValue(list))
+        },
+        _ => RUNTIME_ERR_INVALID_TYPE
+    }
+}
+
+// set(collection, key, value). Returns a new version with the key replaced.
+pub fn __av_set(env: &mut Environment, collection: u64, key: u64, value: u64) -> u64 {
+    if is_error(collection) {
+        return collection
+    }
+    match resolve_atom!(env, collection) {
+        Atom::ListValue(list) => {
+            match list_index(&list, key) {
+                Ok(index) => env.init_value(Atom::ListValue(list.update(index, value))),
+                Err(err) => err
+            }
+        },
+        Atom::MapValue(map) => {
+            match map_key(env, key) {
+                Ok(map_key) => env.init_value(Atom::MapValue(map.update(map_key, value))),
+                Err(err) => err
+            }
+        },
+        _ => RUNTIME_ERR_INVALID_TYPE
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn num(value: f64) -> u64 {
+        return value.to_bits()
+    }
+
+    fn list_of(env: &Environment, list: u64) -> Vector<u64> {
+        match resolve_atom!(env, list) {
+            Atom::ListValue(values) => values,
+            _ => panic!("Expected a list")
+        }
+    }
+
+    #[test]
+    fn test_list_updates_share() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let list = build_list(&mut env, vec![num(1.0), num(2.0)]);
+        let pushed = __av_push(&mut env, list, num(3.0));
+        let updated = __av_set(&mut env, pushed, num(-1.0), num(30.0));
+        // Earlier versions are unchanged
+        assert_eq!(list_of(&env, list), Vector::from(vec![num(1.0), num(2.0)]));
+        assert_eq!(list_of(&env, pushed), Vector::from(vec![num(1.0), num(2.0), num(3.0)]));
+        assert_eq!(list_of(&env, updated), Vector::from(vec![num(1.0), num(2.0), num(30.0)]));
+
+        assert_eq!(__av_get(&mut env, updated, num(0.0)), num(1.0));
+        assert_eq!(__av_get(&mut env, updated, num(-1.0)), num(30.0));
+        assert_eq!(__av_get(&mut env, updated, num(3.0)), RUNTIME_ERR_INVALID_ARG);
+        assert_eq!(__av_get(&mut env, updated, num(0.5)), RUNTIME_ERR_INVALID_ARG);
+        assert_eq!(__av_get(&mut env, num(1.0), num(0.0)), RUNTIME_ERR_INVALID_TYPE);
+        // Errors are kept as elements until they're read
+        let with_error = build_list(&mut env, vec![num(1.0), RUNTIME_ERR_DIV_Z]);
+        assert_eq!(__av_get(&mut env, with_error, num(1.0)), RUNTIME_ERR_DIV_Z);
+    }
+
+    #[test]
+    fn test_map_keys() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let long_key = env.init_string(String::from("A longer key"));
+        let inline_key = env.init_string(String::from("a"));
+        let map = build_map(&mut env, vec![long_key, num(1.0), num(-0.0), num(2.0)]);
+        // Keys match by value, not by symbol
+        let same_key = env.init_string(String::from("A longer key"));
+        assert_eq!(__av_get(&mut env, map, same_key), num(1.0));
+        assert_eq!(__av_get(&mut env, map, num(0.0)), num(2.0));
+        assert_eq!(__av_get(&mut env, map, inline_key), RUNTIME_ERR_UNK_FIELD);
+
+        let updated = __av_set(&mut env, map, inline_key, num(3.0));
+        assert_eq!(__av_get(&mut env, updated, inline_key), num(3.0));
+        assert_eq!(__av_get(&mut env, map, inline_key), RUNTIME_ERR_UNK_FIELD);
+
+        assert_eq!(build_map(&mut env, vec![inline_key]), RUNTIME_ERR_INVALID_ARG);
+        assert_eq!(build_map(&mut env, vec![RUNTIME_ERR_DIV_Z, num(1.0)]), RUNTIME_ERR_DIV_Z);
+        let list = build_list(&mut env, vec![]);
+        assert_eq!(build_map(&mut env, vec![list, num(1.0)]), RUNTIME_ERR_INVALID_ARG);
+        assert!(MapKey::Number(5.0) < MapKey::Text("1".into()));
+    }
+
+    #[test]
+    fn test_repr() {
+        use crate::format::{repr, repr_atom};
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let inline = env.init_string(String::from("a"));
+        let long = env.init_string(String::from("A longer string"));
+        let list = build_list(&mut env, vec![num(1.0), inline, long, RUNTIME_ERR_DIV_Z]);
+        assert_eq!(repr(&env, list), "[1, \"a\", \"A longer string\", #Error]");
+        let map = build_map(&mut env, vec![inline, list]);
+        assert_eq!(repr(&env, map), "{a: [1, \"a\", \"A longer string\", #Error]}");
+        // Without the environment, only inline values can be shown
+        assert_eq!(repr_atom(&Atom::ListValue(Vector::from(vec![num(1.0), inline]))), "[1, \"a\"]");
+    }
+}
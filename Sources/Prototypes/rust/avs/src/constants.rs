@@ -27,9 +27,19 @@ Keywords 0-256 reserved for keywords.
 
 // 8 = 1000 in binary
 use crate::functions::NativeFn2;
-use crate::structs::{Keyword, Module};
+use crate::structs::{Keyword, Module, Atom};
 use crate::operators::*;
 use crate::functions::*;
+use crate::collections::{__av_get, __av_push, __av_set};
+use crate::math::*;
+use crate::strings::*;
+use crate::stats::*;
+use crate::finance::*;
+use crate::random::*;
+use crate::text::*;
+use crate::units::__av_convert;
+use crate::formatting::__av_format;
+use crate::sorting::*;
 
 
 pub const SIGNALING_NAN: u64 = 0xFFF8_0000_0000_0000;
@@ -116,6 +126,14 @@ pub const SYMBOL_CALL_FN: Keyword = Keyword {
     operation: None
 };
 
+// Right associative. 2 ^ 3 ^ 2 = 2 ^ 9
+pub const SYMBOL_CARET: Keyword = Keyword {
+    symbol: 0xFFFF_0000_0000_001D,
+    name: "^",
+    precedence: Some(22),
+    operation: Some(__av_power)
+};
+
 
 
 
@@ -166,14 +184,14 @@ pub const SYMBOL_DBL_EQUALS: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0005,
     name: "==",
     precedence: Some(10),
-    operation: None
+    operation: Some(__av_eq)
 };
 
 pub const SYMBOL_NOT_EQUALS: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0006,
     name: "!=",
     precedence: Some(10),
-    operation: None
+    operation: Some(__av_ne)
 };
 
 
@@ -246,7 +264,7 @@ pub const SYMBOL_DOT: Keyword = Keyword {
     symbol: 0xFFFF_0000_0000_0010,
     name: ".",
     precedence: Some(25),
-    operation: None         // TODO
+    operation: Some(__av_dot)
 };
 
 
@@ -321,16 +339,17 @@ pub const AV_CLASS_STRING: u64 = 0xFFFF_0000_0000_1029;
 
 // Reserve up to 65k symbols for standard library usage. (Classes, functions, etc.)
 
+// min(list), min(list, "skip") or min(a, b, ...)
 pub const AV_FN_MIN: Module = Module {
     symbol: 0xFFFD_0000_0000_0100,
     name: "min",
-    value: NativeFn2::create_atom(__av_min)
+    value: NativeFnN::create_atom(__av_min)
 };
 
 pub const AV_FN_MAX: Module = Module {
     symbol: 0xFFFD_0000_0000_0101,
     name: "max",
-    value: NativeFn2::create_atom(__av_max)
+    value: NativeFnN::create_atom(__av_max)
 };
 
 pub const AV_FN_ABS: Module = Module {
@@ -359,10 +378,11 @@ pub const AV_FN_TRUNC: Module = Module {
     value: NativeFn1::create_atom(__av_truncate)
 };
 
+// round(value) or round(value, digits)
 pub const AV_FN_ROUND: Module = Module {
     symbol: 0xFFFD_0000_0000_0106,
     name: "round",
-    value: NativeFn1::create_atom(__av_round)
+    value: NativeFnN::create_atom(__av_round)
 };
 
 pub const AV_FN_SQRT: Module = Module {
@@ -371,6 +391,520 @@ pub const AV_FN_SQRT: Module = Module {
     value: NativeFn1::create_atom(__av_sqrt)
 };
 
+// Error recovery
+pub const AV_FN_DEFAULT: Module = Module {
+    symbol: 0xFFFD_0000_0000_0108,
+    name: "default",
+    value: NativeFn2::create_atom(__av_default)
+};
+
+pub const AV_FN_IS_ERROR: Module = Module {
+    symbol: 0xFFFD_0000_0000_0109,
+    name: "is_error",
+    value: NativeFn1::create_atom(__av_is_error)
+};
+
+pub const AV_FN_LEN: Module = Module {
+    symbol: 0xFFFD_0000_0000_010A,
+    name: "len",
+    value: NativeFn1::create_atom(__av_len)
+};
+
+pub const AV_FN_DECIMAL: Module = Module {
+    symbol: 0xFFFD_0000_0000_010B,
+    name: "decimal",
+    value: NativeFn2::create_atom(__av_decimal)
+};
+
+pub const AV_FN_DECIMAL_ROUND: Module = Module {
+    symbol: 0xFFFD_0000_0000_010C,
+    name: "decimal_round",
+    value: NativeFn3::create_atom(__av_decimal_round)
+};
+
+pub const AV_FN_DATE: Module = Module {
+    symbol: 0xFFFD_0000_0000_010D,
+    name: "date",
+    value: NativeFn1::create_atom(__av_date)
+};
+
+pub const AV_FN_DATETIME: Module = Module {
+    symbol: 0xFFFD_0000_0000_010E,
+    name: "datetime",
+    value: NativeFn1::create_atom(__av_datetime)
+};
+
+pub const AV_FN_DAYS: Module = Module {
+    symbol: 0xFFFD_0000_0000_010F,
+    name: "days",
+    value: NativeFn1::create_atom(__av_days)
+};
+
+pub const AV_FN_HOURS: Module = Module {
+    symbol: 0xFFFD_0000_0000_0110,
+    name: "hours",
+    value: NativeFn1::create_atom(__av_hours)
+};
+
+pub const AV_FN_MINUTES: Module = Module {
+    symbol: 0xFFFD_0000_0000_0111,
+    name: "minutes",
+    value: NativeFn1::create_atom(__av_minutes)
+};
+
+pub const AV_FN_SECONDS: Module = Module {
+    symbol: 0xFFFD_0000_0000_0112,
+    name: "seconds",
+    value: NativeFn1::create_atom(__av_seconds)
+};
+
+pub const AV_FN_GET: Module = Module {
+    symbol: 0xFFFD_0000_0000_0113,
+    name: "get",
+    value: NativeFn2::create_atom(__av_get)
+};
+
+pub const AV_FN_PUSH: Module = Module {
+    symbol: 0xFFFD_0000_0000_0114,
+    name: "push",
+    value: NativeFn2::create_atom(__av_push)
+};
+
+pub const AV_FN_SET: Module = Module {
+    symbol: 0xFFFD_0000_0000_0115,
+    name: "set",
+    value: NativeFn3::create_atom(__av_set)
+};
+
+
+// Math
+pub const AV_FN_POW: Module = Module {
+    symbol: 0xFFFD_0000_0000_0116,
+    name: "pow",
+    value: NativeFn2::create_atom(__av_pow)
+};
+
+pub const AV_FN_EXP: Module = Module {
+    symbol: 0xFFFD_0000_0000_0117,
+    name: "exp",
+    value: NativeFn1::create_atom(__av_exp)
+};
+
+pub const AV_FN_LN: Module = Module {
+    symbol: 0xFFFD_0000_0000_0118,
+    name: "ln",
+    value: NativeFn1::create_atom(__av_ln)
+};
+
+pub const AV_FN_LOG10: Module = Module {
+    symbol: 0xFFFD_0000_0000_0119,
+    name: "log10",
+    value: NativeFn1::create_atom(__av_log10)
+};
+
+pub const AV_FN_LOG2: Module = Module {
+    symbol: 0xFFFD_0000_0000_011A,
+    name: "log2",
+    value: NativeFn1::create_atom(__av_log2)
+};
+
+pub const AV_FN_SIN: Module = Module {
+    symbol: 0xFFFD_0000_0000_011B,
+    name: "sin",
+    value: NativeFn1::create_atom(__av_sin)
+};
+
+pub const AV_FN_COS: Module = Module {
+    symbol: 0xFFFD_0000_0000_011C,
+    name: "cos",
+    value: NativeFn1::create_atom(__av_cos)
+};
+
+pub const AV_FN_TAN: Module = Module {
+    symbol: 0xFFFD_0000_0000_011D,
+    name: "tan",
+    value: NativeFn1::create_atom(__av_tan)
+};
+
+pub const AV_FN_ASIN: Module = Module {
+    symbol: 0xFFFD_0000_0000_011E,
+    name: "asin",
+    value: NativeFn1::create_atom(__av_asin)
+};
+
+pub const AV_FN_ACOS: Module = Module {
+    symbol: 0xFFFD_0000_0000_011F,
+    name: "acos",
+    value: NativeFn1::create_atom(__av_acos)
+};
+
+pub const AV_FN_ATAN: Module = Module {
+    symbol: 0xFFFD_0000_0000_0120,
+    name: "atan",
+    value: NativeFn1::create_atom(__av_atan)
+};
+
+pub const AV_FN_ATAN2: Module = Module {
+    symbol: 0xFFFD_0000_0000_0121,
+    name: "atan2",
+    value: NativeFn2::create_atom(__av_atan2)
+};
+
+pub const AV_FN_HYPOT: Module = Module {
+    symbol: 0xFFFD_0000_0000_0122,
+    name: "hypot",
+    value: NativeFn2::create_atom(__av_hypot)
+};
+
+pub const AV_FN_CLAMP: Module = Module {
+    symbol: 0xFFFD_0000_0000_0123,
+    name: "clamp",
+    value: NativeFn3::create_atom(__av_clamp)
+};
+
+pub const AV_FN_SIGN: Module = Module {
+    symbol: 0xFFFD_0000_0000_0124,
+    name: "sign",
+    value: NativeFn1::create_atom(__av_sign)
+};
+
+pub const AV_FN_GCD: Module = Module {
+    symbol: 0xFFFD_0000_0000_0125,
+    name: "gcd",
+    value: NativeFn2::create_atom(__av_gcd)
+};
+
+pub const AV_FN_LCM: Module = Module {
+    symbol: 0xFFFD_0000_0000_0126,
+    name: "lcm",
+    value: NativeFn2::create_atom(__av_lcm)
+};
+
+pub const AV_CONST_PI: Module = Module {
+    symbol: 0xFFFD_0000_0000_0127,
+    name: "pi",
+    value: Atom::NumericValue(core::f64::consts::PI)
+};
+
+pub const AV_CONST_E: Module = Module {
+    symbol: 0xFFFD_0000_0000_0128,
+    name: "e",
+    value: Atom::NumericValue(core::f64::consts::E)
+};
+
+pub const AV_CONST_TAU: Module = Module {
+    symbol: 0xFFFD_0000_0000_0129,
+    name: "tau",
+    value: Atom::NumericValue(2.0 * core::f64::consts::PI)
+};
+
+// Strings
+pub const AV_FN_UPPER: Module = Module {
+    symbol: 0xFFFD_0000_0000_012A,
+    name: "upper",
+    value: NativeFn1::create_atom(__av_upper)
+};
+
+pub const AV_FN_LOWER: Module = Module {
+    symbol: 0xFFFD_0000_0000_012B,
+    name: "lower",
+    value: NativeFn1::create_atom(__av_lower)
+};
+
+pub const AV_FN_TRIM: Module = Module {
+    symbol: 0xFFFD_0000_0000_012C,
+    name: "trim",
+    value: NativeFn1::create_atom(__av_trim)
+};
+
+pub const AV_FN_SPLIT: Module = Module {
+    symbol: 0xFFFD_0000_0000_012D,
+    name: "split",
+    value: NativeFn2::create_atom(__av_split)
+};
+
+pub const AV_FN_JOIN: Module = Module {
+    symbol: 0xFFFD_0000_0000_012E,
+    name: "join",
+    value: NativeFn2::create_atom(__av_join)
+};
+
+pub const AV_FN_REPLACE: Module = Module {
+    symbol: 0xFFFD_0000_0000_012F,
+    name: "replace",
+    value: NativeFn3::create_atom(__av_replace)
+};
+
+pub const AV_FN_CONTAINS: Module = Module {
+    symbol: 0xFFFD_0000_0000_0130,
+    name: "contains",
+    value: NativeFn2::create_atom(__av_contains)
+};
+
+pub const AV_FN_STARTS_WITH: Module = Module {
+    symbol: 0xFFFD_0000_0000_0131,
+    name: "starts_with",
+    value: NativeFn2::create_atom(__av_starts_with)
+};
+
+pub const AV_FN_ENDS_WITH: Module = Module {
+    symbol: 0xFFFD_0000_0000_0132,
+    name: "ends_with",
+    value: NativeFn2::create_atom(__av_ends_with)
+};
+
+pub const AV_FN_SLICE: Module = Module {
+    symbol: 0xFFFD_0000_0000_0133,
+    name: "slice",
+    value: NativeFnN::create_atom(__av_slice)
+};
+
+pub const AV_FN_REPEAT: Module = Module {
+    symbol: 0xFFFD_0000_0000_0134,
+    name: "repeat",
+    value: NativeFn2::create_atom(__av_repeat)
+};
+
+pub const AV_FN_PAD_LEFT: Module = Module {
+    symbol: 0xFFFD_0000_0000_0135,
+    name: "pad_left",
+    value: NativeFnN::create_atom(__av_pad_left)
+};
+
+pub const AV_FN_PAD_RIGHT: Module = Module {
+    symbol: 0xFFFD_0000_0000_0136,
+    name: "pad_right",
+    value: NativeFnN::create_atom(__av_pad_right)
+};
+
+pub const AV_FN_CHAR: Module = Module {
+    symbol: 0xFFFD_0000_0000_0137,
+    name: "char",
+    value: NativeFn1::create_atom(__av_char)
+};
+
+pub const AV_FN_CODEPOINT: Module = Module {
+    symbol: 0xFFFD_0000_0000_0138,
+    name: "codepoint",
+    value: NativeFn1::create_atom(__av_codepoint)
+};
+
+// Statistics. Aggregates take an optional policy for non-numeric entries. i.e. sum(list, "skip")
+pub const AV_FN_SUM: Module = Module {
+    symbol: 0xFFFD_0000_0000_0139,
+    name: "sum",
+    value: NativeFnN::create_atom(__av_sum)
+};
+
+pub const AV_FN_MEAN: Module = Module {
+    symbol: 0xFFFD_0000_0000_013A,
+    name: "mean",
+    value: NativeFnN::create_atom(__av_mean)
+};
+
+pub const AV_FN_MEDIAN: Module = Module {
+    symbol: 0xFFFD_0000_0000_013B,
+    name: "median",
+    value: NativeFnN::create_atom(__av_median)
+};
+
+pub const AV_FN_MODE: Module = Module {
+    symbol: 0xFFFD_0000_0000_013C,
+    name: "mode",
+    value: NativeFnN::create_atom(__av_mode)
+};
+
+pub const AV_FN_STDEV: Module = Module {
+    symbol: 0xFFFD_0000_0000_013D,
+    name: "stdev",
+    value: NativeFnN::create_atom(__av_stdev)
+};
+
+pub const AV_FN_STDEV_P: Module = Module {
+    symbol: 0xFFFD_0000_0000_013E,
+    name: "stdev_p",
+    value: NativeFnN::create_atom(__av_stdev_p)
+};
+
+pub const AV_FN_VARIANCE: Module = Module {
+    symbol: 0xFFFD_0000_0000_013F,
+    name: "variance",
+    value: NativeFnN::create_atom(__av_variance)
+};
+
+pub const AV_FN_VARIANCE_P: Module = Module {
+    symbol: 0xFFFD_0000_0000_0140,
+    name: "variance_p",
+    value: NativeFnN::create_atom(__av_variance_p)
+};
+
+pub const AV_FN_PERCENTILE: Module = Module {
+    symbol: 0xFFFD_0000_0000_0141,
+    name: "percentile",
+    value: NativeFnN::create_atom(__av_percentile)
+};
+
+pub const AV_FN_COUNT: Module = Module {
+    symbol: 0xFFFD_0000_0000_0142,
+    name: "count",
+    value: NativeFn1::create_atom(__av_count)
+};
+
+pub const AV_FN_COUNT_IF: Module = Module {
+    symbol: 0xFFFD_0000_0000_0143,
+    name: "count_if",
+    value: NativeFn2::create_atom(__av_count_if)
+};
+
+pub const AV_FN_CUMSUM: Module = Module {
+    symbol: 0xFFFD_0000_0000_0144,
+    name: "cumsum",
+    value: NativeFnN::create_atom(__av_cumsum)
+};
+
+pub const AV_FN_PMT: Module = Module {
+    symbol: 0xFFFD_0000_0000_0145,
+    name: "pmt",
+    value: NativeFnN::create_atom(__av_pmt)
+};
+
+pub const AV_FN_PV: Module = Module {
+    symbol: 0xFFFD_0000_0000_0146,
+    name: "pv",
+    value: NativeFnN::create_atom(__av_pv)
+};
+
+pub const AV_FN_FV: Module = Module {
+    symbol: 0xFFFD_0000_0000_0147,
+    name: "fv",
+    value: NativeFnN::create_atom(__av_fv)
+};
+
+pub const AV_FN_NPER: Module = Module {
+    symbol: 0xFFFD_0000_0000_0148,
+    name: "nper",
+    value: NativeFnN::create_atom(__av_nper)
+};
+
+pub const AV_FN_RATE: Module = Module {
+    symbol: 0xFFFD_0000_0000_0149,
+    name: "rate",
+    value: NativeFnN::create_atom(__av_rate)
+};
+
+pub const AV_FN_NPV: Module = Module {
+    symbol: 0xFFFD_0000_0000_014A,
+    name: "npv",
+    value: NativeFnN::create_atom(__av_npv)
+};
+
+pub const AV_FN_IRR: Module = Module {
+    symbol: 0xFFFD_0000_0000_014B,
+    name: "irr",
+    value: NativeFnN::create_atom(__av_irr)
+};
+
+pub const AV_FN_XIRR: Module = Module {
+    symbol: 0xFFFD_0000_0000_014C,
+    name: "xirr",
+    value: NativeFnN::create_atom(__av_xirr)
+};
+
+pub const AV_FN_RANDOM: Module = Module {
+    symbol: 0xFFFD_0000_0000_014D,
+    name: "random",
+    value: NativeFnN::create_atom(__av_random)
+};
+
+pub const AV_FN_RANDOM_INT: Module = Module {
+    symbol: 0xFFFD_0000_0000_014E,
+    name: "random_int",
+    value: NativeFn2::create_atom(__av_random_int)
+};
+
+pub const AV_FN_CHOICE: Module = Module {
+    symbol: 0xFFFD_0000_0000_014F,
+    name: "choice",
+    value: NativeFn1::create_atom(__av_choice)
+};
+
+pub const AV_FN_SHUFFLE: Module = Module {
+    symbol: 0xFFFD_0000_0000_0150,
+    name: "shuffle",
+    value: NativeFn1::create_atom(__av_shuffle)
+};
+
+pub const AV_FN_SAMPLE: Module = Module {
+    symbol: 0xFFFD_0000_0000_0151,
+    name: "sample",
+    value: NativeFn2::create_atom(__av_sample)
+};
+
+pub const AV_FN_MATCHES: Module = Module {
+    symbol: 0xFFFD_0000_0000_0152,
+    name: "matches",
+    value: NativeFn2::create_atom(__av_matches)
+};
+
+pub const AV_FN_FIND: Module = Module {
+    symbol: 0xFFFD_0000_0000_0153,
+    name: "find",
+    value: NativeFn2::create_atom(__av_find)
+};
+
+pub const AV_FN_FIND_ALL: Module = Module {
+    symbol: 0xFFFD_0000_0000_0154,
+    name: "find_all",
+    value: NativeFn2::create_atom(__av_find_all)
+};
+
+pub const AV_FN_CAPTURES: Module = Module {
+    symbol: 0xFFFD_0000_0000_0155,
+    name: "captures",
+    value: NativeFn2::create_atom(__av_captures)
+};
+
+pub const AV_FN_REPLACE_REGEX: Module = Module {
+    symbol: 0xFFFD_0000_0000_0156,
+    name: "replace_regex",
+    value: NativeFn3::create_atom(__av_replace_regex)
+};
+
+pub const AV_FN_CONVERT: Module = Module {
+    symbol: 0xFFFD_0000_0000_0157,
+    name: "convert",
+    value: NativeFn2::create_atom(__av_convert)
+};
+
+pub const AV_FN_FORMAT: Module = Module {
+    symbol: 0xFFFD_0000_0000_0158,
+    name: "format",
+    value: NativeFnN::create_atom(__av_format)
+};
+
+pub const AV_FN_SORT: Module = Module {
+    symbol: 0xFFFD_0000_0000_0159,
+    name: "sort",
+    value: NativeFn1::create_atom(__av_sort)
+};
+
+pub const AV_FN_SORT_DESC: Module = Module {
+    symbol: 0xFFFD_0000_0000_015A,
+    name: "sort_desc",
+    value: NativeFn1::create_atom(__av_sort_desc)
+};
+
+pub const AV_FN_SORT_BY: Module = Module {
+    symbol: 0xFFFD_0000_0000_015B,
+    name: "sort_by",
+    value: NativeFnN::create_atom(__av_sort_by)
+};
+
+// Functions registered by the host application take IDs from here up to APP_SYMBOL_START.
+// Like builtins, they're never dependencies between cells.
+pub const HOST_SYMBOL_START: u64 = 0xFFFD_0000_0000_8000;
+pub const MAX_HOST_FUNCTIONS: usize = 0x8000;
+
 
 pub const AV_HTTP_REQUEST: u64 = 0xFFFC_0000_0000_1100;
 pub const AV_HTTP_PATH: u64 = 0xFFFC_0000_0000_1101;
@@ -380,6 +914,7 @@ pub const AV_HTTP_QUERY: u64 = 0xFFFC_0000_0000_1102;
 //                               Error Objects                                  //
 //////////////////////////////////////////////////////////////////////////////////
 // Top 16 bits = Error code. Bottom 16 = Pointer to obj with metadata.
+// Within the code, parse errors step by 0x0100, interpreter errors by 0x0010 and runtime errors by 0x0001.
 // Convention: Higher bits for earlier stages. parsing stage -> execution stage.
 // Important! Ensure that constants are not re-used!
 
@@ -419,6 +954,24 @@ pub const RUNTIME_ERR_DIV_Z: u64            = 0xFFF9_0009_0000_0000;
 pub const RUNTIME_ERR_FN_UNK: u64     = 0xFFF9_000A_0000_0000;
 pub const RUNTIME_ERR_FN_ARITY: u64     = 0xFFF9_000B_0000_0000;
 pub const RUNTIME_ERR_FN_EXPECTED: u64     = 0xFFF9_000C_0000_0000;
+// Right type, but outside of what the function accepts. i.e. a negative scale
+pub const RUNTIME_ERR_INVALID_ARG: u64     = 0xFFF9_000D_0000_0000;
+// No attribute with that name. i.e. date.hour
+pub const RUNTIME_ERR_UNK_FIELD: u64       = 0xFFF9_000E_0000_0000;
+// The function is undefined for this input. i.e. ln(0)
+pub const RUNTIME_ERR_DOMAIN: u64          = 0xFFF9_000F_0000_0000;
+// Runtime codes 0x0001 - 0x000F are all used. Later runtime codes take the free 0x00N0 slots
+// after the interpreter's, counting down from 0x00F0 so the two don't meet.
+// Quantities with different dimensions. i.e. 5 km + 3 kg
+pub const RUNTIME_ERR_DIMENSION: u64       = 0xFFF9_00F0_0000_0000;
+
+// Interpreter resource limit errors
+pub const INTERPRETER_ERR_LIMIT_STEPS: u64  = 0xFFF9_0020_0000_0000;
+pub const INTERPRETER_ERR_LIMIT_DEPTH: u64  = 0xFFF9_0030_0000_0000;
+pub const INTERPRETER_ERR_LIMIT_MEMORY: u64 = 0xFFF9_0040_0000_0000;
+pub const INTERPRETER_ERR_TIMEOUT: u64      = 0xFFF9_0050_0000_0000;
+// An iterative solver (i.e. IRR) didn't converge within the configured iterations
+pub const INTERPRETER_ERR_LIMIT_ITERATIONS: u64 = 0xFFF9_0060_0000_0000;
 
 // Note: This must be OR-ed with a symbol header to be a symbol
 pub const APP_SYMBOL_START: u64             = 0x0000_0000_0001_0000;
\ No newline at end of file
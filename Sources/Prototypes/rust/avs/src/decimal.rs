@@ -0,0 +1,300 @@
+use core::cmp::Ordering;
+use core::fmt;
+use num_bigint::BigInt;
+use num_traits::{Zero, Signed};
+
+/*
+Base 10 decimal numbers for currency and other values which need exact decimal digits.
+Stored as an integer mantissa and a scale (digits after the decimal point), so
+1.25 = 125 with a scale of 2. Decimals are opt-in with a `d` suffix (1.25d) or decimal().
+*/
+
+// Minimum digits kept when dividing, since quotients may not terminate.
+pub const DIV_SCALE: u32 = 10;
+
+// Caps the size of literals like 1e999999d
+pub const MAX_SCALE: u32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RoundingMode {
+    // Ties go to the even neighbour. Banker's rounding - avoids bias when summing.
+    HalfEven,
+    // Ties go away from zero.
+    HalfUp
+}
+
+impl RoundingMode {
+    pub fn from_name(name: &str) -> Option<RoundingMode> {
+        match name.to_ascii_lowercase().as_ref() {
+            "half_even" => Some(RoundingMode::HalfEven),
+            "half_up" => Some(RoundingMode::HalfUp),
+            _ => None
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Decimal {
+    pub mantissa: BigInt,
+    pub scale: u32
+}
+
+fn pow10(exp: u32) -> BigInt {
+    return num_traits::pow(BigInt::from(10), exp as usize)
+}
+
+// Integer division with the remainder rounded according to the mode.
+fn round_div(num: &BigInt, den: &BigInt, mode: RoundingMode) -> BigInt {
+    let quotient = num / den;
+    let remainder = num % den;
+    if remainder.is_zero() {
+        return quotient
+    }
+    let twice_remainder: BigInt = remainder.abs() * 2;
+    let away_from_zero = match twice_remainder.cmp(&den.abs()) {
+        Ordering::Greater => true,
+        Ordering::Less => false,
+        Ordering::Equal => {
+            match mode {
+                RoundingMode::HalfUp => true,
+                RoundingMode::HalfEven => !(&quotient % BigInt::from(2)).is_zero()
+            }
+        }
+    };
+    if !away_from_zero {
+        return quotient
+    }
+    // The truncated remainder has the sign of the result
+    if num.is_negative() != den.is_negative() {
+        return quotient - 1
+    }
+    return quotient + 1
+}
+
+impl Decimal {
+    pub fn new(mantissa: BigInt, scale: u32) -> Decimal {
+        return Decimal {
+            mantissa: mantissa,
+            scale: scale
+        }
+    }
+
+    pub fn from_int(value: BigInt) -> Decimal {
+        return Decimal::new(value, 0)
+    }
+
+    // Uses the shortest representation which round-trips, so 0.1 becomes exactly 0.1.
+    pub fn from_f64(value: f64) -> Option<Decimal> {
+        if !value.is_finite() {
+            return None
+        }
+        return Decimal::parse(&format!("{}", value))
+    }
+
+    // Parse decimal text with an optional sign, fraction and exponent. i.e. -1.25e3
+    pub fn parse(text: &str) -> Option<Decimal> {
+        let (text, is_negative) = match text.chars().next() {
+            Some('-') => (&text[1..], true),
+            Some('+') => (&text[1..], false),
+            _ => (text, false)
+        };
+        let (number, exponent) = match text.find(|ch| ch == 'e' || ch == 'E') {
+            Some(index) => (&text[..index], text[index + 1..].parse::<i64>().ok()?),
+            None => (text, 0)
+        };
+        let (whole, fraction) = match number.find('.') {
+            Some(index) => (&number[..index], &number[index + 1..]),
+            None => (number, "")
+        };
+        if whole.is_empty() && fraction.is_empty() {
+            return None
+        }
+        if !whole.chars().chain(fraction.chars()).all(|ch| ch.is_ascii_digit()) {
+            return None
+        }
+
+        let digits = format!("{}{}", whole, fraction);
+        let mut mantissa: BigInt = digits.parse().ok()?;
+        let mut scale = (fraction.len() as i64).checked_sub(exponent)?;
+        if scale > MAX_SCALE as i64 || scale < -(MAX_SCALE as i64) {
+            return None
+        }
+        if scale < 0 {
+            mantissa = mantissa * pow10((-scale) as u32);
+            scale = 0;
+        }
+        if is_negative {
+            mantissa = -mantissa;
+        }
+        return Some(Decimal::new(mantissa, scale as u32))
+    }
+
+    // Change the number of digits after the decimal point, rounding if digits are dropped.
+    pub fn rescale(&self, scale: u32, mode: RoundingMode) -> Decimal {
+        if scale >= self.scale {
+            let mantissa = &self.mantissa * pow10(scale - self.scale);
+            return Decimal::new(mantissa, scale)
+        }
+        let mantissa = round_div(&self.mantissa, &pow10(self.scale - scale), mode);
+        return Decimal::new(mantissa, scale)
+    }
+
+    // Drop trailing zeroes, keeping at least min_scale digits.
+    pub fn trim(&self, min_scale: u32) -> Decimal {
+        let mut result = self.clone();
+        let ten = BigInt::from(10);
+        while result.scale > min_scale && (&result.mantissa % &ten).is_zero() {
+            result.mantissa = result.mantissa / &ten;
+            result.scale -= 1;
+        }
+        return result
+    }
+
+    pub fn add(&self, other: &Decimal) -> Decimal {
+        let scale = self.scale.max(other.scale);
+        let a = self.rescale(scale, RoundingMode::HalfEven);
+        let b = other.rescale(scale, RoundingMode::HalfEven);
+        return Decimal::new(a.mantissa + b.mantissa, scale)
+    }
+
+    pub fn sub(&self, other: &Decimal) -> Decimal {
+        return self.add(&other.neg())
+    }
+
+    // Scales add up when multiplying, so round back down to MAX_SCALE if needed.
+    pub fn mul(&self, other: &Decimal) -> Decimal {
+        let product = Decimal::new(&self.mantissa * &other.mantissa, self.scale + other.scale);
+        if product.scale > MAX_SCALE {
+            return product.rescale(MAX_SCALE, RoundingMode::HalfEven)
+        }
+        return product
+    }
+
+    // Divide, keeping at least the operands' scale. Returns None when dividing by zero.
+    pub fn div(&self, other: &Decimal, mode: RoundingMode) -> Option<Decimal> {
+        if other.mantissa.is_zero() {
+            return None
+        }
+        let min_scale = self.scale.max(other.scale);
+        let scale = min_scale.max(DIV_SCALE);
+        // self / other = (m_a * 10^(scale + s_b - s_a)) / m_b, at the target scale
+        let numerator = &self.mantissa * pow10(scale + other.scale - self.scale);
+        let quotient = round_div(&numerator, &other.mantissa, mode);
+        return Some(Decimal::new(quotient, scale).trim(min_scale))
+    }
+
+    pub fn neg(&self) -> Decimal {
+        return Decimal::new(-&self.mantissa, self.scale)
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        return self.to_string().parse().unwrap_or(core::f64::NAN)
+    }
+}
+
+impl PartialEq for Decimal {
+    // Numeric equality, so 1.5 == 1.50
+    fn eq(&self, other: &Decimal) -> bool {
+        return self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Decimal) -> Option<Ordering> {
+        return Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Decimal) -> Ordering {
+        let scale = self.scale.max(other.scale);
+        let a = self.rescale(scale, RoundingMode::HalfEven);
+        let b = other.rescale(scale, RoundingMode::HalfEven);
+        return a.mantissa.cmp(&b.mantissa)
+    }
+}
+
+// Plain notation without exponents. Keeps trailing zeroes, so 2.50 stays 2.50 for currency.
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.mantissa.abs().to_string();
+        let sign = if self.mantissa.is_negative() { "-" } else { "" };
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits)
+        }
+        // Left pad so there's at least one digit before the decimal point
+        let padded = format!("{:0>width$}", digits, width = scale + 1);
+        let (whole, fraction) = padded.split_at(padded.len() - scale);
+        return write!(f, "{}{}.{}", sign, whole, fraction)
+    }
+}
+
+impl fmt::Debug for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}d", self)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(text: &str) -> Decimal {
+        return Decimal::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(dec("1.25").to_string(), "1.25");
+        assert_eq!(dec("-0.05").to_string(), "-0.05");
+        assert_eq!(dec(".5").to_string(), "0.5");
+        assert_eq!(dec("2.50").to_string(), "2.50");
+        assert_eq!(dec("1.5e3").to_string(), "1500");
+        assert_eq!(dec("15e-4").to_string(), "0.0015");
+        assert_eq!(Decimal::from_f64(0.1).unwrap().to_string(), "0.1");
+        assert_eq!(Decimal::from_f64(1e21).unwrap().to_string(), "1000000000000000000000");
+        assert!(Decimal::parse("1.2.3").is_none());
+        assert!(Decimal::parse("e5").is_none());
+        // Exponents whose scale doesn't fit
+        assert!(Decimal::parse("1e-9223372036854775808").is_none());
+        assert!(Decimal::parse("1e9223372036854775807").is_none());
+        assert!(Decimal::from_f64(core::f64::NAN).is_none());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(dec("0.1").add(&dec("0.2")).to_string(), "0.3");
+        assert_eq!(dec("19.99").sub(&dec("20")).to_string(), "-0.01");
+        assert_eq!(dec("19.99").mul(&dec("3")).to_string(), "59.97");
+        // 1.5e-1000 rounds to even at the max scale
+        let product = Decimal::new(BigInt::from(15), 600).mul(&Decimal::new(BigInt::from(1), 401));
+        assert_eq!(product, Decimal::new(BigInt::from(2), MAX_SCALE));
+        assert_eq!(dec("1").div(&dec("4"), RoundingMode::HalfEven).unwrap().to_string(), "0.25");
+        assert_eq!(dec("10.00").div(&dec("3"), RoundingMode::HalfEven).unwrap().to_string(), "3.3333333333");
+        assert_eq!(dec("2").div(&dec("3"), RoundingMode::HalfEven).unwrap().to_string(), "0.6666666667");
+        assert!(dec("1").div(&dec("0.00"), RoundingMode::HalfEven).is_none());
+        assert_eq!(dec("1.50"), dec("1.5"));
+        assert!(dec("-1.5") < dec("1.25"));
+    }
+
+    #[test]
+    fn test_rounding_modes() {
+        let half_even = |text: &str| dec(text).rescale(0, RoundingMode::HalfEven).to_string();
+        let half_up = |text: &str| dec(text).rescale(0, RoundingMode::HalfUp).to_string();
+        assert_eq!(half_even("2.5"), "2");
+        assert_eq!(half_even("3.5"), "4");
+        assert_eq!(half_even("-2.5"), "-2");
+        assert_eq!(half_even("2.51"), "3");
+        assert_eq!(half_up("2.5"), "3");
+        assert_eq!(half_up("-2.5"), "-3");
+        assert_eq!(half_up("2.49"), "2");
+        assert_eq!(dec("1.005").rescale(2, RoundingMode::HalfUp).to_string(), "1.01");
+        assert_eq!(dec("1.2").rescale(3, RoundingMode::HalfUp).to_string(), "1.200");
+        assert_eq!(RoundingMode::from_name("HALF_UP"), Some(RoundingMode::HalfUp));
+        assert_eq!(RoundingMode::from_name("ceiling"), None);
+    }
+}
@@ -1,8 +1,14 @@
 use crate::types::is_symbol;
-use crate::utils::create_pointer_symbol;
+use crate::utils::{create_pointer_symbol, create_string_pointer, encode_inline_str};
 use crate::structs::{Identifier, Atom};
 use crate::expression::Expression;
-use crate::types::{is_pointer, is_nan};
+use crate::limits::{EvalBudget, EvalLimits};
+use crate::trace::{Tracer, TraceLevel, Span};
+use crate::heap::{Heap, is_heap_pointer};
+use crate::gc::GcState;
+use crate::random::RandomSource;
+use crate::symbols::{SymbolStore, DenseSymbolStore};
+use crate::types::{is_pointer, is_nan, is_error};
 use crate::constants::*;
 use crate::macros::*;
 
@@ -27,28 +33,73 @@ pub struct Environment {
 
     // Symbol ID -> metadata and values
     // values
-    identifiers: FnvHashMap<u64, Identifier>,
+    pub(crate) identifiers: Box<dyn SymbolStore>,
 
-    // Raw code
-    pub body: Vec<Expression>,
+    // Raw code. Shared so evaluation can iterate it while mutating the environment.
+    pub body: Rc<Vec<Expression>>,
+
+    // Objects, allocated in per-class regions
+    pub heap: Heap,
+
+    // Tracks when to collect unreachable values
+    pub gc: GcState,
 
     // TODO: Allocation when there's multiple sub-environments.
     pub next_symbol_id: u64,
+
+    // Resource usage of the cell currently being evaluated.
+    pub budget: EvalBudget,
+
+    // Optional tracing. None = disabled.
+    pub tracer: Option<Box<dyn Tracer>>,
+
+    // Seeded per-cell random streams
+    pub random: RandomSource,
+
+    // (Function symbol, arguments) -> Result, for pure host functions. See MAX_HOST_CACHE_ENTRIES
+    pub host_cache: FnvHashMap<(u64, Vec<u64>), u64>,
 }
 
 impl Environment {
     // TODO: Variant for creating a child environment with a parent arg
     pub fn new(next_symbol_id: u64) -> Environment {
+        // Symbols are sequential, so index them directly
+        return Environment::with_store(next_symbol_id, Box::new(DenseSymbolStore::new(next_symbol_id)))
+    }
+
+    pub fn with_store(next_symbol_id: u64, identifiers: Box<dyn SymbolStore>) -> Environment {
         return Environment {
             parent: Box::new(None),
             normname_symbols: FnvHashMap::default(),
-            identifiers: FnvHashMap::default(),
-            body: Vec::with_capacity(0),
+            identifiers: identifiers,
+            body: Rc::new(Vec::with_capacity(0)),
+            heap: Heap::new(),
+            gc: GcState::new(),
             next_symbol_id: next_symbol_id,
+            budget: EvalBudget::new(EvalLimits::default()),
+            tracer: None,
+            random: RandomSource::default(),
+            host_cache: FnvHashMap::default(),
+        }
+    }
+
+    pub fn trace_enter(&self, span: Span) {
+        if let Some(tracer) = &self.tracer {
+            tracer.enter(span);
+        }
+    }
+
+    pub fn trace_exit(&self, span: Span) {
+        if let Some(tracer) = &self.tracer {
+            tracer.exit(span);
         }
     }
 
     pub fn define_identifier(&mut self) -> u64 {
+        // Reuse ids freed by garbage collection before allocating new ones
+        if let Some(id) = self.identifiers.reuse_id() {
+            return create_pointer_symbol(id);
+        }
         let next_symbol: u64 = create_pointer_symbol(self.next_symbol_id);
         self.next_symbol_id += 1;
         return next_symbol;
@@ -64,7 +115,7 @@ impl Environment {
             self.normname_symbols.insert(uname, symbol);
         }
 
-        if let Some(existing) = self.identifiers.get_mut(&symbol) {
+        if let Some(existing) = self.identifiers.get_mut(symbol) {
             existing.name = Some(name);
         } else {
             let variable = Identifier {
@@ -79,7 +130,7 @@ impl Environment {
     // Bind an identifier to a value
     pub fn bind_value(&mut self, symbol: u64, value: Atom) {
         // self.identifiers.insert(symbol, value)
-        if let Some(existing) = self.identifiers.get_mut(&symbol) {
+        if let Some(existing) = self.identifiers.get_mut(symbol) {
             existing.value = Some(value);
         } else {
             let variable = Identifier {
@@ -98,11 +149,30 @@ impl Environment {
     }
 
     pub fn init_value(&mut self, value: Atom) -> u64 {
+        if let Err(err) = self.budget.allocate() {
+            return err
+        }
+        self.gc.allocated += 1;
         let symbol_id = self.define_identifier();
         self.bind_value(symbol_id, value);
         return symbol_id;
     }
 
+    // Short strings are encoded inline. Longer ones are saved to the heap
+    // behind a string pointer tagged with their length.
+    pub fn init_string(&mut self, value: String) -> u64 {
+        if let Some(inline_str) = encode_inline_str(&value) {
+            return inline_str
+        }
+        if let Err(err) = self.budget.allocate() {
+            return err
+        }
+        self.gc.allocated += 1;
+        let symbol_id = create_string_pointer(self.define_identifier(), value.chars().count());
+        self.bind_value(symbol_id, Atom::StringValue(value.into()));
+        return symbol_id
+    }
+
     // Check whether a name has already been used within this scope
     // Note that it doesn't check whether it's used outside of it.
     pub fn is_valid_name(&self, name: String) -> bool {
@@ -118,43 +188,53 @@ impl Environment {
     }
 
     pub fn lookup(&self, symbol: u64) -> Option<&Identifier> {
-        return self.identifiers.get(&symbol)
+        return self.identifiers.get(symbol)
     }
 
     // Resolve a symbol to a terminal value by following pointers
-    // Terminates at a max depth
+    // Terminates after max_depth pointer hops. Looking up the symbol itself isn't a hop.
     pub fn deep_resolve(&self, symbol: u64) -> Option<&Identifier> {
+        return self.resolve_chain(symbol).ok()
+    }
+
+    // Variant of deep_resolve which reports why the resolution failed.
+    pub fn resolve_chain(&self, symbol: u64) -> Result<&Identifier, u64> {
         let mut count = 0;
         let mut current_symbol = symbol;
 
-        while count < 1000 {
+        while count <= self.budget.limits.max_depth {
             if let Some(ident) = self.lookup(current_symbol) {
                 
                 if let Some(value) = &ident.value {
                     if let Atom::SymbolValue(next_symbol) = value {
                         // Not a pointer, so terminal value
-                        if is_symbol(*next_symbol) {
-                            return Some(ident)
+                        // Errors are terminal as well so they can be recovered from downstream.
+                        // Heap objects are values in their own right.
+                        if is_symbol(*next_symbol) || is_error(*next_symbol) || is_heap_pointer(*next_symbol) {
+                            return Ok(ident)
                         }
 
                         // Check for circular pointers
                         if *next_symbol == current_symbol || *next_symbol == symbol {
-                            println!("Cyclic symbol reference");
-                            return None
+                            av_trace!(self, TraceLevel::Warn, "Cyclic symbol reference {:X}", symbol);
+                            return Err(RUNTIME_ERR_CIRCULAR_DEP)
                         }
                         current_symbol = *next_symbol
                     }
                     else {
-                        return Some(ident)
+                        return Ok(ident)
                     }
                 } else {
-                    return None
+                    return Err(RUNTIME_ERR_UNK_VAL)
                 }
+            } else {
+                // Dangling pointer
+                return Err(RUNTIME_ERR_UNK_VAL)
             }
 
             count += 1;
         }
-        return None
+        return Err(INTERPRETER_ERR_LIMIT_DEPTH)
     }
 }
 
@@ -165,7 +245,10 @@ impl fmt::Debug for Environment {
         let mut parts = vec![];
         parts.push(String::from("Context {\n"));
 
-        parts.push(format!("Cells: {:#?}\n", self.identifiers));
+        let mut symbols = self.identifiers.symbols();
+        symbols.sort();
+        let identifiers: Vec<&Identifier> = symbols.iter().filter_map(|symbol| self.identifiers.get(*symbol)).collect();
+        parts.push(format!("Cells: {:#?}\n", identifiers));
         parts.push(format!("Names: {:#?}\n", self.normname_symbols));
         parts.push(format!("Body: {:#?}\n", self.body));
 
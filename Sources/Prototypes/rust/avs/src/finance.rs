@@ -0,0 +1,389 @@
+use crate::environment::Environment;
+use crate::structs::Atom;
+use crate::constants::*;
+use crate::types::*;
+use crate::numeric::resolve_number;
+use crate::stats::{Policy, list_values, collect_numbers};
+use chrono::Datelike;
+
+/*
+Financial functions, following the spreadsheet definitions. i.e. pmt(rate, nper, pv, fv, type)
+Cash paid out is negative and cash received is positive.
+The optional type is 0 for payments at the end of each period (default) and 1 for the start.
+Rates are found with Newton's method, starting from a guess (10% by default).
+The tolerance and iteration count come from the evaluation limits. A solver which doesn't settle
+within them returns INTERPRETER_ERR_LIMIT_ITERATIONS rather than an imprecise answer.
+*/
+
+const DEFAULT_GUESS: f64 = 0.1;
+const DAYS_PER_YEAR: f64 = 365.0;
+
+// Amounts may be decimals or big integers, but are calculated as f64 like spreadsheets.
+fn number_arg(env: &Environment, value: u64) -> Result<f64, u64> {
+    return resolve_number(env, value).map(|number| number.to_f64())
+}
+
+fn optional_arg(env: &Environment, args: &[u64], index: usize, default: f64) -> Result<f64, u64> {
+    match args.get(index) {
+        Some(value) => number_arg(env, *value),
+        None => Ok(default)
+    }
+}
+
+// 0 = end of period, 1 = start of period
+fn payment_type(env: &Environment, args: &[u64], index: usize) -> Result<f64, u64> {
+    let due = optional_arg(env, args, index, 0.0)?;
+    if due != 0.0 && due != 1.0 {
+        return Err(RUNTIME_ERR_INVALID_ARG)
+    }
+    return Ok(due)
+}
+
+fn check_arity(args: &[u64], min: usize, max: usize) -> Result<(), u64> {
+    if args.len() < min || args.len() > max {
+        return Err(RUNTIME_ERR_FN_ARITY)
+    }
+    return Ok(())
+}
+
+fn checked(result: f64) -> Result<f64, u64> {
+    if result.is_nan() {
+        return Err(RUNTIME_ERR_DOMAIN)
+    } else if result.is_infinite() {
+        return Err(RUNTIME_ERR_DIV_Z)
+    }
+    return Ok(result)
+}
+
+fn to_result(result: Result<f64, u64>) -> u64 {
+    match result {
+        Ok(number) => number.to_bits(),
+        Err(err) => err
+    }
+}
+
+// Newton's method over rates above -100%. f returns the value and its derivative at a rate.
+// RUNTIME_ERR_DOMAIN if there's no finite slope to follow, INTERPRETER_ERR_LIMIT_ITERATIONS
+// if it doesn't converge in time.
+fn solve_rate<F>(env: &Environment, guess: f64, f: F) -> Result<f64, u64> where F: Fn(f64) -> (f64, f64) {
+    if guess <= -1.0 {
+        return Err(RUNTIME_ERR_INVALID_ARG)
+    }
+    let tolerance = env.budget.limits.solver_tolerance;
+    let mut rate = guess;
+    for _ in 0..env.budget.limits.solver_iterations {
+        let (value, slope) = f(rate);
+        if !value.is_finite() || !slope.is_finite() || slope == 0.0 {
+            return Err(RUNTIME_ERR_DOMAIN)
+        }
+        let mut next = rate - value / slope;
+        // Overshooting past -100% would make (1 + rate) negative. Step half way towards it instead.
+        if next <= -1.0 {
+            next = (rate - 1.0) / 2.0;
+        }
+        if (next - rate).abs() < tolerance {
+            return Ok(next)
+        }
+        rate = next;
+    }
+    return Err(INTERPRETER_ERR_LIMIT_ITERATIONS)
+}
+
+// (1 + rate)^nper and the annuity factor for the payments, which is nper when the rate is zero.
+fn growth(rate: f64, nper: f64, due: f64) -> (f64, f64) {
+    if rate == 0.0 {
+        return (1.0, nper)
+    }
+    let compound = (1.0 + rate).powf(nper);
+    return (compound, (1.0 + rate * due) * (compound - 1.0) / rate)
+}
+
+fn pmt(env: &Environment, args: &[u64]) -> Result<f64, u64> {
+    check_arity(args, 3, 5)?;
+    let rate = number_arg(env, args[0])?;
+    let nper = number_arg(env, args[1])?;
+    let pv = number_arg(env, args[2])?;
+    let fv = optional_arg(env, args, 3, 0.0)?;
+    let (compound, annuity) = growth(rate, nper, payment_type(env, args, 4)?);
+    if annuity == 0.0 {
+        return Err(RUNTIME_ERR_DIV_Z)
+    }
+    return checked(-(pv * compound + fv) / annuity)
+}
+
+fn pv(env: &Environment, args: &[u64]) -> Result<f64, u64> {
+    check_arity(args, 3, 5)?;
+    let rate = number_arg(env, args[0])?;
+    let nper = number_arg(env, args[1])?;
+    let payment = number_arg(env, args[2])?;
+    let fv = optional_arg(env, args, 3, 0.0)?;
+    let (compound, annuity) = growth(rate, nper, payment_type(env, args, 4)?);
+    if compound == 0.0 {
+        return Err(RUNTIME_ERR_DIV_Z)
+    }
+    return checked(-(fv + payment * annuity) / compound)
+}
+
+fn fv(env: &Environment, args: &[u64]) -> Result<f64, u64> {
+    check_arity(args, 3, 5)?;
+    let rate = number_arg(env, args[0])?;
+    let nper = number_arg(env, args[1])?;
+    let payment = number_arg(env, args[2])?;
+    let pv = optional_arg(env, args, 3, 0.0)?;
+    let (compound, annuity) = growth(rate, nper, payment_type(env, args, 4)?);
+    return checked(-(pv * compound + payment * annuity))
+}
+
+fn nper(env: &Environment, args: &[u64]) -> Result<f64, u64> {
+    check_arity(args, 3, 5)?;
+    let rate = number_arg(env, args[0])?;
+    let payment = number_arg(env, args[1])?;
+    let pv = number_arg(env, args[2])?;
+    let fv = optional_arg(env, args, 3, 0.0)?;
+    let due = payment_type(env, args, 4)?;
+    if rate == 0.0 {
+        if payment == 0.0 {
+            return Err(RUNTIME_ERR_DIV_Z)
+        }
+        return checked(-(pv + fv) / payment)
+    }
+    let adjusted = payment * (1.0 + rate * due);
+    let ratio = (adjusted - fv * rate) / (adjusted + pv * rate);
+    // The balance never reaches the future value
+    if !(ratio > 0.0) || rate <= -1.0 {
+        return Err(RUNTIME_ERR_DOMAIN)
+    }
+    return checked(ratio.ln() / (1.0 + rate).ln())
+}
+
+fn rate(env: &Environment, args: &[u64]) -> Result<f64, u64> {
+    check_arity(args, 3, 6)?;
+    let nper = number_arg(env, args[0])?;
+    let payment = number_arg(env, args[1])?;
+    let pv = number_arg(env, args[2])?;
+    let fv = optional_arg(env, args, 3, 0.0)?;
+    let due = payment_type(env, args, 4)?;
+    let guess = optional_arg(env, args, 5, DEFAULT_GUESS)?;
+    if nper <= 0.0 {
+        return Err(RUNTIME_ERR_INVALID_ARG)
+    }
+    // Root of pv * (1 + r)^n + pmt * (1 + r * type) * ((1 + r)^n - 1) / r + fv
+    return solve_rate(env, guess, |r| {
+        if r == 0.0 {
+            // Limits as r approaches 0
+            return (pv + payment * nper + fv, pv * nper + payment * (nper * due + nper * (nper - 1.0) / 2.0))
+        }
+        let compound = (1.0 + r).powf(nper);
+        let compound_slope = nper * (1.0 + r).powf(nper - 1.0);
+        let annuity = (1.0 + r * due) * (compound - 1.0) / r;
+        let annuity_slope = due * (compound - 1.0) / r + (1.0 + r * due) * (compound_slope * r - (compound - 1.0)) / (r * r);
+        (pv * compound + payment * annuity + fv, pv * compound_slope + payment * annuity_slope)
+    })
+}
+
+// Cash flows are a list. Errors in it propagate, like other aggregates.
+fn cash_flows(env: &Environment, list: u64) -> Result<Vec<f64>, u64> {
+    return collect_numbers(env, &list_values(env, list)?, Policy::Propagate)
+}
+
+// A rate of return only exists when money flows both ways.
+fn check_flows(flows: &[f64]) -> Result<(), u64> {
+    if !flows.iter().any(|flow| *flow > 0.0) || !flows.iter().any(|flow| *flow < 0.0) {
+        return Err(RUNTIME_ERR_DOMAIN)
+    }
+    return Ok(())
+}
+
+// Value and derivative of the discounted flows. Each flow is discounted by its time in periods.
+fn discounted(flows: &[f64], times: &[f64], rate: f64) -> (f64, f64) {
+    let mut value = 0.0;
+    let mut slope = 0.0;
+    for (flow, time) in flows.iter().zip(times.iter()) {
+        let factor = (1.0 + rate).powf(-time);
+        value += flow * factor;
+        slope -= time * flow * factor / (1.0 + rate);
+    }
+    return (value, slope)
+}
+
+// npv(rate, list) or npv(rate, a, b, ...). The first value is discounted by one period.
+fn npv(env: &Environment, args: &[u64]) -> Result<f64, u64> {
+    if args.len() < 2 {
+        return Err(RUNTIME_ERR_FN_ARITY)
+    }
+    let rate = number_arg(env, args[0])?;
+    if rate == -1.0 {
+        return Err(RUNTIME_ERR_DIV_Z)
+    }
+    let flows = if args.len() == 2 && !is_error(args[1]) && matches!(resolve_atom!(env, args[1]), Atom::ListValue(_)) {
+        cash_flows(env, args[1])?
+    } else {
+        collect_numbers(env, &args[1..], Policy::Propagate)?
+    };
+    let times: Vec<f64> = (1..=flows.len()).map(|period| period as f64).collect();
+    return checked(discounted(&flows, &times, rate).0)
+}
+
+// irr(list) or irr(list, guess). The first value is at time zero.
+fn irr(env: &Environment, args: &[u64]) -> Result<f64, u64> {
+    check_arity(args, 1, 2)?;
+    let flows = cash_flows(env, args[0])?;
+    let guess = optional_arg(env, args, 1, DEFAULT_GUESS)?;
+    check_flows(&flows)?;
+    let times: Vec<f64> = (0..flows.len()).map(|period| period as f64).collect();
+    return solve_rate(env, guess, |rate| discounted(&flows, &times, rate))
+}
+
+// Dates are date values or day numbers
+fn day_number(env: &Environment, value: u64) -> Result<f64, u64> {
+    if is_error(value) {
+        return Err(value)
+    }
+    match resolve_atom!(env, value) {
+        Atom::NumericValue(day) => Ok(day),
+        Atom::DateValue(date) => Ok(date.num_days_from_ce() as f64),
+        _ => Err(RUNTIME_ERR_INVALID_TYPE)
+    }
+}
+
+// xirr(values, dates) or xirr(values, dates, guess). An annual rate for irregularly spaced flows.
+fn xirr(env: &Environment, args: &[u64]) -> Result<f64, u64> {
+    check_arity(args, 2, 3)?;
+    let flows = cash_flows(env, args[0])?;
+    let dates = list_values(env, args[1])?;
+    let guess = optional_arg(env, args, 2, DEFAULT_GUESS)?;
+    if flows.len() != dates.len() {
+        return Err(RUNTIME_ERR_INVALID_ARG)
+    }
+    check_flows(&flows)?;
+    let mut days: Vec<f64> = Vec::with_capacity(dates.len());
+    for date in dates.iter() {
+        days.push(day_number(env, *date)?);
+    }
+    // Flows are measured from the first date, which must be the earliest
+    let start = days[0];
+    if days.iter().any(|day| *day < start) {
+        return Err(RUNTIME_ERR_INVALID_ARG)
+    }
+    let times: Vec<f64> = days.iter().map(|day| (day - start) / DAYS_PER_YEAR).collect();
+    return solve_rate(env, guess, |rate| discounted(&flows, &times, rate))
+}
+
+// pmt(rate, nper, pv) or pmt(rate, nper, pv, fv, type). The payment per period.
+pub fn __av_pmt(env: &mut Environment, args: &[u64]) -> u64 {
+    return to_result(pmt(env, args))
+}
+
+// pv(rate, nper, pmt, fv, type). What a series of payments is worth now.
+pub fn __av_pv(env: &mut Environment, args: &[u64]) -> u64 {
+    return to_result(pv(env, args))
+}
+
+// fv(rate, nper, pmt, pv, type). The balance after the last payment.
+pub fn __av_fv(env: &mut Environment, args: &[u64]) -> u64 {
+    return to_result(fv(env, args))
+}
+
+// nper(rate, pmt, pv, fv, type). The number of payments to reach the future value.
+pub fn __av_nper(env: &mut Environment, args: &[u64]) -> u64 {
+    return to_result(nper(env, args))
+}
+
+// rate(nper, pmt, pv, fv, type, guess). The interest rate per period.
+pub fn __av_rate(env: &mut Environment, args: &[u64]) -> u64 {
+    return to_result(rate(env, args))
+}
+
+pub fn __av_npv(env: &mut Environment, args: &[u64]) -> u64 {
+    return to_result(npv(env, args))
+}
+
+pub fn __av_irr(env: &mut Environment, args: &[u64]) -> u64 {
+    return to_result(irr(env, args))
+}
+
+pub fn __av_xirr(env: &mut Environment, args: &[u64]) -> u64 {
+    return to_result(xirr(env, args))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::build_list;
+
+    fn num(value: f64) -> u64 {
+        return value.to_bits()
+    }
+
+    fn assert_close(result: u64, expected: f64) {
+        let actual = f64::from_bits(result);
+        assert!((actual - expected).abs() < 1e-6, "{} != {}", actual, expected);
+    }
+
+    fn list(env: &mut Environment, values: &[f64]) -> u64 {
+        return build_list(env, values.iter().map(|value| num(*value)).collect())
+    }
+
+    // Expected values are from the spreadsheet documentation examples
+    #[test]
+    fn test_annuities() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        assert_close(__av_pmt(&mut env, &[num(0.08 / 12.0), num(10.0), num(10000.0)]), -1037.0320893591606);
+        assert_close(__av_pmt(&mut env, &[num(0.0), num(10.0), num(10000.0)]), -1000.0);
+        assert_close(__av_pv(&mut env, &[num(0.08 / 12.0), num(240.0), num(500.0)]), -59777.14585118777);
+        assert_close(__av_fv(&mut env, &[num(0.06 / 12.0), num(10.0), num(-200.0), num(-500.0), num(1.0)]), 2581.4033740601185);
+        assert_close(__av_nper(&mut env, &[num(0.01), num(-100.0), num(-1000.0), num(10000.0), num(1.0)]), 59.67386567429457);
+        assert_close(__av_rate(&mut env, &[num(48.0), num(-200.0), num(8000.0)]), 0.007701472488211);
+        assert_eq!(__av_pmt(&mut env, &[num(0.1), num(0.0), num(100.0)]), RUNTIME_ERR_DIV_Z);
+        assert_eq!(__av_pmt(&mut env, &[num(0.1), num(10.0), num(100.0), num(0.0), num(2.0)]), RUNTIME_ERR_INVALID_ARG);
+        assert_eq!(__av_pmt(&mut env, &[num(0.1), num(10.0)]), RUNTIME_ERR_FN_ARITY);
+    }
+
+    #[test]
+    fn test_cash_flows() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let flows = list(&mut env, &[-10000.0, 3000.0, 4200.0, 6800.0]);
+        assert_close(__av_npv(&mut env, &[num(0.1), flows]), 1188.4434123352207);
+        assert_close(__av_npv(&mut env, &[num(0.1), num(-10000.0), num(3000.0), num(4200.0), num(6800.0)]), 1188.4434123352207);
+
+        let investment = list(&mut env, &[-70000.0, 12000.0, 15000.0, 18000.0, 21000.0, 26000.0]);
+        assert_close(__av_irr(&mut env, &[investment]), 0.08663094803653162);
+        let early = list(&mut env, &[-70000.0, 12000.0, 15000.0, 18000.0, 21000.0]);
+        assert_close(__av_irr(&mut env, &[early]), -0.021244848272975403);
+        let one_way = list(&mut env, &[100.0, 200.0]);
+        assert_eq!(__av_irr(&mut env, &[one_way]), RUNTIME_ERR_DOMAIN);
+
+        let flows_with_error = build_list(&mut env, vec![num(-100.0), RUNTIME_ERR_DIV_Z]);
+        assert_eq!(__av_irr(&mut env, &[flows_with_error]), RUNTIME_ERR_DIV_Z);
+    }
+
+    #[test]
+    fn test_xirr() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let flows = list(&mut env, &[-10000.0, 2750.0, 4250.0, 3250.0, 2750.0]);
+        let mut dates: Vec<u64> = Vec::new();
+        for date in ["2008-01-01", "2008-03-01", "2008-10-30", "2009-02-15", "2009-04-01"].iter() {
+            dates.push(env.init_value(Atom::DateValue(crate::temporal::parse_date(date).unwrap())));
+        }
+        let date_list = build_list(&mut env, dates);
+        assert_close(__av_xirr(&mut env, &[flows, date_list]), 0.3733625335188316);
+        let short_dates = list(&mut env, &[0.0, 10.0]);
+        assert_eq!(__av_xirr(&mut env, &[flows, short_dates]), RUNTIME_ERR_INVALID_ARG);
+    }
+
+    #[test]
+    fn test_solver_limits() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let investment = list(&mut env, &[-70000.0, 12000.0, 15000.0, 18000.0, 21000.0, 26000.0]);
+        env.budget.limits.solver_iterations = 2;
+        assert_eq!(__av_irr(&mut env, &[investment]), INTERPRETER_ERR_LIMIT_ITERATIONS);
+        // A looser tolerance settles sooner
+        env.budget.limits.solver_tolerance = 0.1;
+        assert_ne!(__av_irr(&mut env, &[investment]), INTERPRETER_ERR_LIMIT_ITERATIONS);
+        assert_eq!(__av_irr(&mut env, &[investment, num(-1.0)]), RUNTIME_ERR_INVALID_ARG);
+        // No slope to follow
+        assert_eq!(solve_rate(&env, 0.1, |_| (1.0, 0.0)), Err(RUNTIME_ERR_DOMAIN));
+        assert_eq!(solve_rate(&env, 0.1, |rate| (f64::INFINITY, rate)), Err(RUNTIME_ERR_DOMAIN));
+    }
+}
@@ -1,6 +1,18 @@
 use crate::structs::Atom;
 use core::fmt;
 use fnv::FnvHashMap;
+use num_bigint::BigInt;
+use num_traits::FromPrimitive;
+use crate::temporal::{format_datetime, format_duration};
+use crate::units::format_quantity;
+use crate::collections::MapKey;
+use crate::types::{is_nan, is_error, __av_typeof};
+use crate::utils::{is_inline_str, decode_inline_str};
+use crate::environment::Environment;
+use crate::structs::{ValueType, Identifier};
+use crate::objects::{is_class, class_symbol, field_names};
+use crate::heap::is_heap_pointer;
+use im_rc::{Vector, OrdMap};
 
 
 #[cfg(not(target_os = "unknown"))]
@@ -25,7 +37,14 @@ pub fn repr_number(number: u64) -> String {
 pub fn repr_float(f_val: f64) -> String {
     // Print integers without the trailing zeroes
     if f_val.fract() == 0.0 {
-        return format!("{:?}", f_val.trunc() as i64)
+        // Larger values would saturate as i64
+        if f_val.abs() < 9.2e18 {
+            return format!("{:?}", f_val.trunc() as i64)
+        }
+        if let Some(big) = BigInt::from_f64(f_val) {
+            return big.to_string()
+        }
+        return format!("{:?}", f_val)
     } else {
         return format!("{:?}", f_val)
     }
@@ -37,12 +56,36 @@ pub fn repr_atom(atom: &Atom) -> String {
         Atom::NumericValue(num) => {
             format!("{}", repr_float(*num))
         },
+        Atom::BigIntValue(big) => {
+            big.to_string()
+        },
+        Atom::DecimalValue(decimal) => {
+            decimal.to_string()
+        },
+        Atom::DateValue(date) => {
+            date.format("%Y-%m-%d").to_string()
+        },
+        Atom::DateTimeValue(datetime) => {
+            format_datetime(datetime)
+        },
+        Atom::DurationValue(duration) => {
+            format_duration(duration)
+        },
+        Atom::QuantityValue(quantity) => {
+            format_quantity(quantity)
+        },
         Atom::StringValue(str_val) => {
-            format!("{}", str_val)      // "\"{}\""
+            format!("\"{}\"", str_val)
         }
         Atom::SymbolValue(symbol) => {
             repr_symbol(symbol)
         },
+        Atom::ListValue(list) => {
+            repr_list(list, &repr_value)
+        },
+        Atom::MapValue(map) => {
+            repr_map(map, &repr_value)
+        },
         Atom::ObjectValue(obj_val) => {
             format!("{}", obj_val.id)
         },
@@ -55,6 +98,120 @@ pub fn repr_atom(atom: &Atom) -> String {
     }
 }
 
+// Without an environment, boxed elements can only be shown as symbols.
+#[cfg(not(target_os = "unknown"))]
+fn repr_value(value: u64) -> String {
+    if !is_nan(f64::from_bits(value)) {
+        return repr_number(value)
+    }
+    if let Some(inline_str) = decode_inline_str(value) {
+        return repr_atom(&Atom::StringValue(inline_str.into()))
+    }
+    return repr_symbol(&value)
+}
+
+// Lists and maps share their layout whether or not elements can be resolved.
+#[cfg(not(target_os = "unknown"))]
+fn repr_list(list: &Vector<u64>, repr_element: &dyn Fn(u64) -> String) -> String {
+    let values: Vec<String> = list.iter().map(|value| repr_element(*value)).collect();
+    return format!("[{}]", values.join(", "))
+}
+
+#[cfg(not(target_os = "unknown"))]
+fn repr_map(map: &OrdMap<MapKey, u64>, repr_element: &dyn Fn(u64) -> String) -> String {
+    let entries: Vec<String> = map.iter()
+        .map(|(key, value)| format!("{}: {}", repr_key(key), repr_element(*value)))
+        .collect();
+    return format!("{{{}}}", entries.join(", "))
+}
+
+// Display form of any value, resolving boxed values and their elements through the environment.
+#[cfg(not(target_os = "unknown"))]
+pub fn repr(env: &Environment, result: u64) -> String {
+    match __av_typeof(result) {
+        ValueType::NumericType => {
+            return repr_number(result)
+        },
+        ValueType::StringType if is_inline_str(result) => {
+            if let Some(inline_str) = decode_inline_str(result) {
+                return repr_atom(&Atom::StringValue(inline_str.into()));
+            }
+        },
+        ValueType::ObjectType if is_class(env, result) => {
+            return repr_class(env, result)
+        },
+        ValueType::ObjectType if is_heap_pointer(result) => {
+            return repr_object(env, result)
+        },
+        _ => {
+            if let Some(identifier) = env.lookup(result) {
+                match &identifier.value {
+                    Some(Atom::ListValue(list)) => return repr_list(list, &|value| repr_element(env, value)),
+                    Some(Atom::MapValue(map)) => return repr_map(map, &|value| repr_element(env, value)),
+                    Some(atom) => return repr_atom(&atom),
+                    None => {}
+                }
+            }
+        }
+    }
+
+    // Unknown symbol
+    return repr_symbol(&result);
+}
+
+// Errors within a list or map. Top level errors are shown separately with their message.
+#[cfg(not(target_os = "unknown"))]
+fn repr_element(env: &Environment, value: u64) -> String {
+    if is_error(value) {
+        return String::from("#Error")
+    }
+    return repr(env, value)
+}
+
+// Expression stack for tracing and the debugger. i.e. [1, 2, 3]
+#[cfg(not(target_os = "unknown"))]
+pub fn fmt_stack(env: &Environment, stack: &Vec<u64>) -> String {
+    let items: Vec<String> = stack.iter().map(|value| repr(env, *value)).collect();
+    return format!("[{}]", items.join(", "));
+}
+
+// Classes print their name and fields on one line. i.e. "Point: x, y"
+#[cfg(not(target_os = "unknown"))]
+pub fn repr_class(env: &Environment, class: u64) -> String {
+    return format!("{}: {}", class_name(env, class), field_names(env, class).join(", "))
+}
+
+// Instances print their fields. i.e. "Point(x: 1, y: 2)"
+#[cfg(not(target_os = "unknown"))]
+pub fn repr_object(env: &Environment, pointer: u64) -> String {
+    let class = match env.heap.class_of(pointer) {
+        Some(class) if is_class(env, class) => class,
+        _ => return repr_symbol(&pointer)
+    };
+    let values = env.heap.values(pointer).unwrap_or(&[]);
+    let fields: Vec<String> = field_names(env, class).iter().zip(values.iter())
+        .map(|(name, value)| format!("{}: {}", name, repr(env, *value)))
+        .collect();
+    return format!("{}({})", class_name(env, class), fields.join(", "))
+}
+
+#[cfg(not(target_os = "unknown"))]
+fn class_name(env: &Environment, class: u64) -> String {
+    let symbol = class_symbol(env, class).unwrap_or(class);
+    match env.lookup(symbol) {
+        Some(Identifier { name: Some(name), .. }) => name.to_string(),
+        _ => repr_symbol(&symbol)
+    }
+}
+
+#[cfg(not(target_os = "unknown"))]
+pub fn repr_key(key: &MapKey) -> String {
+    match key {
+        MapKey::Number(num) => repr_float(*num),
+        MapKey::Text(text) => text.to_string()
+    }
+}
+
 #[cfg(not(target_os = "unknown"))]
 pub fn fmt_symbols_map(map: &FnvHashMap<u64, Atom>) -> String {
     let mut output = vec![];
@@ -0,0 +1,488 @@
+use crate::environment::Environment;
+use crate::structs::Atom;
+use crate::constants::*;
+use crate::types::*;
+use crate::decimal::{Decimal, RoundingMode};
+use crate::temporal::date_to_datetime;
+use crate::strings::text_arg;
+use crate::locale::{Locale, DEFAULT_LOCALE, find_locale};
+use chrono::{DateTime, FixedOffset, Datelike, Timelike};
+use num_bigint::BigInt;
+use num_traits::{Zero, Signed};
+use std::iter::Peekable;
+use std::str::Chars;
+
+/*
+Pattern based formatting. i.e. format(1234.5, "#,##0.00") = "1,234.50"
+Number patterns follow the spreadsheet style:
+    0 a digit, padded with zeroes    # a digit, only if significant
+    , group separator                . decimal separator
+    % multiply by 100                ¤ the locale's currency symbol
+    E+00 scientific notation         ; separates an optional pattern for negative values
+Date patterns follow Unicode LDML. i.e. "EEEE d MMMM yyyy", "yyyy-MM-dd HH:mm"
+Any other text is copied through. Text in single quotes is always literal.
+The locale only changes the symbols and names, never the pattern's layout.
+Numbers round half away from zero on their shortest decimal form, so 2.675 shows as 2.68.
+*/
+
+// Caps the digits a pattern can ask for, since each one is allocated on every call
+const MAX_PATTERN_DIGITS: usize = 100;
+
+// A literal or a run of one pattern letter in a date pattern
+enum DateToken {
+    Text(String),
+    Field(char, usize)
+}
+
+struct NumberPattern {
+    prefix: String,
+    suffix: String,
+    min_int: usize,
+    // Zero when there are no separators
+    grouping: usize,
+    min_frac: usize,
+    max_frac: usize,
+    // Digits in the exponent, when in scientific notation
+    exponent_digits: Option<usize>,
+    exponent_sign: bool,
+    percent: bool
+}
+
+// Reads up to the closing quote. Two quotes in a row are a literal quote.
+fn read_quoted(chars: &mut Peekable<Chars>) -> Result<String, u64> {
+    let mut text = String::new();
+    loop {
+        match chars.next() {
+            Some('\'') => {
+                if chars.peek() == Some(&'\'') {
+                    chars.next();
+                    text.push('\'');
+                } else {
+                    return Ok(text)
+                }
+            },
+            Some(ch) => text.push(ch),
+            None => return Err(RUNTIME_ERR_INVALID_ARG)
+        }
+    }
+}
+
+fn parse_number_section(section: &str, locale: &Locale) -> Result<NumberPattern, u64> {
+    let mut prefix = String::new();
+    let mut suffix = String::new();
+    let mut digits = String::new();
+    let mut exponent_digits: Option<usize> = None;
+    let mut exponent_sign = false;
+    let mut percent = false;
+    let mut chars = section.chars().peekable();
+    while let Some(ch) = chars.next() {
+        let in_number = !digits.is_empty() && suffix.is_empty() && exponent_digits.is_none();
+        let literal: String = match ch {
+            '#' | '0' if suffix.is_empty() && exponent_digits.is_none() => {
+                digits.push(ch);
+                continue
+            },
+            ',' | '.' if in_number => {
+                digits.push(ch);
+                continue
+            },
+            // A pattern can start at the decimal point. i.e. ".00"
+            '.' if digits.is_empty() && (chars.peek() == Some(&'0') || chars.peek() == Some(&'#')) => {
+                digits.push(ch);
+                continue
+            },
+            'E' if in_number => {
+                let mut lookahead = chars.clone();
+                let sign = lookahead.peek() == Some(&'+');
+                if sign {
+                    lookahead.next();
+                }
+                let mut count = 0;
+                while lookahead.peek() == Some(&'0') {
+                    lookahead.next();
+                    count += 1;
+                }
+                if count == 0 {
+                    ch.to_string()
+                } else {
+                    chars = lookahead;
+                    exponent_digits = Some(count);
+                    exponent_sign = sign;
+                    continue
+                }
+            },
+            '#' | '0' => return Err(RUNTIME_ERR_INVALID_ARG),
+            '\'' => read_quoted(&mut chars)?,
+            '\\' => chars.next().ok_or(RUNTIME_ERR_INVALID_ARG)?.to_string(),
+            '%' => {
+                percent = true;
+                locale.percent.to_string()
+            },
+            '¤' => locale.currency.to_string(),
+            _ => ch.to_string()
+        };
+        if digits.is_empty() {
+            prefix.push_str(&literal);
+        } else {
+            suffix.push_str(&literal);
+        }
+    }
+
+    let (whole, fraction) = match digits.find('.') {
+        Some(index) => (&digits[..index], &digits[index + 1..]),
+        None => (&digits[..], "")
+    };
+    if whole.is_empty() && fraction.is_empty() {
+        return Err(RUNTIME_ERR_INVALID_ARG)
+    }
+    if fraction.contains(|ch| ch == ',' || ch == '.') {
+        return Err(RUNTIME_ERR_INVALID_ARG)
+    }
+    let grouping = match whole.rfind(',') {
+        Some(index) => whole.len() - index - 1,
+        None => 0
+    };
+    if whole.contains(',') && grouping == 0 {
+        return Err(RUNTIME_ERR_INVALID_ARG)
+    }
+    let min_int = whole.matches('0').count();
+    if whole.len() > MAX_PATTERN_DIGITS || fraction.len() > MAX_PATTERN_DIGITS || exponent_digits.unwrap_or(0) > MAX_PATTERN_DIGITS {
+        return Err(RUNTIME_ERR_INVALID_ARG)
+    }
+    return Ok(NumberPattern {
+        prefix: prefix,
+        suffix: suffix,
+        // Scientific notation always shows at least one digit before the point
+        min_int: if exponent_digits.is_some() { min_int.max(1) } else { min_int },
+        grouping: grouping,
+        min_frac: fraction.matches('0').count(),
+        max_frac: fraction.len(),
+        exponent_digits: exponent_digits,
+        exponent_sign: exponent_sign,
+        percent: percent
+    })
+}
+
+// Splits on semicolons outside of quotes
+fn split_sections(pattern: &str) -> Vec<String> {
+    let mut sections = vec![String::new()];
+    let mut quoted = false;
+    let mut escaped = false;
+    for ch in pattern.chars() {
+        if ch == ';' && !quoted && !escaped {
+            sections.push(String::new());
+            continue
+        }
+        if ch == '\'' && !escaped {
+            quoted = !quoted;
+        }
+        escaped = ch == '\\' && !quoted && !escaped;
+        sections.last_mut().unwrap().push(ch);
+    }
+    return sections
+}
+
+fn pow10(exp: u32) -> BigInt {
+    return num_traits::pow(BigInt::from(10), exp as usize)
+}
+
+// value * 10^places
+fn shift(value: &Decimal, places: i64) -> Decimal {
+    let scale = value.scale as i64 - places;
+    if scale < 0 {
+        return Decimal::new(&value.mantissa * pow10((-scale) as u32), 0)
+    }
+    return Decimal::new(value.mantissa.clone(), scale as u32)
+}
+
+// Digits before the decimal point, ignoring leading zeroes
+fn integer_digits(value: &Decimal) -> i64 {
+    if value.mantissa.is_zero() {
+        return 0
+    }
+    return value.mantissa.abs().to_string().len() as i64 - value.scale as i64
+}
+
+fn group_digits(digits: &str, size: usize, separator: &str) -> String {
+    if size == 0 || digits.len() <= size {
+        return digits.to_string()
+    }
+    let mut grouped = String::new();
+    for (index, ch) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index) % size == 0 {
+            grouped.push_str(separator);
+        }
+        grouped.push(ch);
+    }
+    return grouped
+}
+
+// Formats a non-negative value. Also returns whether it rounded to zero.
+fn render_number(pattern: &NumberPattern, value: &Decimal, locale: &Locale) -> (String, bool) {
+    let mut value = value.clone();
+    if pattern.percent {
+        value = shift(&value, 2);
+    }
+    let mut exponent: i64 = 0;
+    if pattern.exponent_digits.is_some() && !value.mantissa.is_zero() {
+        exponent = integer_digits(&value) - pattern.min_int as i64;
+    }
+    let mut rounded = shift(&value, -exponent).rescale(pattern.max_frac as u32, RoundingMode::HalfUp);
+    // Rounding up can carry into another digit. i.e. 9.99 as 0.0E+0
+    if pattern.exponent_digits.is_some() && integer_digits(&rounded) > pattern.min_int as i64 {
+        exponent += 1;
+        rounded = shift(&value, -exponent).rescale(pattern.max_frac as u32, RoundingMode::HalfUp);
+    }
+    let rounded = rounded.trim(pattern.min_frac as u32);
+    let is_zero = rounded.mantissa.is_zero();
+
+    let mut digits = rounded.mantissa.to_string();
+    let scale = rounded.scale as usize;
+    if digits.len() <= scale {
+        digits = format!("{}{}", "0".repeat(scale + 1 - digits.len()), digits);
+    }
+    let (whole, fraction) = digits.split_at(digits.len() - scale);
+    let mut whole = whole.trim_start_matches('0').to_string();
+    if whole.len() < pattern.min_int {
+        whole = format!("{}{}", "0".repeat(pattern.min_int - whole.len()), whole);
+    }
+    // A pattern made only of # still shows something for zero
+    if whole.is_empty() && fraction.is_empty() {
+        whole = String::from("0");
+    }
+
+    let mut text = pattern.prefix.clone();
+    text.push_str(&group_digits(&whole, pattern.grouping, locale.group));
+    if !fraction.is_empty() {
+        text.push_str(locale.decimal);
+        text.push_str(fraction);
+    }
+    if let Some(exponent_digits) = pattern.exponent_digits {
+        let sign = if exponent < 0 { "-" } else if pattern.exponent_sign { "+" } else { "" };
+        text.push_str(&format!("E{}{:0width$}", sign, exponent.abs(), width = exponent_digits));
+    }
+    text.push_str(&pattern.suffix);
+    return (text, is_zero)
+}
+
+pub fn format_number(value: &Decimal, pattern: &str, locale: &Locale) -> Result<String, u64> {
+    let sections = split_sections(pattern);
+    if sections.len() > 2 {
+        return Err(RUNTIME_ERR_INVALID_ARG)
+    }
+    let positive = parse_number_section(&sections[0], locale)?;
+    let is_negative = value.mantissa.is_negative();
+    let magnitude = Decimal::new(value.mantissa.abs(), value.scale);
+    if is_negative && sections.len() == 2 {
+        let negative = parse_number_section(&sections[1], locale)?;
+        return Ok(render_number(&negative, &magnitude, locale).0)
+    }
+    let (text, is_zero) = render_number(&positive, &magnitude, locale);
+    // No sign on values which round to zero, so -0.001 shows as 0.00
+    if is_negative && !is_zero {
+        return Ok(format!("-{}", text))
+    }
+    return Ok(text)
+}
+
+fn tokenize_date(pattern: &str) -> Result<Vec<DateToken>, u64> {
+    let mut tokens: Vec<DateToken> = Vec::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch.is_ascii_alphabetic() {
+            let mut count = 1;
+            while chars.peek() == Some(&ch) {
+                chars.next();
+                count += 1;
+            }
+            tokens.push(DateToken::Field(ch, count));
+        } else if ch == '\'' {
+            tokens.push(DateToken::Text(read_quoted(&mut chars)?));
+        } else {
+            tokens.push(DateToken::Text(ch.to_string()));
+        }
+    }
+    return Ok(tokens)
+}
+
+fn padded(value: i64, width: usize) -> String {
+    return format!("{:0width$}", value, width = width)
+}
+
+pub fn format_date(datetime: &DateTime<FixedOffset>, pattern: &str, locale: &Locale) -> Result<String, u64> {
+    let mut text = String::new();
+    for token in tokenize_date(pattern)? {
+        let (field, count) = match token {
+            DateToken::Text(literal) => {
+                text.push_str(&literal);
+                continue
+            },
+            DateToken::Field(field, count) => (field, count)
+        };
+        let month = datetime.month0() as usize;
+        let weekday = datetime.weekday().num_days_from_monday() as usize;
+        let hour_12 = match datetime.hour() % 12 { 0 => 12, hour => hour };
+        let part: String = match (field, count) {
+            ('y', 2) => padded(datetime.year().rem_euclid(100) as i64, 2),
+            ('y', _) => padded(datetime.year() as i64, count),
+            ('M', 1) | ('M', 2) => padded(datetime.month() as i64, count),
+            ('M', 3) => locale.months_short[month].to_string(),
+            ('M', _) => locale.months[month].to_string(),
+            ('d', 1) | ('d', 2) => padded(datetime.day() as i64, count),
+            ('D', 1..=3) => padded(datetime.ordinal() as i64, count),
+            ('E', 1..=3) => locale.weekdays_short[weekday].to_string(),
+            ('E', _) => locale.weekdays[weekday].to_string(),
+            ('H', 1) | ('H', 2) => padded(datetime.hour() as i64, count),
+            ('h', 1) | ('h', 2) => padded(hour_12 as i64, count),
+            ('m', 1) | ('m', 2) => padded(datetime.minute() as i64, count),
+            ('s', 1) | ('s', 2) => padded(datetime.second() as i64, count),
+            // Fractions of a second, truncated to the requested digits
+            ('S', 1..=9) => padded(datetime.nanosecond() as i64 % 1_000_000_000, 9)[..count].to_string(),
+            ('a', 1..=3) => locale.day_periods[(datetime.hour() >= 12) as usize].to_string(),
+            // UTC offset. i.e. +0530, or +05:30 with five letters
+            ('Z', 1..=3) | ('Z', 5) => {
+                let minutes = datetime.offset().local_minus_utc() / 60;
+                let sign = if minutes < 0 { '-' } else { '+' };
+                let separator = if count == 5 { ":" } else { "" };
+                format!("{}{:02}{}{:02}", sign, minutes.abs() / 60, separator, minutes.abs() % 60)
+            },
+            // Letters are reserved for fields, so typos fail rather than printing as text
+            _ => return Err(RUNTIME_ERR_INVALID_ARG)
+        };
+        text.push_str(&part);
+    }
+    return Ok(text)
+}
+
+fn format_value(env: &mut Environment, args: &[u64]) -> Result<u64, u64> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(RUNTIME_ERR_FN_ARITY)
+    }
+    let value = args[0];
+    if is_error(value) {
+        return Err(value)
+    }
+    let pattern = text_arg(env, args[1])?;
+    let locale = if args.len() == 3 {
+        find_locale(&text_arg(env, args[2])?).ok_or(RUNTIME_ERR_INVALID_ARG)?
+    } else {
+        DEFAULT_LOCALE
+    };
+    let number = f64::from_bits(value);
+    let text = if !is_nan(number) {
+        let decimal = Decimal::from_f64(number).ok_or(RUNTIME_ERR_INVALID_ARG)?;
+        format_number(&decimal, &pattern, locale)?
+    } else {
+        match resolve_atom!(env, value) {
+            Atom::DecimalValue(decimal) => format_number(&decimal, &pattern, locale)?,
+            Atom::BigIntValue(big) => format_number(&Decimal::from_int(big), &pattern, locale)?,
+            Atom::DateValue(date) => format_date(&date_to_datetime(date), &pattern, locale)?,
+            Atom::DateTimeValue(datetime) => format_date(&datetime, &pattern, locale)?,
+            _ => return Err(RUNTIME_ERR_INVALID_TYPE)
+        }
+    };
+    return Ok(env.init_string(text))
+}
+
+// format(value, pattern) or format(value, pattern, locale). Formats numbers and dates as text.
+// The locale defaults to "en".
+pub fn __av_format(env: &mut Environment, args: &[u64]) -> u64 {
+    match format_value(env, args) {
+        Ok(text) => text,
+        Err(err) => err
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporal::parse_datetime;
+
+    fn number(value: &str, pattern: &str, code: &str) -> String {
+        let locale = find_locale(code).unwrap();
+        return format_number(&Decimal::parse(value).unwrap(), pattern, locale).unwrap()
+    }
+
+    fn date(value: &str, pattern: &str, code: &str) -> String {
+        let locale = find_locale(code).unwrap();
+        return format_date(&parse_datetime(value).unwrap(), pattern, locale).unwrap()
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(number("1234567.891", "#,##0.00", "en"), "1,234,567.89");
+        assert_eq!(number("1234567.891", "#,##0.00", "de"), "1.234.567,89");
+        assert_eq!(number("1234567.891", "#,##0.00", "fr"), "1\u{202F}234\u{202F}567,89");
+        assert_eq!(number("2.675", "0.00", "en"), "2.68");
+        assert_eq!(number("0.5", "#.##", "en"), ".5");
+        assert_eq!(number("7", "000", "en"), "007");
+        assert_eq!(number("1.5", "0.0##", "en"), "1.5");
+        assert_eq!(number("1.23456", "0.0##", "en"), "1.235");
+        assert_eq!(number("0.256", "0.0%", "en"), "25.6%");
+        assert_eq!(number("1234.5", "¤#,##0.00", "en"), "$1,234.50");
+        assert_eq!(number("1234.5", "#,##0.00 ¤", "de"), "1.234,50 €");
+        assert_eq!(number("12345", "0.00E+00", "en"), "1.23E+04");
+        assert_eq!(number("0.000123", "0.0E0", "en"), "1.2E-4");
+        assert_eq!(number("9.99", "0.0E0", "en"), "1.0E1");
+        assert_eq!(number("0", "#", "en"), "0");
+        assert_eq!(number("0.25", ".00", "en"), ".25");
+    }
+
+    #[test]
+    fn test_negatives() {
+        assert_eq!(number("-1234.5", "#,##0.00", "en"), "-1,234.50");
+        assert_eq!(number("-1234.5", "#,##0.00;(#,##0.00)", "en"), "(1,234.50)");
+        assert_eq!(number("-0.001", "0.00", "en"), "0.00");
+        // Quoted text, including semicolons, is literal
+        assert_eq!(number("3", "0 'items;#'", "en"), "3 items;#");
+        assert_eq!(number("3", "0 'o''clock'", "en"), "3 o'clock");
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        let locale = DEFAULT_LOCALE;
+        let value = Decimal::parse("1").unwrap();
+        assert_eq!(format_number(&value, "items", locale), Err(RUNTIME_ERR_INVALID_ARG));
+        assert_eq!(format_number(&value, "0.0.0", locale), Err(RUNTIME_ERR_INVALID_ARG));
+        assert_eq!(format_number(&value, "#,", locale), Err(RUNTIME_ERR_INVALID_ARG));
+        assert_eq!(format_number(&value, "0;0;0", locale), Err(RUNTIME_ERR_INVALID_ARG));
+        assert_eq!(format_number(&value, "'unclosed 0", locale), Err(RUNTIME_ERR_INVALID_ARG));
+        let datetime = parse_datetime("2020-01-31").unwrap();
+        assert_eq!(format_date(&datetime, "yyyy-MM-dd Q", locale), Err(RUNTIME_ERR_INVALID_ARG));
+    }
+
+    #[test]
+    fn test_dates() {
+        let morning = "2020-01-31T09:05:07.25+05:30";
+        assert_eq!(date(morning, "yyyy-MM-dd HH:mm:ss.SS", "en"), "2020-01-31 09:05:07.25");
+        assert_eq!(date(morning, "EEEE, MMMM d, yyyy", "en"), "Friday, January 31, 2020");
+        assert_eq!(date(morning, "EEEE d MMMM yyyy", "de"), "Freitag 31 Januar 2020");
+        assert_eq!(date(morning, "EEE d MMM yy", "fr"), "ven. 31 janv. 20");
+        assert_eq!(date(morning, "yyyy年M月d日(E)", "ja"), "2020年1月31日(金)");
+        assert_eq!(date("2020-01-31T21:30:00Z", "h:mm a", "en"), "9:30 PM");
+        assert_eq!(date(morning, "'Q1' yyyy ZZZZZ", "en"), "Q1 2020 +05:30");
+        assert_eq!(date(morning, "D", "en"), "31");
+    }
+
+    #[test]
+    fn test_locales() {
+        assert_eq!(find_locale("de").unwrap().code, "de");
+        assert_eq!(find_locale("EN_gb").unwrap().code, "en-GB");
+        assert_eq!(find_locale("de-AT").unwrap().code, "de");
+        assert!(find_locale("xx").is_none());
+    }
+
+    #[test]
+    fn test_format_builtin() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let pattern = env.init_string(String::from("#,##0.00"));
+        let formatted = __av_format(&mut env, &[(1234.5f64).to_bits(), pattern]);
+        assert_eq!(&*text_arg(&env, formatted).unwrap(), "1,234.50");
+
+        let locale = env.init_string(String::from("xx"));
+        assert_eq!(__av_format(&mut env, &[(1.0f64).to_bits(), pattern, locale]), RUNTIME_ERR_INVALID_ARG);
+        assert_eq!(__av_format(&mut env, &[(1.0f64).to_bits()]), RUNTIME_ERR_FN_ARITY);
+        assert_eq!(__av_format(&mut env, &[pattern, pattern]), RUNTIME_ERR_INVALID_TYPE);
+    }
+}
@@ -2,6 +2,12 @@ use crate::environment::Environment;
 use crate::structs::Atom;
 use crate::constants::*;
 use crate::types::*;
+use crate::utils::known_str_len;
+use crate::decimal::{RoundingMode, MAX_SCALE};
+use crate::numeric::{resolve_decimal, init_number, Number};
+use crate::temporal::{parse_date, parse_datetime, date_to_datetime};
+use chrono::Duration;
+use std::rc::Rc;
 
 
 #[derive(Clone)]
@@ -9,8 +15,10 @@ pub enum NativeFn {
     Fn1(NativeFn1),
     Fn2(NativeFn2),
     Fn3(NativeFn3),
-    // Fn4(NativeFn4),
-    // Fn5(NativeFn5),
+    // Variable number of arguments. The function checks the count itself.
+    FnN(NativeFnN),
+    // Registered by the host application. See registry.rs
+    Host(HostFn),
 }
 
 impl PartialEq for NativeFn {
@@ -55,6 +63,82 @@ pub struct NativeFn3 {
     pub func: fn(&mut Environment, u64, u64, u64) -> u64
 }
 
+impl NativeFn3 {
+    pub const fn create_atom(func: fn(&mut Environment, u64, u64, u64) -> u64) -> Atom {
+        return Atom::FunctionValue(NativeFn::Fn3(NativeFn3 {
+            func: func
+        }))
+    }
+}
+
+#[derive(Clone)]
+pub struct NativeFnN {
+    pub func: fn(&mut Environment, &[u64]) -> u64
+}
+
+impl NativeFnN {
+    pub const fn create_atom(func: fn(&mut Environment, &[u64]) -> u64) -> Atom {
+        return Atom::FunctionValue(NativeFn::FnN(NativeFnN {
+            func: func
+        }))
+    }
+}
+
+#[derive(Clone)]
+pub struct HostFn {
+    // Shared, since closures can't be copied like fn pointers
+    pub func: Rc<dyn Fn(&mut Environment, &[u64]) -> u64>,
+    pub min_args: usize,
+    pub max_args: usize,
+    pub pure: bool
+}
+
+// Results are kept for the whole request, so bound how many are remembered.
+pub const MAX_HOST_CACHE_ENTRIES: usize = 4096;
+
+// Only numbers and values encoded in the symbol itself can be cached.
+// Pointers are reused once the values they refer to are collected.
+fn is_immediate(value: u64) -> bool {
+    return is_number(value) || is_symbol(value)
+}
+
+pub fn call_host(env: &mut Environment, func_symbol: u64, host: &HostFn, args: &[u64]) -> u64 {
+    let cacheable = host.pure && args.iter().all(|arg| is_immediate(*arg));
+    let key = (func_symbol, args.to_vec());
+    if cacheable {
+        if let Some(result) = env.host_cache.get(&key) {
+            return *result
+        }
+    }
+    let result = (host.func)(env, args);
+    // Errors may come from the budget rather than the arguments, so they're never cached
+    if cacheable && is_immediate(result) && !is_error(result) && env.host_cache.len() < MAX_HOST_CACHE_ENTRIES {
+        env.host_cache.insert(key, result);
+    }
+    return result
+}
+
+// Calls a function value after checking the argument count. i.e. the key passed to sort_by
+pub fn call_native(env: &mut Environment, func_symbol: u64, func: &NativeFn, args: &[u64]) -> u64 {
+    let (min_count, max_count) = match func {
+        NativeFn::Fn1(_) => (1, 1),
+        NativeFn::Fn2(_) => (2, 2),
+        NativeFn::Fn3(_) => (3, 3),
+        NativeFn::FnN(_) => (0, usize::MAX),
+        NativeFn::Host(host) => (host.min_args, host.max_args)
+    };
+    if args.len() < min_count || args.len() > max_count {
+        return RUNTIME_ERR_FN_ARITY
+    }
+    match func {
+        NativeFn::Fn1(f1) => (f1.func)(env, args[0]),
+        NativeFn::Fn2(f2) => (f2.func)(env, args[0], args[1]),
+        NativeFn::Fn3(f3) => (f3.func)(env, args[0], args[1], args[2]),
+        NativeFn::FnN(f_n) => (f_n.func)(env, args),
+        NativeFn::Host(host) => call_host(env, func_symbol, host, args)
+    }
+}
+
 
 impl Callable for NativeFn1 {
     fn call(&self, mut env: &mut Environment, args: Vec<u64>) -> u64 {
@@ -77,20 +161,6 @@ impl Callable for NativeFn3 {
     }
 }
 
-pub fn __av_min(_env: &mut Environment, a: u64, b: u64) -> u64 {
-    let f_a: f64 = valid_num!(a);
-	let f_b: f64 = valid_num!(b);
-
-    return f_a.min(f_b).to_bits();
-}
-
-pub fn __av_max(_env: &mut Environment, a: u64, b: u64) -> u64 {
-    let f_a: f64 = valid_num!(a);
-	let f_b: f64 = valid_num!(b);
-
-    return f_a.max(f_b).to_bits();
-}
-
 pub fn __av_abs(_env: &mut Environment, a: u64) -> u64 {
     let f_a: f64 = valid_num!(a);
     return f_a.abs().to_bits();
@@ -111,12 +181,134 @@ pub fn __av_truncate(_env: &mut Environment, a: u64) -> u64 {
     return f_a.trunc().to_bits();
 }
 
-pub fn __av_round(_env: &mut Environment, a: u64) -> u64 {
-    let f_a: f64 = valid_num!(a);
-    return f_a.round().to_bits();
+pub fn __av_default(_env: &mut Environment, a: u64, fallback: u64) -> u64 {
+    // Spreadsheet style IFERROR. Recover from an upstream error with a fallback value.
+    if is_error(a) {
+        return fallback
+    }
+    return a
 }
 
-pub fn __av_sqrt(_env: &mut Environment, a: u64) -> u64 {
-    let f_a: f64 = valid_num!(a);
-    return f_a.sqrt().to_bits();
+pub fn __av_is_error(_env: &mut Environment, a: u64) -> u64 {
+    return __repr_bool(is_error(a));
+}
+
+pub fn __av_len(env: &mut Environment, a: u64) -> u64 {
+    if is_error(a) {
+        return a
+    }
+    // Inline strings and short string pointers know their own length.
+    if let Some(length) = known_str_len(a) {
+        return (length as f64).to_bits();
+    }
+    match resolve_atom!(env, a) {
+        Atom::StringValue(value) => {
+            return (value.chars().count() as f64).to_bits();
+        },
+        Atom::ListValue(list) => {
+            return (list.len() as f64).to_bits();
+        },
+        Atom::MapValue(map) => {
+            return (map.len() as f64).to_bits();
+        },
+        _ => {
+            return RUNTIME_ERR_EXPECTED_STR
+        }
+    }
+}
+
+fn round_to_decimal(env: &mut Environment, value: u64, scale: u64, mode: RoundingMode) -> u64 {
+    let f_scale: f64 = valid_num!(scale);
+    if f_scale.fract() != 0.0 || f_scale < 0.0 || f_scale > MAX_SCALE as f64 {
+        return RUNTIME_ERR_INVALID_ARG
+    }
+    match resolve_decimal(env, value) {
+        Ok(decimal) => {
+            let rounded = decimal.rescale(f_scale as u32, mode);
+            return init_number(env, Number::Decimal(rounded))
+        },
+        Err(err) => return err
+    }
+}
+
+// decimal(value, places). Rounds half to even, so repeated rounding doesn't drift upwards.
+pub fn __av_decimal(env: &mut Environment, value: u64, scale: u64) -> u64 {
+    return round_to_decimal(env, value, scale, RoundingMode::HalfEven)
+}
+
+// decimal_round(value, places, "half_up")
+pub fn __av_decimal_round(env: &mut Environment, value: u64, scale: u64, mode: u64) -> u64 {
+    if is_error(mode) {
+        return mode
+    }
+    let rounding_mode = match resolve_atom!(env, mode) {
+        Atom::StringValue(name) => RoundingMode::from_name(&name),
+        _ => return RUNTIME_ERR_EXPECTED_STR
+    };
+    match rounding_mode {
+        Some(rounding_mode) => round_to_decimal(env, value, scale, rounding_mode),
+        None => RUNTIME_ERR_INVALID_ARG
+    }
+}
+
+// date("2020-03-01")
+pub fn __av_date(env: &mut Environment, a: u64) -> u64 {
+    if is_error(a) {
+        return a
+    }
+    match resolve_atom!(env, a) {
+        Atom::StringValue(text) => {
+            match parse_date(&text) {
+                Some(date) => env.init_value(Atom::DateValue(date)),
+                None => RUNTIME_ERR_INVALID_ARG
+            }
+        },
+        Atom::DateTimeValue(datetime) => env.init_value(Atom::DateValue(datetime.date_naive())),
+        Atom::DateValue(_) => a,
+        _ => RUNTIME_ERR_EXPECTED_STR
+    }
+}
+
+// datetime("2020-03-01T09:30:00+05:30")
+pub fn __av_datetime(env: &mut Environment, a: u64) -> u64 {
+    if is_error(a) {
+        return a
+    }
+    match resolve_atom!(env, a) {
+        Atom::StringValue(text) => {
+            match parse_datetime(&text) {
+                Some(datetime) => env.init_value(Atom::DateTimeValue(datetime)),
+                None => RUNTIME_ERR_INVALID_ARG
+            }
+        },
+        Atom::DateValue(date) => env.init_value(Atom::DateTimeValue(date_to_datetime(date))),
+        Atom::DateTimeValue(_) => a,
+        _ => RUNTIME_ERR_EXPECTED_STR
+    }
+}
+
+fn init_duration(env: &mut Environment, count: u64, unit_seconds: f64) -> u64 {
+    let f_count: f64 = valid_num!(count);
+    let millis = f_count * unit_seconds * 1000.0;
+    // Limit to roughly +/- 100 million days
+    if !millis.is_finite() || millis.abs() > 8.64e18 {
+        return RUNTIME_ERR_INVALID_ARG
+    }
+    return env.init_value(Atom::DurationValue(Duration::milliseconds(millis.round() as i64)))
+}
+
+pub fn __av_days(env: &mut Environment, a: u64) -> u64 {
+    return init_duration(env, a, 86400.0)
+}
+
+pub fn __av_hours(env: &mut Environment, a: u64) -> u64 {
+    return init_duration(env, a, 3600.0)
+}
+
+pub fn __av_minutes(env: &mut Environment, a: u64) -> u64 {
+    return init_duration(env, a, 60.0)
+}
+
+pub fn __av_seconds(env: &mut Environment, a: u64) -> u64 {
+    return init_duration(env, a, 1.0)
 }
@@ -0,0 +1,162 @@
+use crate::environment::Environment;
+use crate::structs::{Atom, Identifier};
+use crate::heap::{is_heap_pointer, ATTRIBUTE_MASK};
+use fnv::FnvHashSet;
+
+/*
+Mark and sweep collection of environment values.
+Evaluation allocates intermediate values (i.e. each string concatenation), which become
+unreachable once a cell's result is bound. The roots are named identifiers (cells, builtins),
+cell results and any values on a live stack, which callers pass in.
+Values can only reference older values, so this can later move to the linear, generational
+scheme in "06 - Garbage Collection" by only sweeping symbols allocated since the last collection.
+Heap objects are traced for the values they reference, but aren't reclaimed yet.
+*/
+
+// Allocations between automatic collections
+pub const DEFAULT_GC_THRESHOLD: u64 = 4096;
+
+#[derive(Debug)]
+pub struct GcState {
+    // Allocations since the last collection
+    pub allocated: u64,
+    pub threshold: u64,
+    pub collections: u64
+}
+
+impl GcState {
+    pub fn new() -> GcState {
+        return GcState {
+            allocated: 0,
+            threshold: DEFAULT_GC_THRESHOLD,
+            collections: 0
+        }
+    }
+
+    pub fn should_collect(&self) -> bool {
+        return self.allocated >= self.threshold
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct GcStats {
+    pub live: usize,
+    pub freed: usize
+}
+
+fn mark(env: &Environment, roots: &[u64]) -> FnvHashSet<u64> {
+    let mut marked: FnvHashSet<u64> = FnvHashSet::default();
+    let mut marked_objects: FnvHashSet<u64> = FnvHashSet::default();
+    let mut worklist: Vec<u64> = roots.to_vec();
+    for symbol in env.identifiers.symbols() {
+        if let Some(Identifier { name: Some(_), .. }) = env.identifiers.get(symbol) {
+            worklist.push(symbol);
+        }
+    }
+    for expr in env.body.iter() {
+        worklist.push(expr.symbol);
+        if let Some(result) = expr.result {
+            worklist.push(result);
+        }
+    }
+
+    while let Some(value) = worklist.pop() {
+        if is_heap_pointer(value) {
+            // Any attribute pointer keeps the whole object alive
+            if marked_objects.insert(value & !ATTRIBUTE_MASK) {
+                if let Some(values) = env.heap.values(value) {
+                    worklist.extend_from_slice(values);
+                }
+            }
+            continue;
+        }
+        if marked.contains(&value) {
+            continue;
+        }
+        if let Some(identifier) = env.identifiers.get(value) {
+            marked.insert(value);
+            match &identifier.value {
+                Some(Atom::SymbolValue(next)) => worklist.push(*next),
+                Some(Atom::ListValue(list)) => worklist.extend(list.iter().cloned()),
+                Some(Atom::MapValue(map)) => worklist.extend(map.values().cloned()),
+                Some(Atom::ObjectValue(obj)) => {
+                    worklist.push(obj.av_class);
+                    if let Some(values) = &obj.av_values {
+                        worklist.extend_from_slice(values);
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+    return marked
+}
+
+// Free every environment value which isn't reachable from the roots.
+pub fn collect_garbage(env: &mut Environment, roots: &[u64]) -> GcStats {
+    let marked = mark(env, roots);
+    let before = env.identifiers.len();
+    env.identifiers.retain(&mut |symbol, _| marked.contains(&symbol));
+    env.gc.allocated = 0;
+    env.gc.collections += 1;
+    return GcStats {
+        live: env.identifiers.len(),
+        freed: before - env.identifiers.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+    use crate::objects::{declare_class, construct};
+    use crate::expression::Expression;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_collect_unreachable() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let named = env.define_identifier();
+        env.bind_name(named, String::from("total"));
+        let kept = env.init_string(String::from("Referenced by name"));
+        env.bind_value(named, Atom::SymbolValue(kept));
+        let garbage = env.init_string(String::from("Intermediate value"));
+        let on_stack = env.init_string(String::from("Still on the stack"));
+        assert_eq!(env.gc.allocated, 3);
+
+        let stats = collect_garbage(&mut env, &[on_stack]);
+        assert_eq!(stats, GcStats { live: 3, freed: 1 });
+        assert!(env.lookup(garbage).is_none());
+        assert!(env.lookup(kept).is_some());
+        assert!(env.lookup(on_stack).is_some());
+        assert_eq!(env.gc.allocated, 0);
+
+        // Once it's off the stack, it can be collected
+        assert_eq!(collect_garbage(&mut env, &[]).freed, 1);
+        assert_eq!(env.gc.collections, 2);
+    }
+
+    #[test]
+    fn test_collect_objects() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let class_symbol = env.define_identifier();
+        let mut cell = Expression::new(1, String::from(""));
+        cell.symbol = class_symbol;
+        env.body = Rc::new(vec![cell]);
+        // Class field names and instance fields are reachable through the class and instance
+        let class = declare_class(&mut env, class_symbol, &[String::from("description")]);
+        let field_value = env.init_string(String::from("A long field value"));
+        let instance = construct(&mut env, class, vec![field_value]);
+        let result_cell = env.define_identifier();
+        env.bind_name(result_cell, String::from("item"));
+        env.bind_value(result_cell, Atom::SymbolValue(instance));
+        env.init_string(String::from("Unreferenced string"));
+
+        let stats = collect_garbage(&mut env, &[]);
+        assert_eq!(stats.freed, 1);
+        assert!(env.lookup(field_value).is_some());
+        assert!(env.lookup(env.heap.get(class, 2).unwrap()).is_some());
+        assert_eq!(env.heap.values(instance), Some(&[field_value][..]));
+    }
+}
@@ -0,0 +1,259 @@
+use crate::constants::*;
+use fnv::FnvHashMap;
+
+/*
+Region based heap. See "06 - Garbage Collection" and "08 - Value representation".
+Objects are allocated linearly into per-class regions, without any per-object header.
+The class and GC metadata lives in the region header instead.
+Pointers encode where to find the object, so objects can later be compacted without rewriting them.
+| Header (16) | Region (16) | Object index (27) | Attribute index (5) |
+Region 0 is never allocated, so heap pointers can't be confused with environment symbols.
+Record classes and instances live here. Other environment values are still Atoms in the identifier table.
+TODO: Move those here too. Strings, lists, maps and big numbers first need the variable-length
+array pointers described in post 08, and native functions need a handle rather than a closure.
+*/
+
+// Minimum region size. 1 WASM page.
+pub const REGION_BYTES: usize = 65536;
+pub const REGION_WORDS: usize = REGION_BYTES / 8;
+
+pub const REGION_SHIFT: u64 = 32;
+pub const REGION_MASK: u64 = 0x0000_FFFF_0000_0000;
+pub const OBJECT_SHIFT: u64 = 5;
+pub const OBJECT_MASK: u64 = 0x07FF_FFFF;
+pub const ATTRIBUTE_MASK: u64 = 0x1F;
+
+// Attribute 0 refers to the object as a whole and 31 is reserved for larger objects.
+pub const ATTRIBUTE_SELF: u64 = 0;
+pub const MAX_ATTRIBUTES: usize = 30;
+
+pub fn heap_pointer(region: u16, object: u64, attribute: u64) -> u64 {
+    return VALUE_T_PTR_OBJ | ((region as u64) << REGION_SHIFT) |
+        ((object & OBJECT_MASK) << OBJECT_SHIFT) | (attribute & ATTRIBUTE_MASK)
+}
+
+pub fn is_heap_pointer(value: u64) -> bool {
+    return (value & VALHEAD_MASK) == VALUE_T_PTR_OBJ && (value & REGION_MASK) != 0
+}
+
+pub fn pointer_region(pointer: u64) -> u16 {
+    return ((pointer & REGION_MASK) >> REGION_SHIFT) as u16
+}
+
+pub fn pointer_object(pointer: u64) -> u64 {
+    return (pointer >> OBJECT_SHIFT) & OBJECT_MASK
+}
+
+pub fn pointer_attribute(pointer: u64) -> u64 {
+    return pointer & ATTRIBUTE_MASK
+}
+
+// Point to an attribute within the same object. i.e. d.year
+pub fn attribute_pointer(pointer: u64, attribute: u64) -> u64 {
+    return (pointer & !ATTRIBUTE_MASK) | (attribute & ATTRIBUTE_MASK)
+}
+
+#[derive(Debug)]
+pub struct RegionHeader {
+    pub av_class: u64,
+    // The class's previous region. Regions for a class form a linked list from newest to oldest.
+    pub previous: Option<u16>,
+    // Object IDs are sequential within a class, so the first ID locates the rest.
+    pub first_object: u64,
+    // Every object of a class has the same size
+    pub object_words: usize,
+    pub len: usize
+}
+
+#[derive(Debug)]
+pub struct Region {
+    pub header: RegionHeader,
+    words: Vec<u64>
+}
+
+impl Region {
+    fn new(av_class: u64, previous: Option<u16>, first_object: u64, object_words: usize) -> Region {
+        return Region {
+            header: RegionHeader {
+                av_class: av_class,
+                previous: previous,
+                first_object: first_object,
+                object_words: object_words,
+                len: 0
+            },
+            words: Vec::with_capacity(REGION_WORDS)
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        // Empty objects still take an object ID
+        return REGION_WORDS / self.header.object_words.max(1)
+    }
+
+    pub fn is_full(&self) -> bool {
+        return self.header.len >= self.capacity()
+    }
+
+    fn object(&self, object: u64) -> Option<&[u64]> {
+        if object < self.header.first_object {
+            return None
+        }
+        let slot = (object - self.header.first_object) as usize;
+        if slot >= self.header.len {
+            return None
+        }
+        let start = slot * self.header.object_words;
+        return Some(&self.words[start..start + self.header.object_words])
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Heap {
+    // Region N is stored at index N - 1
+    regions: Vec<Region>,
+    // Class -> Latest region for that class
+    latest: FnvHashMap<u64, u16>,
+    // Class -> Next object ID
+    next_object: FnvHashMap<u64, u64>
+}
+
+impl Heap {
+    pub fn new() -> Heap {
+        return Heap::default()
+    }
+
+    pub fn region(&self, region: u16) -> Option<&Region> {
+        if region == 0 {
+            return None
+        }
+        return self.regions.get(region as usize - 1)
+    }
+
+    pub fn region_count(&self) -> usize {
+        return self.regions.len()
+    }
+
+    // Allocate an object with the given attribute values. Returns a pointer to it.
+    pub fn alloc(&mut self, av_class: u64, values: &[u64]) -> Result<u64, u64> {
+        if values.len() > MAX_ATTRIBUTES {
+            return Err(RUNTIME_ERR_INVALID_ARG)
+        }
+        let object = *self.next_object.get(&av_class).unwrap_or(&0);
+        if object > OBJECT_MASK {
+            return Err(INTERPRETER_ERR_LIMIT_MEMORY)
+        }
+
+        let latest = self.latest.get(&av_class).cloned();
+        let region_id = match latest {
+            Some(region_id) if !self.regions[region_id as usize - 1].is_full() => {
+                if self.regions[region_id as usize - 1].header.object_words != values.len() {
+                    return Err(RUNTIME_ERR_INVALID_ARG)
+                }
+                region_id
+            },
+            _ => {
+                // All region IDs are taken
+                if self.regions.len() >= 0xFFFF {
+                    return Err(INTERPRETER_ERR_LIMIT_MEMORY)
+                }
+                self.regions.push(Region::new(av_class, latest, object, values.len()));
+                let region_id = self.regions.len() as u16;
+                self.latest.insert(av_class, region_id);
+                region_id
+            }
+        };
+
+        let region = &mut self.regions[region_id as usize - 1];
+        region.words.extend_from_slice(values);
+        region.header.len += 1;
+        self.next_object.insert(av_class, object + 1);
+        return Ok(heap_pointer(region_id, object, ATTRIBUTE_SELF))
+    }
+
+    pub fn class_of(&self, pointer: u64) -> Option<u64> {
+        if !is_heap_pointer(pointer) {
+            return None
+        }
+        return Some(self.region(pointer_region(pointer))?.header.av_class)
+    }
+
+    // All attribute values of the object, in attribute order.
+    pub fn values(&self, pointer: u64) -> Option<&[u64]> {
+        if !is_heap_pointer(pointer) {
+            return None
+        }
+        return self.region(pointer_region(pointer))?.object(pointer_object(pointer))
+    }
+
+    // Read an attribute. Attributes are numbered from 1.
+    pub fn get(&self, pointer: u64, attribute: u64) -> Option<u64> {
+        if attribute == ATTRIBUTE_SELF {
+            return None
+        }
+        return self.values(pointer)?.get(attribute as usize - 1).cloned()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLASS_A: u64 = 0xFFFD_0000_0001_0001;
+    const CLASS_B: u64 = 0xFFFD_0000_0001_0002;
+
+    #[test]
+    fn test_pointer_layout() {
+        let pointer = heap_pointer(3, 1234, 7);
+        assert_eq!(pointer & VALHEAD_MASK, VALUE_T_PTR_OBJ);
+        assert!(is_heap_pointer(pointer));
+        assert_eq!(pointer_region(pointer), 3);
+        assert_eq!(pointer_object(pointer), 1234);
+        assert_eq!(pointer_attribute(pointer), 7);
+        assert_eq!(pointer_attribute(attribute_pointer(pointer, 2)), 2);
+        assert_eq!(pointer_object(heap_pointer(0xFFFF, OBJECT_MASK, 31)), OBJECT_MASK);
+        // Environment symbols and builtins live in region 0
+        assert!(!is_heap_pointer(0xFFFD_0000_0001_0000));
+        assert!(!is_heap_pointer(AV_FN_MIN.symbol));
+    }
+
+    #[test]
+    fn test_alloc() {
+        let mut heap = Heap::new();
+        let a1 = heap.alloc(CLASS_A, &[1, 2]).unwrap();
+        let b1 = heap.alloc(CLASS_B, &[3]).unwrap();
+        let a2 = heap.alloc(CLASS_A, &[4, 5]).unwrap();
+        // Each class gets its own region
+        assert_eq!(heap.region_count(), 2);
+        assert_eq!(pointer_region(a1), pointer_region(a2));
+        assert_ne!(pointer_region(a1), pointer_region(b1));
+        assert_eq!(pointer_object(a2), 1);
+
+        assert_eq!(heap.class_of(b1), Some(CLASS_B));
+        assert_eq!(heap.values(a2), Some(&[4, 5][..]));
+        assert_eq!(heap.get(a1, 2), Some(2));
+        assert_eq!(heap.get(a1, 3), None);
+        assert_eq!(heap.get(a1, ATTRIBUTE_SELF), None);
+        assert_eq!(heap.values(heap_pointer(9, 0, 0)), None);
+
+        assert_eq!(heap.alloc(CLASS_A, &[1]), Err(RUNTIME_ERR_INVALID_ARG));
+        assert_eq!(heap.alloc(CLASS_B, &[0; 31]), Err(RUNTIME_ERR_INVALID_ARG));
+    }
+
+    #[test]
+    fn test_region_overflow() {
+        let mut heap = Heap::new();
+        let per_region = REGION_WORDS / 4;
+        let mut last = 0;
+        for i in 0..(per_region + 1) {
+            last = heap.alloc(CLASS_A, &[i as u64, 0, 0, 0]).unwrap();
+        }
+        // The next region links back to the full one
+        assert_eq!(heap.region_count(), 2);
+        assert_eq!(pointer_region(last), 2);
+        assert_eq!(heap.region(2).unwrap().header.previous, Some(1));
+        assert_eq!(heap.region(2).unwrap().header.first_object, per_region as u64);
+        assert_eq!(heap.get(last, 1), Some(per_region as u64));
+        assert_eq!(heap.get(heap_pointer(1, 5, 0), 1), Some(5));
+    }
+}
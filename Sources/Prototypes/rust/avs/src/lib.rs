@@ -15,9 +15,31 @@ pub mod format;
 pub mod functions;
 pub mod expression;
 pub mod environment;
+pub mod limits;
+pub mod trace;
+pub mod numeric;
+pub mod decimal;
+pub mod temporal;
+pub mod objects;
+pub mod heap;
+pub mod gc;
+pub mod symbols;
+pub mod collections;
+pub mod math;
+pub mod strings;
+pub mod stats;
+pub mod finance;
+pub mod random;
+pub mod text;
+pub mod units;
+pub mod locale;
+pub mod formatting;
+pub mod sorting;
 
 #[cfg(not(target_os = "unknown"))]
 pub mod runtime;
+#[cfg(not(target_os = "unknown"))]
+pub mod registry;
 
 #[macro_use]
 extern crate lazy_static;
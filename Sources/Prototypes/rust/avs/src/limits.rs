@@ -0,0 +1,154 @@
+use crate::constants::*;
+use std::time::{Duration, Instant};
+
+// Max number of pointer hops when resolving a symbol to a value.
+pub const DEFAULT_MAX_DEPTH: u32 = 1000;
+
+// Checking the clock on every step is relatively expensive, so only check it periodically.
+const TIMEOUT_CHECK_INTERVAL: u64 = 256;
+
+// Iterative solvers (i.e. IRR) stop once successive guesses are this close.
+pub const DEFAULT_SOLVER_TOLERANCE: f64 = 1e-10;
+pub const DEFAULT_SOLVER_ITERATIONS: u32 = 100;
+
+// Resource limits for evaluating a cell. None = unlimited.
+// These are configured per request and applied to each cell individually,
+// so a single runaway cell errors out without starving the rest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EvalLimits {
+    pub max_steps: Option<u64>,
+    pub max_depth: u32,
+    pub max_allocations: Option<u64>,
+    // Only checked every TIMEOUT_CHECK_INTERVAL steps, so a cell may run slightly past it.
+    pub timeout: Option<Duration>,
+    // Precision and give-up point for iterative solvers, rather than unbounded resources.
+    pub solver_tolerance: f64,
+    pub solver_iterations: u32
+}
+
+impl Default for EvalLimits {
+    fn default() -> EvalLimits {
+        return EvalLimits {
+            max_steps: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_allocations: None,
+            timeout: None,
+            solver_tolerance: DEFAULT_SOLVER_TOLERANCE,
+            solver_iterations: DEFAULT_SOLVER_ITERATIONS
+        }
+    }
+}
+
+// Usage counters for the cell currently being evaluated.
+#[derive(Debug)]
+pub struct EvalBudget {
+    pub limits: EvalLimits,
+    pub steps: u64,
+    pub allocations: u64,
+    // Not limited. Tracked for evaluation statistics.
+    pub operators: u64,
+    started: Option<Instant>
+}
+
+impl EvalBudget {
+    pub fn new(limits: EvalLimits) -> EvalBudget {
+        return EvalBudget {
+            limits: limits,
+            steps: 0,
+            allocations: 0,
+            operators: 0,
+            started: None
+        }
+    }
+
+    // Start a fresh budget for the next cell.
+    pub fn reset(&mut self) {
+        self.steps = 0;
+        self.allocations = 0;
+        self.operators = 0;
+        // Only read the clock if there's a deadline to enforce.
+        self.started = match self.limits.timeout {
+            Some(_) => Some(Instant::now()),
+            None => None
+        };
+    }
+
+    // Consume one unit of fuel. Called for each evaluated token.
+    pub fn step(&mut self) -> Result<(), u64> {
+        self.steps += 1;
+        if let Some(max_steps) = self.limits.max_steps {
+            if self.steps > max_steps {
+                return Err(INTERPRETER_ERR_LIMIT_STEPS);
+            }
+        }
+
+        if self.steps % TIMEOUT_CHECK_INTERVAL == 0 {
+            return self.check_timeout();
+        }
+        return Ok(())
+    }
+
+    pub fn allocate(&mut self) -> Result<(), u64> {
+        self.allocations += 1;
+        if let Some(max_allocations) = self.limits.max_allocations {
+            if self.allocations > max_allocations {
+                return Err(INTERPRETER_ERR_LIMIT_MEMORY);
+            }
+        }
+        return Ok(())
+    }
+
+    pub fn check_timeout(&self) -> Result<(), u64> {
+        if let (Some(timeout), Some(started)) = (self.limits.timeout, self.started) {
+            if started.elapsed() > timeout {
+                return Err(INTERPRETER_ERR_TIMEOUT);
+            }
+        }
+        return Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_limit() {
+        let mut limits = EvalLimits::default();
+        limits.max_steps = Some(2);
+        let mut budget = EvalBudget::new(limits);
+        budget.reset();
+        assert_eq!(budget.step(), Ok(()));
+        assert_eq!(budget.step(), Ok(()));
+        assert_eq!(budget.step(), Err(INTERPRETER_ERR_LIMIT_STEPS));
+
+        // Each cell gets a fresh budget
+        budget.reset();
+        assert_eq!(budget.step(), Ok(()));
+    }
+
+    #[test]
+    fn test_allocation_limit() {
+        let mut limits = EvalLimits::default();
+        limits.max_allocations = Some(1);
+        let mut budget = EvalBudget::new(limits);
+        assert_eq!(budget.allocate(), Ok(()));
+        assert_eq!(budget.allocate(), Err(INTERPRETER_ERR_LIMIT_MEMORY));
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut limits = EvalLimits::default();
+        limits.timeout = Some(Duration::from_millis(0));
+        let mut budget = EvalBudget::new(limits);
+        budget.reset();
+        std::thread::sleep(Duration::from_millis(1));
+        assert_eq!(budget.check_timeout(), Err(INTERPRETER_ERR_TIMEOUT));
+
+        // No deadline without a timeout
+        let mut unlimited = EvalBudget::new(EvalLimits::default());
+        unlimited.reset();
+        assert_eq!(unlimited.check_timeout(), Ok(()));
+    }
+}
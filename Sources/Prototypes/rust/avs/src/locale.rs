@@ -0,0 +1,96 @@
+/*
+Locale data for format(). Bundled as constants so formatting never depends on the host
+or the network, and a notebook formats the same way wherever it's evaluated.
+Only the symbols and names which patterns can refer to are included.
+*/
+
+pub struct Locale {
+    pub code: &'static str,
+    pub decimal: &'static str,
+    pub group: &'static str,
+    pub percent: &'static str,
+    pub currency: &'static str,
+    pub months: [&'static str; 12],
+    pub months_short: [&'static str; 12],
+    // Monday first, matching ISO weekday numbers
+    pub weekdays: [&'static str; 7],
+    pub weekdays_short: [&'static str; 7],
+    // Before and after noon
+    pub day_periods: [&'static str; 2]
+}
+
+pub const DEFAULT_LOCALE: &'static Locale = &LOCALES[0];
+
+pub const LOCALES: [Locale; 6] = [
+    Locale {
+        code: "en",
+        decimal: ".", group: ",", percent: "%", currency: "$",
+        months: ["January", "February", "March", "April", "May", "June",
+            "July", "August", "September", "October", "November", "December"],
+        months_short: ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"],
+        weekdays: ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"],
+        weekdays_short: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+        day_periods: ["AM", "PM"]
+    },
+    Locale {
+        code: "en-GB",
+        decimal: ".", group: ",", percent: "%", currency: "£",
+        months: ["January", "February", "March", "April", "May", "June",
+            "July", "August", "September", "October", "November", "December"],
+        months_short: ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sept", "Oct", "Nov", "Dec"],
+        weekdays: ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"],
+        weekdays_short: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+        day_periods: ["am", "pm"]
+    },
+    Locale {
+        code: "de",
+        decimal: ",", group: ".", percent: "%", currency: "€",
+        months: ["Januar", "Februar", "März", "April", "Mai", "Juni",
+            "Juli", "August", "September", "Oktober", "November", "Dezember"],
+        months_short: ["Jan.", "Feb.", "März", "Apr.", "Mai", "Juni", "Juli", "Aug.", "Sept.", "Okt.", "Nov.", "Dez."],
+        weekdays: ["Montag", "Dienstag", "Mittwoch", "Donnerstag", "Freitag", "Samstag", "Sonntag"],
+        weekdays_short: ["Mo.", "Di.", "Mi.", "Do.", "Fr.", "Sa.", "So."],
+        day_periods: ["AM", "PM"]
+    },
+    Locale {
+        // The group separator is a narrow no-break space
+        code: "fr",
+        decimal: ",", group: "\u{202F}", percent: "%", currency: "€",
+        months: ["janvier", "février", "mars", "avril", "mai", "juin",
+            "juillet", "août", "septembre", "octobre", "novembre", "décembre"],
+        months_short: ["janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.", "août", "sept.", "oct.", "nov.", "déc."],
+        weekdays: ["lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche"],
+        weekdays_short: ["lun.", "mar.", "mer.", "jeu.", "ven.", "sam.", "dim."],
+        day_periods: ["AM", "PM"]
+    },
+    Locale {
+        code: "es",
+        decimal: ",", group: ".", percent: "%", currency: "€",
+        months: ["enero", "febrero", "marzo", "abril", "mayo", "junio",
+            "julio", "agosto", "septiembre", "octubre", "noviembre", "diciembre"],
+        months_short: ["ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sept", "oct", "nov", "dic"],
+        weekdays: ["lunes", "martes", "miércoles", "jueves", "viernes", "sábado", "domingo"],
+        weekdays_short: ["lun", "mar", "mié", "jue", "vie", "sáb", "dom"],
+        day_periods: ["a. m.", "p. m."]
+    },
+    Locale {
+        code: "ja",
+        decimal: ".", group: ",", percent: "%", currency: "¥",
+        months: ["1月", "2月", "3月", "4月", "5月", "6月", "7月", "8月", "9月", "10月", "11月", "12月"],
+        months_short: ["1月", "2月", "3月", "4月", "5月", "6月", "7月", "8月", "9月", "10月", "11月", "12月"],
+        weekdays: ["月曜日", "火曜日", "水曜日", "木曜日", "金曜日", "土曜日", "日曜日"],
+        weekdays_short: ["月", "火", "水", "木", "金", "土", "日"],
+        day_periods: ["午前", "午後"]
+    }
+];
+
+// Case insensitive, with _ or - between parts. Regional variants we don't bundle
+// fall back to their language, so de-AT formats as de.
+pub fn find_locale(code: &str) -> Option<&'static Locale> {
+    let code = code.trim().replace('_', "-");
+    if let Some(locale) = LOCALES.iter().find(|locale| locale.code.eq_ignore_ascii_case(&code)) {
+        return Some(locale)
+    }
+    let language = code.split('-').next()?;
+    return LOCALES.iter().find(|locale| locale.code.eq_ignore_ascii_case(language))
+}
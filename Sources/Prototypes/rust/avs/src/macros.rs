@@ -22,6 +22,10 @@ macro_rules! disallow_nan {
 macro_rules! valid_num {
 	($val:expr) => ({
 		let f_val = f64::from_bits($val);
+		// Propagate upstream errors as-is so they can be recovered from
+		if is_error($val) {
+			return $val
+		}
 		// Disallow nan or other data types
 		if is_nan(f_val) {
 			return RUNTIME_ERR_EXPECTED_NUM
@@ -37,9 +41,12 @@ macro_rules! resolve_atom {
 		// Given a u64, return the f64 resolved value or raise error
 		if !is_nan(f_val) {
 			Atom::NumericValue(f_val)
+		} else if let Some(inline_str) = $crate::utils::decode_inline_str($val) {
+			// Small strings are stored directly in the value
+			Atom::StringValue(inline_str.into())
 		} else if is_pointer($val) {
 			let symbol_resolution = $env.deep_resolve($val);
-			println!("Resolved symbol {:X} -> {:?}", $val, symbol_resolution);
+			av_trace!($env, $crate::trace::TraceLevel::Trace, "Resolved symbol {:X} -> {:?}", $val, symbol_resolution);
 			if let Some(symbol_id) = symbol_resolution {
 				if let Some(symbol_value) = &symbol_id.value {
 					symbol_value.clone()
@@ -58,3 +65,16 @@ macro_rules! resolve_atom {
 	})
 }
 
+
+
+#[macro_export]
+macro_rules! av_trace {
+	($env:expr, $level:expr, $($arg:tt)+) => ({
+		// Only format the message if a tracer is listening at this level
+		if let Some(tracer) = &$env.tracer {
+			if tracer.enabled($level) {
+				tracer.event($level, &format!($($arg)+));
+			}
+		}
+	})
+}
@@ -0,0 +1,206 @@
+use crate::environment::Environment;
+use crate::constants::*;
+use crate::types::*;
+use crate::decimal::{Decimal, RoundingMode};
+use crate::numeric::int_arg;
+
+/*
+Math standard library.
+Results outside a function's domain (i.e. ln(0) or asin(2)) are returned as RUNTIME_ERR_DOMAIN
+rather than NaN, since a NaN would only surface as a confusing type error in the next operation.
+Integer functions (gcd, lcm) are limited to integers which are exact in an f64.
+*/
+
+// Digits beyond this are below f64 precision anyway
+const MAX_ROUND_DIGITS: f64 = 308.0;
+
+fn checked_result(result: f64) -> u64 {
+    if result.is_nan() {
+        return RUNTIME_ERR_DOMAIN
+    }
+    return result.to_bits()
+}
+
+macro_rules! unary_math_fn {
+    ($name:ident, $method:ident) => {
+        pub fn $name(_env: &mut Environment, a: u64) -> u64 {
+            let f_a: f64 = valid_num!(a);
+            return checked_result(f_a.$method())
+        }
+    }
+}
+
+unary_math_fn!(__av_exp, exp);
+unary_math_fn!(__av_sin, sin);
+unary_math_fn!(__av_cos, cos);
+unary_math_fn!(__av_tan, tan);
+unary_math_fn!(__av_asin, asin);
+unary_math_fn!(__av_acos, acos);
+unary_math_fn!(__av_atan, atan);
+
+// Logarithms of zero are -infinity, which is as undefined as a negative input.
+macro_rules! log_fn {
+    ($name:ident, $method:ident) => {
+        pub fn $name(_env: &mut Environment, a: u64) -> u64 {
+            let f_a: f64 = valid_num!(a);
+            if f_a <= 0.0 {
+                return RUNTIME_ERR_DOMAIN
+            }
+            return f_a.$method().to_bits()
+        }
+    }
+}
+
+log_fn!(__av_ln, ln);
+log_fn!(__av_log10, log10);
+log_fn!(__av_log2, log2);
+
+pub fn __av_sqrt(_env: &mut Environment, a: u64) -> u64 {
+    let f_a: f64 = valid_num!(a);
+    return checked_result(f_a.sqrt())
+}
+
+// pow(base, exponent). Also the ^ operator.
+pub fn __av_pow(_env: &mut Environment, a: u64, b: u64) -> u64 {
+    let f_a: f64 = valid_num!(a);
+    let f_b: f64 = valid_num!(b);
+    if f_a == 0.0 && f_b < 0.0 {
+        return RUNTIME_ERR_DIV_Z
+    }
+    // Fractional powers of negative numbers are complex
+    return checked_result(f_a.powf(f_b))
+}
+
+// atan2(y, x)
+pub fn __av_atan2(_env: &mut Environment, y: u64, x: u64) -> u64 {
+    let f_y: f64 = valid_num!(y);
+    let f_x: f64 = valid_num!(x);
+    return checked_result(f_y.atan2(f_x))
+}
+
+pub fn __av_hypot(_env: &mut Environment, a: u64, b: u64) -> u64 {
+    let f_a: f64 = valid_num!(a);
+    let f_b: f64 = valid_num!(b);
+    return f_a.hypot(f_b).to_bits()
+}
+
+// clamp(value, low, high)
+pub fn __av_clamp(_env: &mut Environment, value: u64, low: u64, high: u64) -> u64 {
+    let f_value: f64 = valid_num!(value);
+    let f_low: f64 = valid_num!(low);
+    let f_high: f64 = valid_num!(high);
+    if f_low > f_high {
+        return RUNTIME_ERR_INVALID_ARG
+    }
+    return f_value.max(f_low).min(f_high).to_bits()
+}
+
+// -1, 0 or 1. Unlike f64::signum, zero has no sign.
+pub fn __av_sign(_env: &mut Environment, a: u64) -> u64 {
+    let f_a: f64 = valid_num!(a);
+    if f_a == 0.0 {
+        return (0.0 as f64).to_bits()
+    }
+    return f_a.signum().to_bits()
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let remainder = a % b;
+        a = b;
+        b = remainder;
+    }
+    return a
+}
+
+pub fn __av_gcd(_env: &mut Environment, a: u64, b: u64) -> u64 {
+    match (int_arg(a), int_arg(b)) {
+        (Ok(i_a), Ok(i_b)) => (gcd(i_a.unsigned_abs(), i_b.unsigned_abs()) as f64).to_bits(),
+        (Err(err), _) | (_, Err(err)) => err
+    }
+}
+
+pub fn __av_lcm(_env: &mut Environment, a: u64, b: u64) -> u64 {
+    match (int_arg(a), int_arg(b)) {
+        (Ok(0), Ok(_)) | (Ok(_), Ok(0)) => (0.0 as f64).to_bits(),
+        (Ok(i_a), Ok(i_b)) => {
+            let (i_a, i_b) = (i_a.unsigned_abs(), i_b.unsigned_abs());
+            let lcm = (i_a / gcd(i_a, i_b)) as f64 * i_b as f64;
+            if lcm > crate::numeric::MAX_SAFE_INT {
+                return RUNTIME_ERR_INVALID_ARG
+            }
+            lcm.to_bits()
+        },
+        (Err(err), _) | (_, Err(err)) => err
+    }
+}
+
+// round(value) or round(value, digits). Halves round away from zero.
+// Negative digits round to the left of the decimal point. i.e. round(1250, -2) = 1300
+pub fn __av_round(_env: &mut Environment, args: &[u64]) -> u64 {
+    if args.len() == 0 || args.len() > 2 {
+        return RUNTIME_ERR_FN_ARITY
+    }
+    let f_value: f64 = valid_num!(args[0]);
+    let digits: f64 = if args.len() == 2 { valid_num!(args[1]) } else { 0.0 };
+    if digits.fract() != 0.0 || digits.abs() > MAX_ROUND_DIGITS {
+        return RUNTIME_ERR_INVALID_ARG
+    }
+    if digits <= 0.0 {
+        let scale = (10.0 as f64).powi(-digits as i32);
+        return ((f_value / scale).round() * scale).to_bits()
+    }
+    // Round the decimal representation, so 2.675 rounds to 2.68 even though the f64 is slightly less.
+    match Decimal::from_f64(f_value) {
+        Some(decimal) => decimal.rescale(digits as u32, RoundingMode::HalfUp).to_f64().to_bits(),
+        None => args[0]
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn num(value: f64) -> u64 {
+        return value.to_bits()
+    }
+
+    #[test]
+    fn test_domain_errors() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        assert_eq!(__av_ln(&mut env, num(0.0)), RUNTIME_ERR_DOMAIN);
+        assert_eq!(__av_log2(&mut env, num(8.0)), num(3.0));
+        assert_eq!(__av_sqrt(&mut env, num(-1.0)), RUNTIME_ERR_DOMAIN);
+        assert_eq!(__av_asin(&mut env, num(2.0)), RUNTIME_ERR_DOMAIN);
+        assert_eq!(__av_pow(&mut env, num(-8.0), num(0.5)), RUNTIME_ERR_DOMAIN);
+        assert_eq!(__av_pow(&mut env, num(-2.0), num(3.0)), num(-8.0));
+        assert_eq!(__av_pow(&mut env, num(0.0), num(-1.0)), RUNTIME_ERR_DIV_Z);
+        // Errors propagate rather than becoming domain errors
+        assert_eq!(__av_exp(&mut env, RUNTIME_ERR_DIV_Z), RUNTIME_ERR_DIV_Z);
+    }
+
+    #[test]
+    fn test_integer_fns() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        assert_eq!(__av_gcd(&mut env, num(12.0), num(-18.0)), num(6.0));
+        assert_eq!(__av_gcd(&mut env, num(0.0), num(5.0)), num(5.0));
+        assert_eq!(__av_lcm(&mut env, num(4.0), num(6.0)), num(12.0));
+        assert_eq!(__av_lcm(&mut env, num(0.0), num(6.0)), num(0.0));
+        assert_eq!(__av_gcd(&mut env, num(1.5), num(3.0)), RUNTIME_ERR_INVALID_ARG);
+        assert_eq!(__av_sign(&mut env, num(-0.0)), num(0.0));
+        assert_eq!(__av_clamp(&mut env, num(15.0), num(0.0), num(10.0)), num(10.0));
+        assert_eq!(__av_clamp(&mut env, num(5.0), num(10.0), num(0.0)), RUNTIME_ERR_INVALID_ARG);
+    }
+
+    #[test]
+    fn test_round_digits() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        assert_eq!(__av_round(&mut env, &[num(2.5)]), num(3.0));
+        assert_eq!(__av_round(&mut env, &[num(-2.5)]), num(-3.0));
+        assert_eq!(__av_round(&mut env, &[num(2.675), num(2.0)]), num(2.68));
+        assert_eq!(__av_round(&mut env, &[num(1250.0), num(-2.0)]), num(1300.0));
+        assert_eq!(__av_round(&mut env, &[num(1.0), num(0.5)]), RUNTIME_ERR_INVALID_ARG);
+        assert_eq!(__av_round(&mut env, &[]), RUNTIME_ERR_FN_ARITY);
+    }
+}
@@ -0,0 +1,366 @@
+use crate::environment::Environment;
+use crate::structs::Atom;
+use crate::constants::*;
+use crate::types::{is_nan, is_error, is_pointer};
+use core::cmp::Ordering;
+use num_bigint::BigInt;
+use crate::decimal::{Decimal, RoundingMode};
+use num_traits::{Zero, ToPrimitive, FromPrimitive};
+
+/*
+Number tower. Numbers are f64 by default for fast math.
+Integers which grow past what f64 can represent exactly overflow into arbitrary precision
+integers, which are boxed on the heap behind a pointer. Results which fit back into the
+safe range are demoted to f64, so there's a single representation for every integer.
+Decimals are opt-in and contagious - any arithmetic involving a decimal results in a decimal.
+*/
+
+// 2^53 - 1. Larger integers can't be told apart from their rounded neighbours as f64.
+pub const MAX_SAFE_INT: f64 = 9007199254740991.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Number {
+    Float(f64),
+    Int(BigInt),
+    Decimal(Decimal)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arithmetic {
+    Add,
+    Sub,
+    Mul,
+    Div
+}
+
+#[inline(always)]
+pub fn is_safe_int(value: f64) -> bool {
+    return value.fract() == 0.0 && value.abs() <= MAX_SAFE_INT
+}
+
+// Whole number arguments, i.e. counts and indexes. Upstream errors pass through.
+pub(crate) fn int_arg(value: u64) -> Result<i64, u64> {
+    let f_value = f64::from_bits(value);
+    if is_error(value) {
+        return Err(value)
+    } else if is_nan(f_value) {
+        return Err(RUNTIME_ERR_EXPECTED_NUM)
+    } else if !is_safe_int(f_value) {
+        return Err(RUNTIME_ERR_INVALID_ARG)
+    }
+    return Ok(f_value as i64)
+}
+
+impl Number {
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Number::Float(f_val) => *f_val,
+            Number::Int(big) => big.to_f64().unwrap_or(core::f64::NAN),
+            Number::Decimal(decimal) => decimal.to_f64()
+        }
+    }
+
+    // Integer value of the number, if it's known exactly.
+    pub fn to_bigint(&self) -> Option<BigInt> {
+        match self {
+            Number::Float(f_val) => {
+                if is_safe_int(*f_val) {
+                    return BigInt::from_f64(*f_val)
+                }
+                return None
+            },
+            Number::Int(big) => Some(big.clone()),
+            Number::Decimal(_) => None
+        }
+    }
+
+    pub fn to_decimal(&self) -> Option<Decimal> {
+        match self {
+            Number::Float(f_val) => Decimal::from_f64(*f_val),
+            Number::Int(big) => Some(Decimal::from_int(big.clone())),
+            Number::Decimal(decimal) => Some(decimal.clone())
+        }
+    }
+
+    pub fn is_decimal(&self) -> bool {
+        if let Number::Decimal(_) = self {
+            return true
+        }
+        return false
+    }
+}
+
+// Parse an integer literal which is too large for f64 to represent exactly.
+pub fn parse_int_literal(digits: &str, is_negative: bool) -> Option<Atom> {
+    let mut big: BigInt = digits.parse().ok()?;
+    if is_negative {
+        big = -big;
+    }
+    if let Some(f_val) = big.to_f64() {
+        if is_safe_int(f_val) {
+            return None
+        }
+    }
+    return Some(Atom::BigIntValue(big))
+}
+
+pub fn resolve_number(env: &Environment, value: u64) -> Result<Number, u64> {
+    if is_error(value) {
+        return Err(value)
+    }
+    let f_val = f64::from_bits(value);
+    if !is_nan(f_val) {
+        return Ok(Number::Float(f_val))
+    }
+    if is_pointer(value) {
+        if let Some(identifier) = env.deep_resolve(value) {
+            match &identifier.value {
+                Some(Atom::NumericValue(num)) => return Ok(Number::Float(*num)),
+                Some(Atom::BigIntValue(big)) => return Ok(Number::Int(big.clone())),
+                Some(Atom::DecimalValue(decimal)) => return Ok(Number::Decimal(decimal.clone())),
+                _ => {}
+            }
+        }
+    }
+    return Err(RUNTIME_ERR_EXPECTED_NUM)
+}
+
+// Save a number, demoting integers to f64 when they fit.
+pub fn init_number(env: &mut Environment, number: Number) -> u64 {
+    match number {
+        Number::Float(f_val) => f_val.to_bits(),
+        Number::Int(big) => {
+            if let Some(f_val) = big.to_f64() {
+                if is_safe_int(f_val) {
+                    return f_val.to_bits()
+                }
+            }
+            return env.init_value(Atom::BigIntValue(big))
+        },
+        Number::Decimal(decimal) => env.init_value(Atom::DecimalValue(decimal))
+    }
+}
+
+// Convert any number to a decimal. i.e. for decimal(0.1, 2)
+pub fn resolve_decimal(env: &Environment, value: u64) -> Result<Decimal, u64> {
+    let number = resolve_number(env, value)?;
+    match number.to_decimal() {
+        Some(decimal) => Ok(decimal),
+        None => Err(RUNTIME_ERR_EXPECTED_NUM)
+    }
+}
+
+fn apply_float(op: Arithmetic, f_a: f64, f_b: f64) -> Result<f64, u64> {
+    match op {
+        Arithmetic::Add => Ok(f_a + f_b),
+        Arithmetic::Sub => Ok(f_a - f_b),
+        Arithmetic::Mul => Ok(f_a * f_b),
+        Arithmetic::Div => {
+            if f_b == 0.0 {
+                return Err(RUNTIME_ERR_DIV_Z)
+            }
+            Ok(f_a / f_b)
+        }
+    }
+}
+
+fn apply_decimal(op: Arithmetic, a: Decimal, b: Decimal) -> Result<Number, u64> {
+    match op {
+        Arithmetic::Add => Ok(Number::Decimal(a.add(&b))),
+        Arithmetic::Sub => Ok(Number::Decimal(a.sub(&b))),
+        Arithmetic::Mul => Ok(Number::Decimal(a.mul(&b))),
+        Arithmetic::Div => {
+            match a.div(&b, RoundingMode::HalfEven) {
+                Some(result) => Ok(Number::Decimal(result)),
+                None => Err(RUNTIME_ERR_DIV_Z)
+            }
+        }
+    }
+}
+
+fn apply_int(op: Arithmetic, a: BigInt, b: BigInt) -> Result<Number, u64> {
+    match op {
+        Arithmetic::Add => Ok(Number::Int(a + b)),
+        Arithmetic::Sub => Ok(Number::Int(a - b)),
+        Arithmetic::Mul => Ok(Number::Int(a * b)),
+        Arithmetic::Div => {
+            if b.is_zero() {
+                return Err(RUNTIME_ERR_DIV_Z)
+            }
+            // Exact quotients stay integers. Others fall back to floats.
+            if (&a % &b).is_zero() {
+                return Ok(Number::Int(a / b))
+            }
+            let f_a = a.to_f64().unwrap_or(core::f64::NAN);
+            let f_b = b.to_f64().unwrap_or(core::f64::NAN);
+            Ok(Number::Float(f_a / f_b))
+        }
+    }
+}
+
+pub fn apply_arithmetic(env: &mut Environment, op: Arithmetic, a: u64, b: u64) -> u64 {
+    let f_a = f64::from_bits(a);
+    let f_b = f64::from_bits(b);
+    // Fast path for regular floats
+    if !is_nan(f_a) && !is_nan(f_b) {
+        let result = match apply_float(op, f_a, f_b) {
+            Ok(result) => result,
+            Err(err) => return err
+        };
+        // Promote when exact integers overflow what f64 can represent
+        if !(result.abs() > MAX_SAFE_INT) || !is_safe_int(f_a) || !is_safe_int(f_b) {
+            return result.to_bits()
+        }
+    }
+
+    let n_a = match resolve_number(env, a) {
+        Ok(n_a) => n_a,
+        Err(err) => return err
+    };
+    let n_b = match resolve_number(env, b) {
+        Ok(n_b) => n_b,
+        Err(err) => return err
+    };
+
+    if n_a.is_decimal() || n_b.is_decimal() {
+        let result = match (n_a.to_decimal(), n_b.to_decimal()) {
+            (Some(d_a), Some(d_b)) => apply_decimal(op, d_a, d_b),
+            // NaN and infinities have no decimal equivalent
+            _ => Err(RUNTIME_ERR_EXPECTED_NUM)
+        };
+        return match result {
+            Ok(number) => init_number(env, number),
+            Err(err) => err
+        }
+    }
+
+    let result = match (n_a.to_bigint(), n_b.to_bigint()) {
+        (Some(big_a), Some(big_b)) => apply_int(op, big_a, big_b),
+        // Inexact values on either side make the whole result inexact
+        _ => apply_float(op, n_a.to_f64(), n_b.to_f64()).map(Number::Float)
+    };
+    match result {
+        Ok(number) => init_number(env, number),
+        Err(err) => err
+    }
+}
+
+pub fn compare_numbers(a: &Number, b: &Number) -> Option<Ordering> {
+    if a.is_decimal() || b.is_decimal() {
+        let d_a = a.to_decimal()?;
+        let d_b = b.to_decimal()?;
+        return Some(d_a.cmp(&d_b))
+    }
+    match (a, b) {
+        (Number::Float(f_a), Number::Float(f_b)) => f_a.partial_cmp(f_b),
+        (Number::Int(big_a), Number::Int(big_b)) => Some(big_a.cmp(big_b)),
+        (Number::Int(big), Number::Float(f_val)) => compare_int_float(big, *f_val),
+        (Number::Float(f_val), Number::Int(big)) => compare_int_float(big, *f_val).map(|order| order.reverse()),
+        _ => None
+    }
+}
+
+fn compare_int_float(big: &BigInt, f_val: f64) -> Option<Ordering> {
+    if f_val.is_nan() {
+        return None
+    }
+    if f_val.is_infinite() {
+        return Some(if f_val > 0.0 { Ordering::Less } else { Ordering::Greater })
+    }
+    // Every finite integral f64 converts exactly
+    if f_val.fract() == 0.0 {
+        return BigInt::from_f64(f_val).map(|f_big| big.cmp(&f_big))
+    }
+    // Compare against the integer part. Ties go to the float, which has a fraction.
+    let f_big = BigInt::from_f64(f_val.trunc())?;
+    match big.cmp(&f_big) {
+        Ordering::Equal => Some(if f_val.is_sign_positive() { Ordering::Less } else { Ordering::Greater }),
+        order => Some(order)
+    }
+}
+
+pub fn atom_number(atom: &Atom) -> Option<Number> {
+    match atom {
+        Atom::NumericValue(f_val) => Some(Number::Float(*f_val)),
+        Atom::BigIntValue(big) => Some(Number::Int(big.clone())),
+        Atom::DecimalValue(decimal) => Some(Number::Decimal(decimal.clone())),
+        _ => None
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(digits: &str) -> BigInt {
+        return digits.parse().unwrap()
+    }
+
+    #[test]
+    fn test_overflow_promotion() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        // Small results stay as floats
+        assert_eq!(apply_arithmetic(&mut env, Arithmetic::Add, 1.0f64.to_bits(), 2.0f64.to_bits()), 3.0f64.to_bits());
+
+        let result = apply_arithmetic(&mut env, Arithmetic::Mul, MAX_SAFE_INT.to_bits(), 3.0f64.to_bits());
+        assert_eq!(resolve_number(&env, result), Ok(Number::Int(big("27021597764222973"))));
+
+        // 2^53 + 1 rounds to 2^53 as a float
+        let result = apply_arithmetic(&mut env, Arithmetic::Add, MAX_SAFE_INT.to_bits(), 2.0f64.to_bits());
+        assert_eq!(resolve_number(&env, result), Ok(Number::Int(big("9007199254740993"))));
+
+        // And demotes back once it fits again
+        let result = apply_arithmetic(&mut env, Arithmetic::Sub, result, 3.0f64.to_bits());
+        assert_eq!(result, 9007199254740990.0f64.to_bits());
+    }
+
+    #[test]
+    fn test_mixed_arithmetic() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let big_val = env.init_value(Atom::BigIntValue(big("100000000000000000000")));
+
+        let result = apply_arithmetic(&mut env, Arithmetic::Div, big_val, 4.0f64.to_bits());
+        assert_eq!(resolve_number(&env, result), Ok(Number::Int(big("25000000000000000000"))));
+
+        // Inexact division and fractional operands fall back to floats
+        let result = apply_arithmetic(&mut env, Arithmetic::Div, big_val, 3.0f64.to_bits());
+        assert_eq!(f64::from_bits(result), 1e20 / 3.0);
+        let result = apply_arithmetic(&mut env, Arithmetic::Mul, big_val, 0.5f64.to_bits());
+        assert_eq!(f64::from_bits(result), 5e19);
+
+        assert_eq!(apply_arithmetic(&mut env, Arithmetic::Div, big_val, 0.0f64.to_bits()), RUNTIME_ERR_DIV_Z);
+        assert_eq!(apply_arithmetic(&mut env, Arithmetic::Add, big_val, SYMBOL_TRUE.symbol), RUNTIME_ERR_EXPECTED_NUM);
+    }
+
+    #[test]
+    fn test_compare_numbers() {
+        let large = Number::Int(big("9007199254740993"));
+        assert_eq!(compare_numbers(&large, &Number::Float(MAX_SAFE_INT)), Some(Ordering::Greater));
+        assert_eq!(compare_numbers(&Number::Float(MAX_SAFE_INT), &large), Some(Ordering::Less));
+        assert_eq!(compare_numbers(&Number::Int(big("-9007199254740993")), &Number::Float(-0.5)), Some(Ordering::Less));
+        assert_eq!(compare_numbers(&large, &Number::Float(core::f64::INFINITY)), Some(Ordering::Less));
+        assert_eq!(compare_numbers(&large, &Number::Int(big("9007199254740993"))), Some(Ordering::Equal));
+    }
+
+    #[test]
+    fn test_decimal_arithmetic() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let tenth = env.init_value(Atom::DecimalValue(Decimal::parse("0.1").unwrap()));
+        let result = apply_arithmetic(&mut env, Arithmetic::Add, tenth, 0.2f64.to_bits());
+        assert_eq!(resolve_number(&env, result), Ok(Number::Decimal(Decimal::parse("0.3").unwrap())));
+
+        let result = apply_arithmetic(&mut env, Arithmetic::Mul, MAX_SAFE_INT.to_bits(), tenth);
+        assert_eq!(resolve_number(&env, result), Ok(Number::Decimal(Decimal::parse("900719925474099.1").unwrap())));
+
+        assert_eq!(apply_arithmetic(&mut env, Arithmetic::Div, tenth, 0.0f64.to_bits()), RUNTIME_ERR_DIV_Z);
+        assert_eq!(apply_arithmetic(&mut env, Arithmetic::Add, tenth, core::f64::INFINITY.to_bits()), RUNTIME_ERR_EXPECTED_NUM);
+        assert_eq!(compare_numbers(&Number::Float(0.1), &Number::Decimal(Decimal::parse("0.10").unwrap())), Some(Ordering::Equal));
+    }
+
+    #[test]
+    fn test_parse_int_literal() {
+        assert_eq!(parse_int_literal("123", false), None);
+        assert_eq!(parse_int_literal("9007199254740993", true), Some(Atom::BigIntValue(big("-9007199254740993"))));
+    }
+}
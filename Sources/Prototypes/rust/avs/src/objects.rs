@@ -0,0 +1,170 @@
+use crate::environment::Environment;
+use crate::structs::Atom;
+use crate::types::*;
+use crate::constants::{AV_CLASS_CLASS, RUNTIME_ERR_INVALID_ARG};
+use crate::heap::{is_heap_pointer, MAX_ATTRIBUTES};
+use crate::operators::values_equal;
+
+/*
+User declared record types. i.e.
+DateString:
+    year
+    month
+    day
+Classes and their instances are both allocated on the heap.
+A class is an object of AV_CLASS_CLASS. Its first attribute is the symbol of the cell which declared it,
+followed by its field names. Classes are padded to MAX_ATTRIBUTES so they all fit the same region.
+Instances are allocated in their class's regions, with each field at its attribute index.
+*/
+
+// The declaring cell's symbol. Field names follow it.
+const CLASS_SYMBOL: usize = 0;
+// Pads the unused attributes of a class. Field names are strings, so this never matches one.
+const NO_FIELD: u64 = 0;
+
+pub const MAX_FIELDS: usize = MAX_ATTRIBUTES - 1;
+
+// Define a class and bind it to the symbol of the cell which declared it. Returns the class.
+pub fn declare_class(env: &mut Environment, symbol: u64, fields: &[String]) -> u64 {
+    if fields.len() > MAX_FIELDS {
+        return RUNTIME_ERR_INVALID_ARG
+    }
+    let mut attributes: Vec<u64> = vec![NO_FIELD; MAX_ATTRIBUTES];
+    attributes[CLASS_SYMBOL] = symbol;
+    for (index, field) in fields.iter().enumerate() {
+        let name = env.init_string(field.to_string());
+        if is_error(name) {
+            return name
+        }
+        attributes[CLASS_SYMBOL + 1 + index] = name;
+    }
+    match env.heap.alloc(AV_CLASS_CLASS, &attributes) {
+        Ok(class) => {
+            env.bind_value(symbol, Atom::SymbolValue(class));
+            return class
+        },
+        Err(err) => return err
+    }
+}
+
+pub fn is_class(env: &Environment, value: u64) -> bool {
+    return env.heap.class_of(value) == Some(AV_CLASS_CLASS)
+}
+
+// Create an instance with values in field order.
+pub fn construct(env: &mut Environment, class: u64, values: Vec<u64>) -> u64 {
+    if let Some(err) = values.iter().find(|value| is_error(**value)) {
+        return *err
+    }
+    if let Err(err) = env.budget.allocate() {
+        return err
+    }
+    match env.heap.alloc(class, &values) {
+        Ok(pointer) => pointer,
+        Err(err) => err
+    }
+}
+
+// The cell which declared the class. Used for its name.
+pub fn class_symbol(env: &Environment, class: u64) -> Option<u64> {
+    return env.heap.values(class)?.get(CLASS_SYMBOL).cloned()
+}
+
+fn field_values(env: &Environment, class: u64) -> &[u64] {
+    let fields = match env.heap.values(class) {
+        Some(values) if is_class(env, class) => &values[CLASS_SYMBOL + 1..],
+        _ => &[]
+    };
+    let len = fields.iter().position(|value| *value == NO_FIELD).unwrap_or(fields.len());
+    return &fields[..len]
+}
+
+pub fn field_count(env: &Environment, class: u64) -> usize {
+    return field_values(env, class).len()
+}
+
+pub fn field_names(env: &Environment, class: u64) -> Vec<String> {
+    let mut names: Vec<String> = vec![];
+    for field in field_values(env, class).iter() {
+        match resolve_atom!(env, *field) {
+            Atom::StringValue(name) => names.push(name.to_string()),
+            _ => names.push(String::from("?"))
+        }
+    }
+    return names
+}
+
+// Field names are case insensitive, like other identifiers.
+pub fn field_index(env: &Environment, class: u64, name: &str) -> Option<usize> {
+    return field_names(env, class).iter().position(|field| field.eq_ignore_ascii_case(name))
+}
+
+// Attribute access on an instance. i.e. d.year
+pub fn object_field(env: &Environment, pointer: u64, name: &str) -> Option<u64> {
+    let class = env.heap.class_of(pointer)?;
+    let index = field_index(env, class, name)?;
+    return env.heap.get(pointer, index as u64 + 1)
+}
+
+// Instances are equal if they have the same class and equal fields.
+pub fn objects_equal(env: &mut Environment, a: u64, b: u64) -> bool {
+    if !is_heap_pointer(a) || !is_heap_pointer(b) || env.heap.class_of(a) != env.heap.class_of(b) {
+        return false
+    }
+    let values_a = env.heap.values(a).map(|values| values.to_vec());
+    let values_b = env.heap.values(b).map(|values| values.to_vec());
+    match (values_a, values_b) {
+        (Some(values_a), Some(values_b)) => {
+            values_a.iter().zip(values_b.iter()).all(|(v_a, v_b)| values_equal(env, *v_a, *v_b))
+        },
+        _ => false
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+    use crate::heap::ATTRIBUTE_SELF;
+
+    #[test]
+    fn test_fields() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let cell = env.define_identifier();
+        let fields = vec![String::from("year"), String::from("month")];
+        let class = declare_class(&mut env, cell, &fields);
+        assert!(is_class(&env, class));
+        assert_eq!(class_symbol(&env, class), Some(cell));
+        assert_eq!(field_names(&env, class), fields);
+
+        let instance = construct(&mut env, class, vec![(2023.0 as f64).to_bits(), (3.0 as f64).to_bits()]);
+        assert!(is_heap_pointer(instance));
+        assert!(!is_class(&env, instance));
+        assert_eq!(env.heap.class_of(instance), Some(class));
+        assert_eq!(object_field(&env, instance, "MONTH"), Some((3.0 as f64).to_bits()));
+        assert_eq!(object_field(&env, instance, "day"), None);
+        // Classes don't expose their field names as values
+        assert_eq!(object_field(&env, class, "year"), None);
+        assert_eq!(env.heap.get(instance, ATTRIBUTE_SELF), None);
+
+        assert_eq!(construct(&mut env, class, vec![RUNTIME_ERR_DIV_Z, 0]), RUNTIME_ERR_DIV_Z);
+    }
+
+    #[test]
+    fn test_class_sizes() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        // Classes share a region regardless of how many fields they have
+        let point_symbol = env.define_identifier();
+        let point = declare_class(&mut env, point_symbol, &[String::from("x"), String::from("y")]);
+        let empty_symbol = env.define_identifier();
+        let empty = declare_class(&mut env, empty_symbol, &[]);
+        assert_eq!(field_count(&env, point), 2);
+        assert_eq!(field_count(&env, empty), 0);
+        assert_eq!(env.heap.region_count(), 1);
+
+        let wide: Vec<String> = (0..MAX_FIELDS + 1).map(|index| format!("f{}", index)).collect();
+        let symbol = env.define_identifier();
+        assert_eq!(declare_class(&mut env, symbol, &wide), RUNTIME_ERR_INVALID_ARG);
+    }
+}
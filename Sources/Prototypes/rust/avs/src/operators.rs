@@ -5,20 +5,47 @@ use crate::constants::*;
 use crate::macros::*;
 use alloc::string::String;
 use alloc::borrow::Cow;
+use crate::utils::{is_inline_str, inline_str_key, concat_inline_str, known_str_len};
+use crate::numeric::{apply_arithmetic, compare_numbers, atom_number, Arithmetic};
+use crate::temporal::{is_temporal, apply_temporal, temporal_arithmetic, compare_temporal, temporal_field};
+use crate::units::{is_quantity, apply_quantity, quantity_arithmetic, quantity_power, compare_quantity};
+use crate::objects::{object_field, objects_equal};
+use crate::heap::is_heap_pointer;
+use crate::math::__av_pow;
+use core::cmp::Ordering;
 
 
 #[no_mangle]
 pub extern fn __av_add(env: &mut Environment, a: u64, b: u64) -> u64 {
 	// + is an overloaded operator, allowing combinations across various things
 	// To prevent exponential branching, resolve both elements to Atoms and then do the math.
+	if is_error(a) {
+		return a
+	} else if is_error(b) {
+		return b
+	}
+	if !is_nan(f64::from_bits(a)) && !is_nan(f64::from_bits(b)) {
+		return apply_arithmetic(env, Arithmetic::Add, a, b)
+	}
+	// Fast path for small strings without decoding them
+	if is_inline_str(a) && is_inline_str(b) {
+		if let Some(result) = concat_inline_str(a, b) {
+			return result
+		}
+	}
 	let atom_a = resolve_atom!(env, a);
 	let atom_b = resolve_atom!(env, b);
+	if is_temporal(&atom_a) || is_temporal(&atom_b) {
+		return apply_temporal(env, Arithmetic::Add, &atom_a, &atom_b)
+	}
+	if is_quantity(&atom_a) || is_quantity(&atom_b) {
+		return apply_quantity(env, Arithmetic::Add, &atom_a, &atom_b)
+	}
 	match atom_a {
-		Atom::NumericValue(f_a) => {
+		Atom::NumericValue(_) | Atom::BigIntValue(_) | Atom::DecimalValue(_) => {
 			match atom_b {
-				Atom::NumericValue(f_b) => {
-					let result = (f_a + f_b);
-					return result.to_bits();
+				Atom::NumericValue(_) | Atom::BigIntValue(_) | Atom::DecimalValue(_) => {
+					return apply_arithmetic(env, Arithmetic::Add, a, b)
 				},
 				_ => return RUNTIME_ERR_EXPECTED_NUM
 			}
@@ -30,7 +57,7 @@ pub extern fn __av_add(env: &mut Environment, a: u64, b: u64) -> u64 {
 					result_str.push_str(&str_a);
 					result_str.push_str(&str_b);
 
-					let result_symbol = env.init_value(Atom::StringValue(result_str));
+					let result_symbol = env.init_string(result_str);
 					return result_symbol
 				},
 				_ => return RUNTIME_ERR_EXPECTED_STR
@@ -41,31 +68,66 @@ pub extern fn __av_add(env: &mut Environment, a: u64, b: u64) -> u64 {
 }
 
 #[no_mangle]
-pub extern fn __av_sub(_env: &mut Environment, a: u64, b: u64) -> u64 {
-	let f_a: f64 = valid_num!(a);
-	let f_b: f64 = valid_num!(b);
-	return (f_a - f_b).to_bits()
+pub extern fn __av_sub(env: &mut Environment, a: u64, b: u64) -> u64 {
+	// i.e. days until a deadline
+	if let Some(result) = temporal_arithmetic(env, Arithmetic::Sub, a, b) {
+		return result
+	}
+	// i.e. 5 km - 300 m
+	if let Some(result) = quantity_arithmetic(env, Arithmetic::Sub, a, b) {
+		return result
+	}
+	return apply_arithmetic(env, Arithmetic::Sub, a, b)
 }
 
 #[no_mangle]
-pub extern fn __av_mul(_env: &mut Environment, a: u64, b: u64) -> u64 {
-	let f_a: f64 = valid_num!(a);
-	let f_b: f64 = valid_num!(b);
-	return (f_a * f_b).to_bits()
+pub extern fn __av_mul(env: &mut Environment, a: u64, b: u64) -> u64 {
+	if let Some(result) = quantity_arithmetic(env, Arithmetic::Mul, a, b) {
+		return result
+	}
+	return apply_arithmetic(env, Arithmetic::Mul, a, b)
 }
 
 #[no_mangle]
-pub extern fn __av_div(_env: &mut Environment, a: u64, b: u64) -> u64 {
-	let f_a: f64 = valid_num!(a);
-	let f_b: f64 = valid_num!(b);
-
-	if f_b == 0.0 {
-		return RUNTIME_ERR_DIV_Z;
+pub extern fn __av_div(env: &mut Environment, a: u64, b: u64) -> u64 {
+	// Errors on division by zero
+	if let Some(result) = quantity_arithmetic(env, Arithmetic::Div, a, b) {
+		return result
 	}
+	return apply_arithmetic(env, Arithmetic::Div, a, b)
+}
 
-	return (f_a / f_b).to_bits()
+
+// Attribute access. i.e. deadline.year or d.month. The attribute name is passed as a string.
+#[no_mangle]
+pub extern fn __av_dot(env: &mut Environment, a: u64, b: u64) -> u64 {
+	if is_error(a) {
+		return a
+	}
+	let field = match resolve_atom!(env, b) {
+		Atom::StringValue(field) => field,
+		_ => return RUNTIME_ERR_EXPECTED_STR
+	};
+	if is_heap_pointer(a) {
+		return object_field(env, a, &field).unwrap_or(RUNTIME_ERR_UNK_FIELD)
+	}
+	let atom_a = resolve_atom!(env, a);
+	match temporal_field(&atom_a, &field) {
+		Some(Atom::NumericValue(num)) => num.to_bits(),
+		Some(atom) => env.init_value(atom),
+		None => RUNTIME_ERR_UNK_FIELD
+	}
 }
 
+#[no_mangle]
+pub extern fn __av_power(env: &mut Environment, a: u64, b: u64) -> u64 {
+	if is_pointer(a) && !is_error(a) && !is_nan(f64::from_bits(b)) {
+		if let Atom::QuantityValue(quantity) = resolve_atom!(env, a) {
+			return quantity_power(env, &quantity, f64::from_bits(b))
+		}
+	}
+	return __av_pow(env, a, b)
+}
 
 #[no_mangle]
 pub extern fn __av_and(_env: &mut Environment, a: u64, b: u64) -> u64 {
@@ -90,35 +152,141 @@ pub extern fn __av_not(_env: &mut Environment, a: u64) -> u64 {
 	return __repr_bool(result);
 }
 
+// Compare numbers or strings. Other types aren't ordered.
+fn compare_values(env: &mut Environment, a: u64, b: u64) -> Result<Ordering, u64> {
+	if is_error(a) {
+		return Err(a)
+	} else if is_error(b) {
+		return Err(b)
+	}
+	let f_a = f64::from_bits(a);
+	let f_b = f64::from_bits(b);
+	if !is_nan(f_a) && !is_nan(f_b) {
+		return Ok(f_a.partial_cmp(&f_b).unwrap())
+	}
+	// Inline strings are packed so their payloads sort lexicographically
+	if is_inline_str(a) && is_inline_str(b) {
+		return Ok(inline_str_key(a).cmp(&inline_str_key(b)))
+	}
+
+	let atom_a = resolve_atom!(env, a);
+	let atom_b = resolve_atom!(env, b);
+	if let (Some(n_a), Some(n_b)) = (atom_number(&atom_a), atom_number(&atom_b)) {
+		match compare_numbers(&n_a, &n_b) {
+			Some(order) => return Ok(order),
+			None => return Err(RUNTIME_ERR_EXPECTED_NUM)
+		}
+	}
+	if let Some(order) = compare_temporal(&atom_a, &atom_b) {
+		return Ok(order)
+	}
+	if let Some(order) = compare_quantity(&atom_a, &atom_b) {
+		return Ok(order)
+	}
+	match (atom_a, atom_b) {
+		(Atom::StringValue(str_a), Atom::StringValue(str_b)) => Ok(str_a.cmp(&str_b)),
+		_ => Err(RUNTIME_ERR_EXPECTED_NUM)
+	}
+}
+
+pub fn values_equal(env: &mut Environment, a: u64, b: u64) -> bool {
+	let f_a = f64::from_bits(a);
+	let f_b = f64::from_bits(b);
+	if !is_nan(f_a) && !is_nan(f_b) {
+		return f_a == f_b
+	}
+	// Symbols and inline strings have a single encoding, so identical bits mean equal values.
+	if a == b {
+		return true
+	}
+	if is_inline_str(a) && is_inline_str(b) {
+		return false
+	}
+	if is_heap_pointer(a) || is_heap_pointer(b) {
+		return objects_equal(env, a, b)
+	}
+	// Strings of different lengths can't be equal. Checked without dereferencing.
+	if let (Some(len_a), Some(len_b)) = (known_str_len(a), known_str_len(b)) {
+		if len_a != len_b {
+			return false
+		}
+	}
+	let atom_a = resolve_atom!(env, a);
+	let atom_b = resolve_atom!(env, b);
+	// Large integers may be compared against floats
+	if let (Some(n_a), Some(n_b)) = (atom_number(&atom_a), atom_number(&atom_b)) {
+		return compare_numbers(&n_a, &n_b) == Some(Ordering::Equal)
+	}
+	// Datetimes in different offsets may be the same instant
+	if let Some(order) = compare_temporal(&atom_a, &atom_b) {
+		return order == Ordering::Equal
+	}
+	if let Some(order) = compare_quantity(&atom_a, &atom_b) {
+		return order == Ordering::Equal
+	}
+	match (atom_a, atom_b) {
+		// Elements may be boxed, so compare them by value
+		(Atom::ListValue(list_a), Atom::ListValue(list_b)) => {
+			return list_a.len() == list_b.len() &&
+				list_a.iter().zip(list_b.iter()).all(|(v_a, v_b)| values_equal(env, *v_a, *v_b))
+		},
+		(Atom::MapValue(map_a), Atom::MapValue(map_b)) => {
+			return map_a.len() == map_b.len() &&
+				map_a.iter().zip(map_b.iter()).all(|((k_a, v_a), (k_b, v_b))| k_a == k_b && values_equal(env, *v_a, *v_b))
+		},
+		(atom_a, atom_b) => return atom_a == atom_b
+	}
+}
+
 #[no_mangle]
-pub extern fn __av_gt(_env: &mut Environment, a: u64, b: u64) -> u64 {
-	let f_a: f64 = valid_num!(a);
-	let f_b: f64 = valid_num!(b);
-	let result = f_a > f_b;
-	return __repr_bool(result);
+pub extern fn __av_eq(env: &mut Environment, a: u64, b: u64) -> u64 {
+	if is_error(a) {
+		return a
+	} else if is_error(b) {
+		return b
+	}
+	return __repr_bool(values_equal(env, a, b));
 }
 
 #[no_mangle]
-pub extern fn __av_gte(_env: &mut Environment, a: u64, b: u64) -> u64 {
-	let f_a: f64 = valid_num!(a);
-	let f_b: f64 = valid_num!(b);
-	let result = f_a >= f_b;
-	return __repr_bool(result);
+pub extern fn __av_ne(env: &mut Environment, a: u64, b: u64) -> u64 {
+	if is_error(a) {
+		return a
+	} else if is_error(b) {
+		return b
+	}
+	return __repr_bool(!values_equal(env, a, b));
 }
 
+#[no_mangle]
+pub extern fn __av_gt(env: &mut Environment, a: u64, b: u64) -> u64 {
+	match compare_values(env, a, b) {
+		Ok(order) => __repr_bool(order == Ordering::Greater),
+		Err(err) => err
+	}
+}
 
 #[no_mangle]
-pub extern fn __av_lt(_env: &mut Environment, a: u64, b: u64) -> u64 {
-	let f_a: f64 = valid_num!(a);
-	let f_b: f64 = valid_num!(b);
-	let result = f_a < f_b;
-	return __repr_bool(result);
+pub extern fn __av_gte(env: &mut Environment, a: u64, b: u64) -> u64 {
+	match compare_values(env, a, b) {
+		Ok(order) => __repr_bool(order != Ordering::Less),
+		Err(err) => err
+	}
 }
 
+
 #[no_mangle]
-pub extern fn __av_lte(_env: &mut Environment, a: u64, b: u64) -> u64 {
-	let f_a: f64 = valid_num!(a);
-	let f_b: f64 = valid_num!(b);
-	let result = f_a <= f_b;
-	return __repr_bool(result);
-}
\ No newline at end of file
+pub extern fn __av_lt(env: &mut Environment, a: u64, b: u64) -> u64 {
+	match compare_values(env, a, b) {
+		Ok(order) => __repr_bool(order == Ordering::Less),
+		Err(err) => err
+	}
+}
+
+#[no_mangle]
+pub extern fn __av_lte(env: &mut Environment, a: u64, b: u64) -> u64 {
+	match compare_values(env, a, b) {
+		Ok(order) => __repr_bool(order != Ordering::Greater),
+		Err(err) => err
+	}
+}
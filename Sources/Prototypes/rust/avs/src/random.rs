@@ -0,0 +1,206 @@
+use crate::environment::Environment;
+use crate::constants::*;
+use crate::collections::build_list;
+use crate::stats::list_values;
+use crate::numeric::int_arg;
+use fnv::FnvHashMap;
+
+/*
+Seeded random numbers. i.e. random(), random_int(1, 6), shuffle([1, 2, 3])
+Each cell draws from its own stream, derived from the session seed, the cell ID and how many
+times that cell has been explicitly re-run. Re-evaluating the notebook after an unrelated edit
+gives a cell the same values again. Re-running the cell itself moves it to a new stream.
+The generator is SplitMix64. It's fast and well distributed, but not for cryptographic use.
+*/
+
+const GOLDEN_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+// SplitMix64 output function. Also used to mix the seed, cell and run into a starting state.
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    return z ^ (z >> 31)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RandomSource {
+    pub seed: u64,
+    // Cell ID -> Number of explicit re-runs. Missing cells haven't been re-run.
+    pub runs: FnvHashMap<u64, u64>,
+    state: u64
+}
+
+impl RandomSource {
+    pub fn new(seed: u64, runs: FnvHashMap<u64, u64>) -> RandomSource {
+        return RandomSource {
+            seed: seed,
+            runs: runs,
+            state: mix(seed)
+        }
+    }
+
+    // Called before evaluating each cell
+    pub fn start_cell(&mut self, cell_id: u64) {
+        let run = *self.runs.get(&cell_id).unwrap_or(&0);
+        self.state = mix(mix(mix(self.seed) ^ cell_id) ^ run);
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(GOLDEN_GAMMA);
+        return mix(self.state)
+    }
+
+    // Uniform in [0, 1), using the top 53 bits
+    pub fn next_f64(&mut self) -> f64 {
+        return (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // Uniform in [0, bound). Rejects the biased tail rather than using a modulo directly.
+    pub fn next_below(&mut self, bound: u64) -> u64 {
+        let zone = u64::MAX - (u64::MAX % bound);
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return value % bound
+            }
+        }
+    }
+}
+
+// random(). A number in [0, 1)
+pub fn __av_random(env: &mut Environment, args: &[u64]) -> u64 {
+    if args.len() != 0 {
+        return RUNTIME_ERR_FN_ARITY
+    }
+    return env.random.next_f64().to_bits()
+}
+
+// random_int(low, high). Both ends are included, like a die roll.
+pub fn __av_random_int(env: &mut Environment, low: u64, high: u64) -> u64 {
+    let (low, high) = match (int_arg(low), int_arg(high)) {
+        (Ok(low), Ok(high)) => (low, high),
+        (Err(err), _) | (_, Err(err)) => return err
+    };
+    if low > high {
+        return RUNTIME_ERR_INVALID_ARG
+    }
+    let offset = env.random.next_below((high - low) as u64 + 1);
+    return ((low + offset as i64) as f64).to_bits()
+}
+
+// choice(list). One element, picked uniformly.
+pub fn __av_choice(env: &mut Environment, list: u64) -> u64 {
+    let values = match list_values(env, list) {
+        Ok(values) => values,
+        Err(err) => return err
+    };
+    if values.len() == 0 {
+        return RUNTIME_ERR_INVALID_ARG
+    }
+    let index = env.random.next_below(values.len() as u64) as usize;
+    return values[index]
+}
+
+// Fisher-Yates. Only the first count positions are shuffled, which is all sample needs.
+fn shuffle_prefix(env: &mut Environment, values: &mut Vec<u64>, count: usize) {
+    for index in 0..count.min(values.len().saturating_sub(1)) {
+        let other = index + env.random.next_below((values.len() - index) as u64) as usize;
+        values.swap(index, other);
+    }
+}
+
+// shuffle(list). Returns a new list. The original is unchanged.
+pub fn __av_shuffle(env: &mut Environment, list: u64) -> u64 {
+    let mut values = match list_values(env, list) {
+        Ok(values) => values,
+        Err(err) => return err
+    };
+    let count = values.len();
+    shuffle_prefix(env, &mut values, count);
+    return build_list(env, values)
+}
+
+// sample(list, k). k distinct elements, in random order.
+pub fn __av_sample(env: &mut Environment, list: u64, count: u64) -> u64 {
+    let mut values = match list_values(env, list) {
+        Ok(values) => values,
+        Err(err) => return err
+    };
+    let count = match int_arg(count) {
+        Ok(count) => count,
+        Err(err) => return err
+    };
+    if count < 0 || count as usize > values.len() {
+        return RUNTIME_ERR_INVALID_ARG
+    }
+    shuffle_prefix(env, &mut values, count as usize);
+    values.truncate(count as usize);
+    return build_list(env, values)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn num(value: f64) -> u64 {
+        return value.to_bits()
+    }
+
+    fn draws(source: &mut RandomSource, cell_id: u64) -> Vec<u64> {
+        source.start_cell(cell_id);
+        return (0..4).map(|_| source.next_u64()).collect()
+    }
+
+    #[test]
+    fn test_streams() {
+        let mut source = RandomSource::new(42, FnvHashMap::default());
+        let first = draws(&mut source, 1);
+        // The same cell restarts the same stream, regardless of other cells
+        draws(&mut source, 2);
+        assert_eq!(draws(&mut source, 1), first);
+        assert_ne!(draws(&mut source, 2), first);
+
+        source.runs.insert(1, 1);
+        assert_ne!(draws(&mut source, 1), first);
+        let mut other_seed = RandomSource::new(7, FnvHashMap::default());
+        assert_ne!(draws(&mut other_seed, 1), first);
+    }
+
+    #[test]
+    fn test_ranges() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        env.random.start_cell(1);
+        for _ in 0..100 {
+            let roll = f64::from_bits(__av_random_int(&mut env, num(1.0), num(6.0)));
+            assert!(roll >= 1.0 && roll <= 6.0 && roll.fract() == 0.0);
+            let fraction = f64::from_bits(__av_random(&mut env, &[]));
+            assert!(fraction >= 0.0 && fraction < 1.0);
+        }
+        assert_eq!(__av_random_int(&mut env, num(3.0), num(3.0)), num(3.0));
+        assert_eq!(__av_random_int(&mut env, num(6.0), num(1.0)), RUNTIME_ERR_INVALID_ARG);
+        assert_eq!(__av_random(&mut env, &[num(1.0)]), RUNTIME_ERR_FN_ARITY);
+    }
+
+    #[test]
+    fn test_permutations() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let values: Vec<u64> = (0..10).map(|value| num(value as f64)).collect();
+        let list = build_list(&mut env, values.clone());
+        let shuffled = __av_shuffle(&mut env, list);
+        let mut sorted = list_values(&env, shuffled).unwrap();
+        sorted.sort_by(|a, b| f64::from_bits(*a).total_cmp(&f64::from_bits(*b)));
+        assert_eq!(sorted, values);
+
+        let sampled = __av_sample(&mut env, list, num(3.0));
+        let sample = list_values(&env, sampled).unwrap();
+        assert_eq!(sample.len(), 3);
+        assert!(sample.iter().all(|value| values.contains(value)));
+        assert!(sample[0] != sample[1] && sample[1] != sample[2] && sample[0] != sample[2]);
+        assert_eq!(__av_sample(&mut env, list, num(11.0)), RUNTIME_ERR_INVALID_ARG);
+
+        let empty = build_list(&mut env, vec![]);
+        assert_eq!(__av_choice(&mut env, empty), RUNTIME_ERR_INVALID_ARG);
+        assert!(values.contains(&__av_choice(&mut env, list)));
+    }
+}
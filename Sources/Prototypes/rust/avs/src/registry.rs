@@ -0,0 +1,218 @@
+use crate::environment::Environment;
+use crate::structs::Atom;
+use crate::constants::{HOST_SYMBOL_START, MAX_HOST_FUNCTIONS};
+use crate::functions::{NativeFn, HostFn};
+use crate::runtime::{BUILTIN_MODULES, RESERVED_SYMBOLS};
+use std::rc::Rc;
+
+/*
+Native functions provided by the application embedding Arevel. i.e. a price lookup backed by
+the host's own data. Functions are registered once on a FunctionRegistry, then bound next to
+the builtins for each evaluation. Cells call them, pass them around and receive their errors
+exactly like builtins, without the host needing to fork avs.
+*/
+
+// Why a function couldn't be registered
+#[derive(Debug, PartialEq)]
+pub enum RegistryError {
+    // Not something a cell could refer to. i.e. "price lookup"
+    InvalidName,
+    // Already a builtin, keyword or registered function. Names are case insensitive.
+    DuplicateName,
+    // A required parameter follows an optional one
+    InvalidSignature,
+    // No symbols left in the range reserved for host functions
+    TooMany
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: String,
+    // Optional parameters are left off the end of the call
+    pub optional: bool
+}
+
+impl Param {
+    pub fn required(name: &str) -> Param {
+        return Param { name: name.to_string(), optional: false }
+    }
+
+    pub fn optional(name: &str) -> Param {
+        return Param { name: name.to_string(), optional: true }
+    }
+}
+
+#[derive(Clone)]
+pub struct HostFunction {
+    pub name: String,
+    pub params: Vec<Param>,
+    // Shown to users alongside the signature
+    pub docs: String,
+    // Same arguments always give the same result, without side effects.
+    // Pure calls may be answered from a cache rather than calling the host again.
+    pub pure: bool,
+    pub func: Rc<dyn Fn(&mut Environment, &[u64]) -> u64>
+}
+
+impl HostFunction {
+    // i.e. "price(symbol, date?)"
+    pub fn signature(&self) -> String {
+        let params: Vec<String> = self.params.iter()
+            .map(|param| if param.optional { format!("{}?", param.name) } else { param.name.clone() })
+            .collect();
+        return format!("{}({})", self.name, params.join(", "))
+    }
+
+    fn to_atom(&self) -> Atom {
+        let min_args = self.params.iter().filter(|param| !param.optional).count();
+        return Atom::FunctionValue(NativeFn::Host(HostFn {
+            func: self.func.clone(),
+            min_args: min_args,
+            max_args: self.params.len(),
+            pure: self.pure
+        }))
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    match name.chars().next() {
+        Some(first) if first.is_alphabetic() || first == '_' => {
+            name.chars().all(|ch| ch.is_alphanumeric() || ch == '_')
+        },
+        _ => false
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct FunctionRegistry {
+    functions: Vec<HostFunction>
+}
+
+impl FunctionRegistry {
+    pub fn new() -> FunctionRegistry {
+        return FunctionRegistry::default()
+    }
+
+    pub fn register(&mut self, function: HostFunction) -> Result<(), RegistryError> {
+        if !is_valid_name(&function.name) {
+            return Err(RegistryError::InvalidName)
+        }
+        let is_taken = |name: &str| name.eq_ignore_ascii_case(&function.name);
+        if BUILTIN_MODULES.iter().any(|module| is_taken(module.name)) ||
+            RESERVED_SYMBOLS.iter().any(|keyword| is_taken(keyword.name)) ||
+            self.functions.iter().any(|existing| is_taken(&existing.name)) {
+            return Err(RegistryError::DuplicateName)
+        }
+        if function.params.windows(2).any(|pair| pair[0].optional && !pair[1].optional) {
+            return Err(RegistryError::InvalidSignature)
+        }
+        if self.functions.len() >= MAX_HOST_FUNCTIONS {
+            return Err(RegistryError::TooMany)
+        }
+        self.functions.push(function);
+        return Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&HostFunction> {
+        return self.functions.iter().find(|function| function.name.eq_ignore_ascii_case(name))
+    }
+
+    // In registration order. For listing docs and signatures.
+    pub fn functions(&self) -> &[HostFunction] {
+        return &self.functions
+    }
+
+    // Called after the builtins are bound, before cells are lexed
+    pub fn bind(&self, env: &mut Environment) {
+        for (index, function) in self.functions.iter().enumerate() {
+            let symbol = HOST_SYMBOL_START + index as u64;
+            env.bind_name(symbol, function.name.clone());
+            env.bind_value(symbol, function.to_atom());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use crate::strings::text_arg;
+    use crate::constants::*;
+    use crate::functions::{call_host, MAX_HOST_CACHE_ENTRIES};
+
+    fn price_lookup(calls: Rc<Cell<u32>>, pure: bool) -> HostFunction {
+        return HostFunction {
+            name: String::from("price"),
+            params: vec![Param::required("symbol"), Param::optional("date")],
+            docs: String::from("Closing price for a ticker symbol"),
+            pure: pure,
+            func: Rc::new(move |env: &mut Environment, args: &[u64]| {
+                calls.set(calls.get() + 1);
+                match text_arg(env, args[0]) {
+                    Ok(symbol) if &*symbol == "ACME" => (12.5f64).to_bits(),
+                    Ok(_) => RUNTIME_ERR_INVALID_ARG,
+                    Err(err) => err
+                }
+            })
+        }
+    }
+
+    #[test]
+    fn test_register() {
+        let calls = Rc::new(Cell::new(0));
+        let mut registry = FunctionRegistry::new();
+        assert_eq!(registry.register(price_lookup(calls.clone(), true)), Ok(()));
+        assert_eq!(registry.get("PRICE").unwrap().signature(), "price(symbol, date?)");
+        assert_eq!(registry.register(price_lookup(calls.clone(), true)), Err(RegistryError::DuplicateName));
+
+        let mut renamed = price_lookup(calls.clone(), true);
+        renamed.name = String::from("Sum");
+        assert_eq!(registry.register(renamed.clone()), Err(RegistryError::DuplicateName));
+        renamed.name = String::from("and");
+        assert_eq!(registry.register(renamed.clone()), Err(RegistryError::DuplicateName));
+        renamed.name = String::from("price lookup");
+        assert_eq!(registry.register(renamed.clone()), Err(RegistryError::InvalidName));
+        renamed.name = String::from("price_on");
+        renamed.params = vec![Param::optional("date"), Param::required("symbol")];
+        assert_eq!(registry.register(renamed), Err(RegistryError::InvalidSignature));
+        assert_eq!(registry.functions().len(), 1);
+    }
+
+    #[test]
+    fn test_cache() {
+        let calls = Rc::new(Cell::new(0));
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let host = match price_lookup(calls.clone(), true).to_atom() {
+            Atom::FunctionValue(NativeFn::Host(host)) => host,
+            _ => panic!("Expected a host function")
+        };
+        assert_eq!((host.min_args, host.max_args), (1, 2));
+        let acme = env.init_string(String::from("ACME"));
+        assert_eq!(call_host(&mut env, 1, &host, &[acme]), (12.5f64).to_bits());
+        assert_eq!(call_host(&mut env, 1, &host, &[acme]), (12.5f64).to_bits());
+        assert_eq!(calls.get(), 1);
+        // Errors are always recomputed
+        let other = env.init_string(String::from("OTHER"));
+        call_host(&mut env, 1, &host, &[other]);
+        call_host(&mut env, 1, &host, &[other]);
+        assert_eq!(calls.get(), 3);
+
+        let impure = match price_lookup(calls.clone(), false).to_atom() {
+            Atom::FunctionValue(NativeFn::Host(host)) => host,
+            _ => panic!("Expected a host function")
+        };
+        call_host(&mut env, 2, &impure, &[acme]);
+        call_host(&mut env, 2, &impure, &[acme]);
+        assert_eq!(calls.get(), 5);
+
+        // Once full, new results are computed but not remembered
+        env.host_cache.clear();
+        for i in 0..MAX_HOST_CACHE_ENTRIES {
+            env.host_cache.insert((3, vec![(i as f64).to_bits()]), 0);
+        }
+        call_host(&mut env, 1, &host, &[acme]);
+        call_host(&mut env, 1, &host, &[acme]);
+        assert_eq!(calls.get(), 7);
+        assert_eq!(env.host_cache.len(), MAX_HOST_CACHE_ENTRIES);
+    }
+}
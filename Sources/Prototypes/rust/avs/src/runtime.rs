@@ -6,7 +6,7 @@ use fnv::FnvHashMap;
 
 // Ordered by their symbol ID for table lookup
 #[cfg(not(target_os = "unknown"))]
-pub const RESERVED_SYMBOLS: [&'static Keyword; 29] = [ 
+pub const RESERVED_SYMBOLS: [&'static Keyword; 30] = [ 
     &SYMBOL_COMMA, &SYMBOL_EQUALS,
     &SYMBOL_OR, &SYMBOL_AND, &SYMBOL_NOT, 
     &SYMBOL_DBL_EQUALS, &SYMBOL_NOT_EQUALS, 
@@ -18,12 +18,30 @@ pub const RESERVED_SYMBOLS: [&'static Keyword; 29] = [
     &SYMBOL_OPEN_BRACE, &SYMBOL_CLOSE_BRACE, 
     &SYMBOL_COLON, &SYMBOL_SEMI_COLON, 
     &SYMBOL_TRUE, &SYMBOL_FALSE, &SYMBOL_NONE,
-    &SYMBOL_CALL_FN
+    &SYMBOL_CALL_FN, &SYMBOL_CARET
 ];
 
-pub const BUILTIN_MODULES: [&'static Module; 8] = [
+pub const BUILTIN_MODULES: [&'static Module; 92] = [
     &AV_FN_MIN, &AV_FN_MAX, &AV_FN_ABS, &AV_FN_CEIL, 
-    &AV_FN_FLOOR, &AV_FN_TRUNC, &AV_FN_ROUND, &AV_FN_SQRT
+    &AV_FN_FLOOR, &AV_FN_TRUNC, &AV_FN_ROUND, &AV_FN_SQRT,
+    &AV_FN_DEFAULT, &AV_FN_IS_ERROR, &AV_FN_LEN,
+    &AV_FN_DECIMAL, &AV_FN_DECIMAL_ROUND,
+    &AV_FN_DATE, &AV_FN_DATETIME, &AV_FN_DAYS, &AV_FN_HOURS, &AV_FN_MINUTES, &AV_FN_SECONDS,
+    &AV_FN_GET, &AV_FN_PUSH, &AV_FN_SET,
+    &AV_FN_POW, &AV_FN_EXP, &AV_FN_LN, &AV_FN_LOG10, &AV_FN_LOG2,
+    &AV_FN_SIN, &AV_FN_COS, &AV_FN_TAN, &AV_FN_ASIN, &AV_FN_ACOS, &AV_FN_ATAN, &AV_FN_ATAN2,
+    &AV_FN_HYPOT, &AV_FN_CLAMP, &AV_FN_SIGN, &AV_FN_GCD, &AV_FN_LCM,
+    &AV_CONST_PI, &AV_CONST_E, &AV_CONST_TAU,
+    &AV_FN_UPPER, &AV_FN_LOWER, &AV_FN_TRIM, &AV_FN_SPLIT, &AV_FN_JOIN, &AV_FN_REPLACE,
+    &AV_FN_CONTAINS, &AV_FN_STARTS_WITH, &AV_FN_ENDS_WITH, &AV_FN_SLICE, &AV_FN_REPEAT,
+    &AV_FN_PAD_LEFT, &AV_FN_PAD_RIGHT, &AV_FN_CHAR, &AV_FN_CODEPOINT,
+    &AV_FN_SUM, &AV_FN_MEAN, &AV_FN_MEDIAN, &AV_FN_MODE, &AV_FN_STDEV, &AV_FN_STDEV_P,
+    &AV_FN_VARIANCE, &AV_FN_VARIANCE_P, &AV_FN_PERCENTILE, &AV_FN_COUNT, &AV_FN_COUNT_IF, &AV_FN_CUMSUM,
+    &AV_FN_PMT, &AV_FN_PV, &AV_FN_FV, &AV_FN_NPER, &AV_FN_RATE, &AV_FN_NPV, &AV_FN_IRR, &AV_FN_XIRR,
+    &AV_FN_RANDOM, &AV_FN_RANDOM_INT, &AV_FN_CHOICE, &AV_FN_SHUFFLE, &AV_FN_SAMPLE,
+    &AV_FN_MATCHES, &AV_FN_FIND, &AV_FN_FIND_ALL, &AV_FN_CAPTURES, &AV_FN_REPLACE_REGEX,
+    &AV_FN_CONVERT, &AV_FN_FORMAT,
+    &AV_FN_SORT, &AV_FN_SORT_DESC, &AV_FN_SORT_BY
 ];
 // todo: path, query
 
@@ -85,6 +103,10 @@ lazy_static! {
         m.insert(RUNTIME_ERR_UNK_VAL, "The code tried to read from an unknown value.");
         m.insert(RUNTIME_ERR_CIRCULAR_DEP, "There's a circular reference between these cells.");
         m.insert(RUNTIME_ERR_EXPECTED_STR, "Arevel expects some text value here.");
+        m.insert(RUNTIME_ERR_INVALID_ARG, "This value is outside the range the function accepts.");
+        m.insert(RUNTIME_ERR_UNK_FIELD, "This value doesn't have an attribute with that name.");
+        m.insert(RUNTIME_ERR_DOMAIN, "The function isn't defined for this value. i.e. the square root of a negative number.");
+        m.insert(RUNTIME_ERR_DIMENSION, "These units measure different things, so they can't be combined. i.e. a distance and a weight.");
         m.insert(RUNTIME_ERR_DIV_Z, "Dividing by zero is undefined. Make sure the denominator is not a zero before dividing.");
 
         // TODO
@@ -92,6 +114,12 @@ lazy_static! {
         m.insert(RUNTIME_ERR_FN_ARITY, "Unexpected number of parameters.");
         m.insert(RUNTIME_ERR_FN_EXPECTED, "Arevel expect a valid function here.");
 
+        m.insert(INTERPRETER_ERR_LIMIT_STEPS, "This cell took too many steps to run, so Arevel stopped it.");
+        m.insert(INTERPRETER_ERR_LIMIT_DEPTH, "This value refers to other values too many levels deep.");
+        m.insert(INTERPRETER_ERR_LIMIT_MEMORY, "This cell created too many values, so Arevel stopped it.");
+        m.insert(INTERPRETER_ERR_TIMEOUT, "This cell took too long to run, so Arevel stopped it.");
+        m.insert(INTERPRETER_ERR_LIMIT_ITERATIONS, "Arevel couldn't find an answer that settles within the iteration limit. Try a different guess.");
+
         m
     };
 
@@ -0,0 +1,464 @@
+use crate::environment::Environment;
+use crate::structs::Atom;
+use crate::constants::*;
+use crate::types::*;
+use crate::utils::is_inline_str;
+use crate::numeric::{atom_number, compare_numbers, Number};
+use crate::temporal::date_to_datetime;
+use crate::units::Dimension;
+use crate::collections::{build_list, __av_get};
+use crate::operators::__av_dot;
+use crate::functions::{call_native, NativeFn};
+use crate::stats::list_values;
+use core::cmp::Ordering;
+use chrono::{DateTime, FixedOffset, Duration};
+use std::rc::Rc;
+
+/*
+Sorting lists. i.e. sort([3, 1, 2]), sort_by(people, "age"), sort_by(words, len)
+Every value has a place in the order, so mixed lists sort rather than fail. As in spreadsheets,
+numbers come first, then quantities, dates, durations, text, booleans, lists and anything else.
+NaN and errors always go last, NaN before errors, in either direction.
+Text ignores case, except to break ties.
+Sorts are stable, so ties keep their original order. sort_by takes a list of keys for multi-key sorts.
+*/
+
+// The part of a value which decides its position. Keys are computed once per element.
+#[derive(Debug)]
+enum SortKey {
+    Number(Number),
+    Quantity(Dimension, f64),
+    // Dates compare as midnight UTC, as they do with <
+    Instant(DateTime<FixedOffset>),
+    Duration(Duration),
+    // Case-folded first, then the original text so "Apple" and "apple" still have an order
+    Text(String, Rc<str>),
+    Keyword(u64),
+    List(Vec<SortKey>),
+    Other,
+    NaN,
+    Error
+}
+
+impl SortKey {
+    fn rank(&self) -> u8 {
+        match self {
+            SortKey::Number(_) => 0,
+            SortKey::Quantity(_, _) => 1,
+            SortKey::Instant(_) => 2,
+            SortKey::Duration(_) => 3,
+            SortKey::Text(..) => 4,
+            SortKey::Keyword(_) => 5,
+            SortKey::List(_) => 6,
+            SortKey::Other => 7,
+            SortKey::NaN => 8,
+            SortKey::Error => 9
+        }
+    }
+
+    fn is_missing(&self) -> bool {
+        match self {
+            SortKey::NaN | SortKey::Error => true,
+            _ => false
+        }
+    }
+}
+
+fn sort_key(env: &Environment, value: u64) -> SortKey {
+    let number = f64::from_bits(value);
+    if !is_nan(number) {
+        return SortKey::Number(Number::Float(number))
+    }
+    if is_error(value) {
+        return SortKey::Error
+    }
+    // Keywords would otherwise resolve as NaN
+    if is_symbol(value) && !is_inline_str(value) {
+        return SortKey::Keyword(value)
+    }
+    let atom = resolve_atom!(env, value);
+    if let Some(number) = atom_number(&atom) {
+        return match number {
+            Number::Float(_) => SortKey::NaN,
+            number => SortKey::Number(number)
+        }
+    }
+    match atom {
+        Atom::QuantityValue(quantity) => SortKey::Quantity(quantity.unit.dimension, quantity.value),
+        Atom::DateValue(date) => SortKey::Instant(date_to_datetime(date)),
+        Atom::DateTimeValue(datetime) => SortKey::Instant(datetime),
+        Atom::DurationValue(duration) => SortKey::Duration(duration),
+        Atom::StringValue(text) => SortKey::Text(text.to_lowercase(), text),
+        Atom::ListValue(list) => SortKey::List(list.iter().map(|element| sort_key(env, *element)).collect()),
+        _ => SortKey::Other
+    }
+}
+
+// -inf and inf bound every number, including decimals which can't represent them
+fn infinity_rank(number: &Number) -> i8 {
+    match number {
+        Number::Float(f_val) if f_val.is_infinite() => if *f_val > 0.0 { 1 } else { -1 },
+        _ => 0
+    }
+}
+
+fn compare_number_keys(a: &Number, b: &Number) -> Ordering {
+    let (rank_a, rank_b) = (infinity_rank(a), infinity_rank(b));
+    if rank_a != 0 || rank_b != 0 {
+        return rank_a.cmp(&rank_b)
+    }
+    return compare_numbers(a, b).unwrap_or(Ordering::Equal)
+}
+
+fn keyword_rank(keyword: u64) -> u64 {
+    if keyword == SYMBOL_FALSE.symbol {
+        return 0
+    } else if keyword == SYMBOL_TRUE.symbol {
+        return 1
+    }
+    return keyword
+}
+
+fn compare_keys(a: &SortKey, b: &SortKey) -> Ordering {
+    match (a, b) {
+        (SortKey::Number(n_a), SortKey::Number(n_b)) => compare_number_keys(n_a, n_b),
+        // Different dimensions are grouped, rather than compared
+        (SortKey::Quantity(d_a, v_a), SortKey::Quantity(d_b, v_b)) => {
+            d_a.cmp(d_b).then((v_a + 0.0).total_cmp(&(v_b + 0.0)))
+        },
+        (SortKey::Instant(i_a), SortKey::Instant(i_b)) => i_a.cmp(i_b),
+        (SortKey::Duration(d_a), SortKey::Duration(d_b)) => d_a.cmp(d_b),
+        (SortKey::Text(f_a, t_a), SortKey::Text(f_b, t_b)) => f_a.cmp(f_b).then(t_a.cmp(t_b)),
+        (SortKey::Keyword(k_a), SortKey::Keyword(k_b)) => keyword_rank(*k_a).cmp(&keyword_rank(*k_b)),
+        (SortKey::List(l_a), SortKey::List(l_b)) => {
+            for (e_a, e_b) in l_a.iter().zip(l_b.iter()) {
+                let order = compare_keys(e_a, e_b);
+                if order != Ordering::Equal {
+                    return order
+                }
+            }
+            l_a.len().cmp(&l_b.len())
+        },
+        _ => a.rank().cmp(&b.rank())
+    }
+}
+
+// Descending reverses the order of values, but missing values stay at the end
+fn compare_directed(a: &SortKey, b: &SortKey, descending: bool) -> Ordering {
+    let order = compare_keys(a, b);
+    if descending && !a.is_missing() && !b.is_missing() {
+        return order.reverse()
+    }
+    return order
+}
+
+fn compare_rows(a: &[SortKey], b: &[SortKey], descending: &[bool]) -> Ordering {
+    for ((key_a, key_b), is_descending) in a.iter().zip(b.iter()).zip(descending.iter()) {
+        let order = compare_directed(key_a, key_b, *is_descending);
+        if order != Ordering::Equal {
+            return order
+        }
+    }
+    return Ordering::Equal
+}
+
+enum KeySpec {
+    // Called with each element. i.e. len
+    Function(u64, NativeFn),
+    // A map key, list index or record field. i.e. "age"
+    Field(u64)
+}
+
+fn key_spec(env: &Environment, key: u64) -> Result<KeySpec, u64> {
+    if is_error(key) {
+        return Err(key)
+    }
+    if is_number(key) {
+        return Ok(KeySpec::Field(key))
+    }
+    match resolve_atom!(env, key) {
+        Atom::FunctionValue(func) => Ok(KeySpec::Function(key, func)),
+        Atom::StringValue(_) => Ok(KeySpec::Field(key)),
+        _ => Err(RUNTIME_ERR_INVALID_ARG)
+    }
+}
+
+// A single key or a list of them
+fn key_specs(env: &Environment, keys: u64) -> Result<Vec<KeySpec>, u64> {
+    let specs = match resolve_atom!(env, keys) {
+        Atom::ListValue(list) => list.iter().map(|key| key_spec(env, *key)).collect::<Result<Vec<KeySpec>, u64>>()?,
+        _ => vec![key_spec(env, keys)?]
+    };
+    if specs.is_empty() {
+        return Err(RUNTIME_ERR_INVALID_ARG)
+    }
+    return Ok(specs)
+}
+
+fn is_descending(env: &Environment, order: u64) -> Result<bool, u64> {
+    if is_error(order) {
+        return Err(order)
+    }
+    match resolve_atom!(env, order) {
+        Atom::StringValue(name) => {
+            match name.to_ascii_lowercase().as_str() {
+                "asc" => Ok(false),
+                "desc" => Ok(true),
+                _ => Err(RUNTIME_ERR_INVALID_ARG)
+            }
+        },
+        _ => Err(RUNTIME_ERR_EXPECTED_STR)
+    }
+}
+
+// "asc", "desc", or a list with one of those per key
+fn directions(env: &Environment, order: u64, key_count: usize) -> Result<Vec<bool>, u64> {
+    if let Atom::ListValue(list) = resolve_atom!(env, order) {
+        if list.len() != key_count {
+            return Err(RUNTIME_ERR_INVALID_ARG)
+        }
+        return list.iter().map(|direction| is_descending(env, *direction)).collect()
+    }
+    return Ok(vec![is_descending(env, order)?; key_count])
+}
+
+fn extract_key(env: &mut Environment, spec: &KeySpec, value: u64) -> u64 {
+    match spec {
+        KeySpec::Function(symbol, func) => call_native(env, *symbol, func, &[value]),
+        KeySpec::Field(field) => {
+            if is_error(value) {
+                return value
+            }
+            match resolve_atom!(env, value) {
+                Atom::MapValue(_) | Atom::ListValue(_) => __av_get(env, value, *field),
+                _ => __av_dot(env, value, *field)
+            }
+        }
+    }
+}
+
+// With no key specs, values are sorted by themselves
+fn sort_list(env: &mut Environment, list: u64, specs: &[KeySpec], descending: &[bool]) -> Result<u64, u64> {
+    let values = list_values(env, list)?;
+    let mut rows: Vec<(Vec<SortKey>, u64)> = Vec::with_capacity(values.len());
+    for value in values {
+        let mut keys: Vec<SortKey> = Vec::with_capacity(descending.len());
+        if specs.is_empty() {
+            keys.push(sort_key(env, value));
+        }
+        for spec in specs.iter() {
+            let key = extract_key(env, spec, value);
+            keys.push(sort_key(env, key));
+        }
+        rows.push((keys, value));
+    }
+    rows.sort_by(|(keys_a, _), (keys_b, _)| compare_rows(keys_a, keys_b, descending));
+    let sorted: Vec<u64> = rows.into_iter().map(|(_, value)| value).collect();
+    return Ok(build_list(env, sorted))
+}
+
+fn to_result(result: Result<u64, u64>) -> u64 {
+    match result {
+        Ok(value) => value,
+        Err(err) => err
+    }
+}
+
+pub fn __av_sort(env: &mut Environment, list: u64) -> u64 {
+    return to_result(sort_list(env, list, &[], &[false]))
+}
+
+pub fn __av_sort_desc(env: &mut Environment, list: u64) -> u64 {
+    return to_result(sort_list(env, list, &[], &[true]))
+}
+
+fn sort_by(env: &mut Environment, args: &[u64]) -> Result<u64, u64> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(RUNTIME_ERR_FN_ARITY)
+    }
+    let specs = key_specs(env, args[1])?;
+    let descending = if args.len() == 3 { directions(env, args[2], specs.len())? } else { vec![false; specs.len()] };
+    return sort_list(env, args[0], &specs, &descending)
+}
+
+// sort_by(list, key) or sort_by(list, key, order). The key is a function, a field name or a list index.
+// A list of keys sorts by each in turn. The order is "asc", "desc", or a list with one per key.
+pub fn __av_sort_by(env: &mut Environment, args: &[u64]) -> u64 {
+    return to_result(sort_by(env, args))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::build_map;
+    use crate::functions::NativeFn1;
+    use crate::functions::__av_len;
+    use proptest::prelude::*;
+
+    fn num(value: f64) -> u64 {
+        return value.to_bits()
+    }
+
+    fn sorted(env: &mut Environment, list: u64) -> Vec<u64> {
+        let result = __av_sort(env, list);
+        return list_values(env, result).unwrap()
+    }
+
+    #[test]
+    fn test_mixed_types() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let pear = env.init_string(String::from("pear"));
+        let apple = env.init_string(String::from("apple"));
+        let values = vec![pear, SYMBOL_TRUE.symbol, RUNTIME_ERR_DIV_Z, num(f64::NAN), num(10.0), SYMBOL_FALSE.symbol, apple, num(2.0)];
+        let list = build_list(&mut env, values);
+        assert_eq!(sorted(&mut env, list), vec![num(2.0), num(10.0), apple, pear,
+            SYMBOL_FALSE.symbol, SYMBOL_TRUE.symbol, num(f64::NAN), RUNTIME_ERR_DIV_Z]);
+
+        // Missing values stay last when descending
+        let descending = __av_sort_desc(&mut env, list);
+        assert_eq!(list_values(&env, descending).unwrap(), vec![SYMBOL_TRUE.symbol, SYMBOL_FALSE.symbol,
+            pear, apple, num(10.0), num(2.0), num(f64::NAN), RUNTIME_ERR_DIV_Z]);
+
+        assert_eq!(__av_sort(&mut env, num(1.0)), RUNTIME_ERR_INVALID_TYPE);
+        assert_eq!(__av_sort(&mut env, RUNTIME_ERR_DIV_Z), RUNTIME_ERR_DIV_Z);
+    }
+
+    #[test]
+    fn test_text_case() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let zebra = env.init_string(String::from("Zebra"));
+        let apple = env.init_string(String::from("apple"));
+        let upper_apple = env.init_string(String::from("Apple"));
+        let list = build_list(&mut env, vec![zebra, apple, upper_apple]);
+        // Case only breaks ties
+        assert_eq!(sorted(&mut env, list), vec![upper_apple, apple, zebra]);
+    }
+
+    #[test]
+    fn test_sort_by() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let name = env.init_string(String::from("name"));
+        let team = env.init_string(String::from("team"));
+        let mut people: Vec<u64> = Vec::new();
+        for (person, group) in [("ada", 2.0), ("grace", 1.0), ("alan", 2.0), ("edsger", 1.0)].iter() {
+            let person = env.init_string(person.to_string());
+            people.push(build_map(&mut env, vec![name, person, team, num(*group)]));
+        }
+        let list = build_list(&mut env, people.clone());
+
+        // Ties keep their original order
+        let by_team = __av_sort_by(&mut env, &[list, team]);
+        assert_eq!(list_values(&env, by_team).unwrap(), vec![people[1], people[3], people[0], people[2]]);
+
+        let keys = build_list(&mut env, vec![team, name]);
+        let desc = env.init_string(String::from("desc"));
+        let asc = env.init_string(String::from("asc"));
+        let orders = build_list(&mut env, vec![desc, asc]);
+        let by_both = __av_sort_by(&mut env, &[list, keys, orders]);
+        assert_eq!(list_values(&env, by_both).unwrap(), vec![people[0], people[2], people[3], people[1]]);
+
+        let len_symbol = env.init_value(NativeFn1::create_atom(__av_len));
+        let by_name_desc = __av_sort_by(&mut env, &[list, name, desc]);
+        assert_eq!(list_values(&env, by_name_desc).unwrap(), vec![people[1], people[3], people[2], people[0]]);
+        let words = list_values(&env, list).unwrap().iter().map(|person| __av_get(&mut env, *person, name)).collect::<Vec<u64>>();
+        let word_list = build_list(&mut env, words.clone());
+        let by_length = __av_sort_by(&mut env, &[word_list, len_symbol]);
+        assert_eq!(list_values(&env, by_length).unwrap(), vec![words[0], words[2], words[1], words[3]]);
+
+        assert_eq!(__av_sort_by(&mut env, &[list, team, team]), RUNTIME_ERR_INVALID_ARG);
+        // A single order applies to every key
+        let both_desc = build_list(&mut env, vec![desc, desc]);
+        let expected = __av_sort_by(&mut env, &[list, keys, both_desc]);
+        let actual = __av_sort_by(&mut env, &[list, keys, desc]);
+        assert_eq!(list_values(&env, actual).unwrap(), list_values(&env, expected).unwrap());
+        assert_eq!(__av_sort_by(&mut env, &[list]), RUNTIME_ERR_FN_ARITY);
+    }
+
+    // A value of each kind the order distinguishes
+    #[derive(Debug, Clone)]
+    enum Sample {
+        Number(f64),
+        Text(String),
+        Keyword(bool),
+        Error(u64)
+    }
+
+    fn sample() -> impl Strategy<Value = Sample> {
+        prop_oneof![
+            1 => Just(Sample::Number(f64::NAN)),
+            3 => (prop::num::f64::NORMAL | prop::num::f64::INFINITE | prop::num::f64::ZERO).prop_map(Sample::Number),
+            2 => (-3i32..3).prop_map(|value| Sample::Number(value as f64)),
+            3 => "[a-cA-C]{0,12}".prop_map(Sample::Text),
+            1 => any::<bool>().prop_map(Sample::Keyword),
+            1 => prop::sample::select(vec![RUNTIME_ERR_DIV_Z, RUNTIME_ERR_INVALID_ARG]).prop_map(Sample::Error)
+        ]
+    }
+
+    fn init_sample(env: &mut Environment, sample: &Sample) -> u64 {
+        match sample {
+            Sample::Number(value) => value.to_bits(),
+            Sample::Text(text) => env.init_string(text.clone()),
+            Sample::Keyword(true) => SYMBOL_TRUE.symbol,
+            Sample::Keyword(false) => SYMBOL_FALSE.symbol,
+            Sample::Error(err) => *err
+        }
+    }
+
+    fn init_samples(env: &mut Environment, samples: &[Sample]) -> Vec<u64> {
+        return samples.iter().map(|sample| init_sample(env, sample)).collect()
+    }
+
+    proptest! {
+        #[test]
+        fn prop_sort_is_ordered_permutation(samples in prop::collection::vec(sample(), 0..60), descending in any::<bool>()) {
+            let mut env = Environment::new(APP_SYMBOL_START);
+            let values = init_samples(&mut env, &samples);
+            let list = build_list(&mut env, values.clone());
+            let result = if descending { __av_sort_desc(&mut env, list) } else { __av_sort(&mut env, list) };
+            let output = list_values(&env, result).unwrap();
+
+            let mut expected = values.clone();
+            let mut actual = output.clone();
+            expected.sort();
+            actual.sort();
+            prop_assert_eq!(actual, expected);
+            for pair in output.windows(2) {
+                let order = compare_directed(&sort_key(&env, pair[0]), &sort_key(&env, pair[1]), descending);
+                prop_assert_ne!(order, Ordering::Greater);
+            }
+        }
+
+        #[test]
+        fn prop_order_is_total(a in sample(), b in sample(), c in sample()) {
+            let mut env = Environment::new(APP_SYMBOL_START);
+            let values = init_samples(&mut env, &[a, b, c]);
+            let keys: Vec<SortKey> = values.iter().map(|value| sort_key(&env, *value)).collect();
+            let (a, b, c) = (&keys[0], &keys[1], &keys[2]);
+            prop_assert_eq!(compare_keys(a, a), Ordering::Equal);
+            prop_assert_eq!(compare_keys(a, b), compare_keys(b, a).reverse());
+            if compare_keys(a, b) != Ordering::Greater && compare_keys(b, c) != Ordering::Greater {
+                prop_assert_ne!(compare_keys(a, c), Ordering::Greater);
+            }
+        }
+
+        #[test]
+        fn prop_sort_by_is_stable(groups in prop::collection::vec(0i32..4, 0..60)) {
+            // Rows of [group, original position], sorted by group alone
+            let mut env = Environment::new(APP_SYMBOL_START);
+            let rows: Vec<u64> = groups.iter().enumerate()
+                .map(|(index, group)| build_list(&mut env, vec![num(*group as f64), num(index as f64)]))
+                .collect();
+            let list = build_list(&mut env, rows);
+            let result = __av_sort_by(&mut env, &[list, num(0.0)]);
+            let mut positions: Vec<(f64, f64)> = Vec::new();
+            for row in list_values(&env, result).unwrap() {
+                let fields = list_values(&env, row).unwrap();
+                positions.push((f64::from_bits(fields[0]), f64::from_bits(fields[1])));
+            }
+            for pair in positions.windows(2) {
+                prop_assert!(pair[0].0 < pair[1].0 || (pair[0].0 == pair[1].0 && pair[0].1 < pair[1].1));
+            }
+        }
+    }
+}
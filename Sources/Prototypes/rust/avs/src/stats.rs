@@ -0,0 +1,473 @@
+use crate::environment::Environment;
+use crate::structs::Atom;
+use crate::constants::*;
+use crate::types::*;
+use crate::numeric::atom_number;
+use crate::collections::build_list;
+use core::cmp::Ordering;
+use std::rc::Rc;
+use fnv::FnvHashMap;
+
+/*
+Statistics and aggregation over lists. i.e. mean([1, 2, 3])
+Aggregates take an optional policy for entries which aren't numbers:
+"propagate" (default) returns the first error, or RUNTIME_ERR_EXPECTED_NUM for other values.
+"skip" ignores errors and non-numeric entries, like spreadsheet AVERAGE ignores text.
+Averages, percentiles and extremes of an empty list are a division by zero, like the mean.
+Sample statistics of fewer than two values are a division by zero too, as in spreadsheets.
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Policy {
+    Propagate,
+    Skip
+}
+
+impl Policy {
+    pub fn from_name(name: &str) -> Option<Policy> {
+        match name.to_ascii_lowercase().as_str() {
+            "propagate" => Some(Policy::Propagate),
+            "skip" => Some(Policy::Skip),
+            _ => None
+        }
+    }
+}
+
+pub(crate) fn list_values(env: &Environment, list: u64) -> Result<Vec<u64>, u64> {
+    if is_error(list) {
+        return Err(list)
+    }
+    match resolve_atom!(env, list) {
+        Atom::ListValue(values) => Ok(values.iter().cloned().collect()),
+        _ => Err(RUNTIME_ERR_INVALID_TYPE)
+    }
+}
+
+fn value_number(env: &Environment, value: u64) -> Option<f64> {
+    let f_value = f64::from_bits(value);
+    if !is_nan(f_value) {
+        return Some(f_value)
+    }
+    if is_error(value) {
+        return None
+    }
+    return atom_number(&resolve_atom!(env, value)).map(|number| number.to_f64())
+}
+
+pub(crate) fn collect_numbers(env: &Environment, values: &[u64], policy: Policy) -> Result<Vec<f64>, u64> {
+    let mut numbers: Vec<f64> = Vec::with_capacity(values.len());
+    for value in values.iter() {
+        match value_number(env, *value) {
+            Some(number) => numbers.push(number),
+            None if policy == Policy::Skip => {},
+            None if is_error(*value) => return Err(*value),
+            None => return Err(RUNTIME_ERR_EXPECTED_NUM)
+        }
+    }
+    return Ok(numbers)
+}
+
+// Arguments are the list, any extra arguments, then an optional policy. i.e. percentile(list, p, "skip")
+fn list_numbers(env: &Environment, args: &[u64], extra_args: usize) -> Result<Vec<f64>, u64> {
+    if args.len() < 1 + extra_args || args.len() > 2 + extra_args {
+        return Err(RUNTIME_ERR_FN_ARITY)
+    }
+    let policy = if args.len() == 2 + extra_args {
+        let policy_arg = args[args.len() - 1];
+        if is_error(policy_arg) {
+            return Err(policy_arg)
+        }
+        match resolve_atom!(env, policy_arg) {
+            Atom::StringValue(name) => Policy::from_name(&name).ok_or(RUNTIME_ERR_INVALID_ARG)?,
+            _ => return Err(RUNTIME_ERR_EXPECTED_STR)
+        }
+    } else {
+        Policy::Propagate
+    };
+    return collect_numbers(env, &list_values(env, args[0])?, policy)
+}
+
+// Compensated (Neumaier) summation, so sum([0.1, 0.2, 0.3]) is 0.6
+#[derive(Debug, Default)]
+struct RunningSum {
+    total: f64,
+    compensation: f64
+}
+
+impl RunningSum {
+    fn add(&mut self, number: f64) {
+        let next = self.total + number;
+        if self.total.abs() >= number.abs() {
+            self.compensation += (self.total - next) + number;
+        } else {
+            self.compensation += (number - next) + self.total;
+        }
+        self.total = next;
+    }
+
+    fn value(&self) -> f64 {
+        return self.total + self.compensation
+    }
+}
+
+fn sum(numbers: &[f64]) -> f64 {
+    let mut running = RunningSum::default();
+    for number in numbers.iter() {
+        running.add(*number);
+    }
+    return running.value()
+}
+
+fn non_empty(numbers: &[f64]) -> Result<(), u64> {
+    if numbers.len() == 0 {
+        return Err(RUNTIME_ERR_DIV_Z)
+    }
+    return Ok(())
+}
+
+fn mean(numbers: &[f64]) -> Result<f64, u64> {
+    non_empty(numbers)?;
+    return Ok(sum(numbers) / numbers.len() as f64)
+}
+
+// Sample variance divides by n - 1, population variance by n.
+fn variance(numbers: &[f64], is_sample: bool) -> Result<f64, u64> {
+    let denominator = if is_sample { numbers.len() as f64 - 1.0 } else { numbers.len() as f64 };
+    if denominator <= 0.0 {
+        return Err(RUNTIME_ERR_DIV_Z)
+    }
+    let average = mean(numbers)?;
+    let squares: Vec<f64> = numbers.iter().map(|number| (number - average) * (number - average)).collect();
+    return Ok(sum(&squares) / denominator)
+}
+
+fn sorted(mut numbers: Vec<f64>) -> Vec<f64> {
+    numbers.sort_by(|a, b| a.total_cmp(b));
+    return numbers
+}
+
+// Linear interpolation between closest ranks. Matches spreadsheet PERCENTILE.INC
+fn percentile(numbers: Vec<f64>, fraction: f64) -> Result<f64, u64> {
+    if !(0.0..=1.0).contains(&fraction) {
+        return Err(RUNTIME_ERR_INVALID_ARG)
+    }
+    non_empty(&numbers)?;
+    let numbers = sorted(numbers);
+    let rank = fraction * (numbers.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    return Ok(numbers[lower] + (numbers[upper] - numbers[lower]) * (rank - lower as f64))
+}
+
+fn to_result(result: Result<f64, u64>) -> u64 {
+    match result {
+        Ok(number) => number.to_bits(),
+        Err(err) => err
+    }
+}
+
+macro_rules! numbers_or_return {
+    ($env:expr, $args:expr, $extra_args:expr) => ({
+        match list_numbers($env, $args, $extra_args) {
+            Ok(numbers) => numbers,
+            Err(err) => return err
+        }
+    })
+}
+
+pub fn __av_sum(env: &mut Environment, args: &[u64]) -> u64 {
+    let numbers = numbers_or_return!(env, args, 0);
+    return sum(&numbers).to_bits()
+}
+
+pub fn __av_mean(env: &mut Environment, args: &[u64]) -> u64 {
+    let numbers = numbers_or_return!(env, args, 0);
+    return to_result(mean(&numbers))
+}
+
+pub fn __av_median(env: &mut Environment, args: &[u64]) -> u64 {
+    let numbers = numbers_or_return!(env, args, 0);
+    return to_result(percentile(numbers, 0.5))
+}
+
+// The most frequent value. Ties go to the value which appears first.
+// Undefined when no value repeats, like spreadsheet MODE.
+pub fn __av_mode(env: &mut Environment, args: &[u64]) -> u64 {
+    let numbers = numbers_or_return!(env, args, 0);
+    if let Err(err) = non_empty(&numbers) {
+        return err
+    }
+    // -0 and 0 count as the same value
+    let mut counts: FnvHashMap<u64, usize> = FnvHashMap::default();
+    for number in numbers.iter() {
+        *counts.entry((number + 0.0).to_bits()).or_insert(0) += 1;
+    }
+    let mut best: Option<(f64, usize)> = None;
+    for number in numbers.iter() {
+        let count = counts[&(number + 0.0).to_bits()];
+        if best.map_or(true, |(_, best_count)| count > best_count) {
+            best = Some((*number, count));
+        }
+    }
+    match best {
+        Some((number, count)) if count > 1 => number.to_bits(),
+        _ => RUNTIME_ERR_DOMAIN
+    }
+}
+
+pub fn __av_variance(env: &mut Environment, args: &[u64]) -> u64 {
+    let numbers = numbers_or_return!(env, args, 0);
+    return to_result(variance(&numbers, true))
+}
+
+pub fn __av_variance_p(env: &mut Environment, args: &[u64]) -> u64 {
+    let numbers = numbers_or_return!(env, args, 0);
+    return to_result(variance(&numbers, false))
+}
+
+pub fn __av_stdev(env: &mut Environment, args: &[u64]) -> u64 {
+    let numbers = numbers_or_return!(env, args, 0);
+    return to_result(variance(&numbers, true).map(|var| var.sqrt()))
+}
+
+pub fn __av_stdev_p(env: &mut Environment, args: &[u64]) -> u64 {
+    let numbers = numbers_or_return!(env, args, 0);
+    return to_result(variance(&numbers, false).map(|var| var.sqrt()))
+}
+
+// percentile(list, fraction). i.e. percentile(scores, 0.9)
+pub fn __av_percentile(env: &mut Environment, args: &[u64]) -> u64 {
+    let numbers = numbers_or_return!(env, args, 1);
+    let fraction: f64 = valid_num!(args[1]);
+    return to_result(percentile(numbers, fraction))
+}
+
+// Running totals. i.e. cumsum([1, 2, 3]) = [1, 3, 6]
+pub fn __av_cumsum(env: &mut Environment, args: &[u64]) -> u64 {
+    let numbers = numbers_or_return!(env, args, 0);
+    let mut totals: Vec<u64> = Vec::with_capacity(numbers.len());
+    let mut running = RunningSum::default();
+    for number in numbers.iter() {
+        running.add(*number);
+        totals.push(running.value().to_bits());
+    }
+    return build_list(env, totals)
+}
+
+// Counts the numbers in a list. Text and errors aren't counted, like spreadsheet COUNT.
+pub fn __av_count(env: &mut Environment, list: u64) -> u64 {
+    match list_values(env, list) {
+        Ok(values) => {
+            let count = values.iter().filter(|value| value_number(env, **value).is_some()).count();
+            (count as f64).to_bits()
+        },
+        Err(err) => err
+    }
+}
+
+// min(list), min(list, "skip") or min(a, b, ...). Errors in separate arguments propagate.
+fn extreme(env: &mut Environment, args: &[u64], want: Ordering) -> u64 {
+    let is_list = match args.first() {
+        Some(first) if !is_error(*first) => matches!(resolve_atom!(env, *first), Atom::ListValue(_)),
+        _ => false
+    };
+    let numbers = if is_list {
+        numbers_or_return!(env, args, 0)
+    } else if args.len() == 0 {
+        return RUNTIME_ERR_FN_ARITY
+    } else {
+        match collect_numbers(env, args, Policy::Propagate) {
+            Ok(numbers) => numbers,
+            Err(err) => return err
+        }
+    };
+    let best = numbers.into_iter().fold(None, |best: Option<f64>, number| {
+        match best {
+            Some(current) if number.partial_cmp(&current) != Some(want) => Some(current),
+            _ => Some(number)
+        }
+    });
+    return best.map_or(RUNTIME_ERR_DIV_Z, |number| number.to_bits())
+}
+
+pub fn __av_min(env: &mut Environment, args: &[u64]) -> u64 {
+    return extreme(env, args, Ordering::Less)
+}
+
+pub fn __av_max(env: &mut Environment, args: &[u64]) -> u64 {
+    return extreme(env, args, Ordering::Greater)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+    Number(f64),
+    Text(String)
+}
+
+// Spreadsheet style criteria. i.e. 5, ">=10", "<>done", "apple"
+// Text matches are case insensitive.
+#[derive(Debug, Clone, PartialEq)]
+struct Criteria {
+    // The orderings which match. i.e. >= is [Greater, Equal]
+    matches: Vec<Ordering>,
+    target: Target
+}
+
+const CRITERIA_OPERATORS: [(&str, &[Ordering]); 6] = [
+    (">=", &[Ordering::Greater, Ordering::Equal]),
+    ("<=", &[Ordering::Less, Ordering::Equal]),
+    ("<>", &[Ordering::Less, Ordering::Greater]),
+    (">", &[Ordering::Greater]),
+    ("<", &[Ordering::Less]),
+    ("=", &[Ordering::Equal])
+];
+
+fn parse_criteria(env: &Environment, criteria: u64) -> Result<Criteria, u64> {
+    if let Some(number) = value_number(env, criteria) {
+        return Ok(Criteria { matches: vec![Ordering::Equal], target: Target::Number(number) })
+    }
+    let text: Rc<str> = match resolve_atom!(env, criteria) {
+        Atom::StringValue(text) => text,
+        _ => return Err(RUNTIME_ERR_EXPECTED_STR)
+    };
+    let (matches, operand) = CRITERIA_OPERATORS.iter()
+        .find(|(prefix, _)| text.starts_with(prefix))
+        .map(|(prefix, matches)| (matches.to_vec(), &text[prefix.len()..]))
+        .unwrap_or((vec![Ordering::Equal], &text[..]));
+    let target = match operand.trim().parse::<f64>() {
+        Ok(number) => Target::Number(number),
+        Err(_) => Target::Text(operand.to_lowercase())
+    };
+    return Ok(Criteria { matches: matches, target: target })
+}
+
+fn criteria_matches(env: &Environment, criteria: &Criteria, value: u64) -> bool {
+    if is_error(value) {
+        return false
+    }
+    let order = match &criteria.target {
+        Target::Number(target) => value_number(env, value).and_then(|number| number.partial_cmp(target)),
+        Target::Text(target) => {
+            match resolve_atom!(env, value) {
+                Atom::StringValue(text) => Some(text.to_lowercase().as_str().cmp(target.as_str())),
+                _ => None
+            }
+        }
+    };
+    match order {
+        Some(order) => criteria.matches.contains(&order),
+        // Values of a different type are only matched by <>
+        None => criteria.matches.len() == 2 && !criteria.matches.contains(&Ordering::Equal)
+    }
+}
+
+// count_if(list, criteria). i.e. count_if(scores, ">=50")
+pub fn __av_count_if(env: &mut Environment, list: u64, criteria: u64) -> u64 {
+    if is_error(criteria) {
+        return criteria
+    }
+    let values = match list_values(env, list) {
+        Ok(values) => values,
+        Err(err) => return err
+    };
+    let criteria = match parse_criteria(env, criteria) {
+        Ok(criteria) => criteria,
+        Err(err) => return err
+    };
+    let count = values.iter().filter(|value| criteria_matches(env, &criteria, **value)).count();
+    return (count as f64).to_bits()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn num(value: f64) -> u64 {
+        return value.to_bits()
+    }
+
+    fn list(env: &mut Environment, values: &[u64]) -> u64 {
+        return build_list(env, values.to_vec())
+    }
+
+    #[test]
+    fn test_summaries() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let values = list(&mut env, &[num(2.0), num(4.0), num(4.0), num(4.0), num(5.0), num(5.0), num(7.0), num(9.0)]);
+        assert_eq!(__av_mean(&mut env, &[values]), num(5.0));
+        assert_eq!(__av_median(&mut env, &[values]), num(4.5));
+        assert_eq!(__av_mode(&mut env, &[values]), num(4.0));
+        assert_eq!(__av_stdev_p(&mut env, &[values]), num(2.0));
+        assert_eq!(__av_variance(&mut env, &[values]), num(32.0 / 7.0));
+        assert_eq!(__av_percentile(&mut env, &[values, num(0.25)]), num(4.0));
+        assert_eq!(__av_percentile(&mut env, &[values, num(1.5)]), RUNTIME_ERR_INVALID_ARG);
+
+        let decimals = list(&mut env, &[num(0.1), num(0.2), num(0.3)]);
+        assert_eq!(__av_sum(&mut env, &[decimals]), num(0.6));
+
+        let single = list(&mut env, &[num(1.0)]);
+        assert_eq!(__av_stdev(&mut env, &[single]), RUNTIME_ERR_DIV_Z);
+        let empty = list(&mut env, &[]);
+        assert_eq!(__av_mean(&mut env, &[empty]), RUNTIME_ERR_DIV_Z);
+        assert_eq!(__av_median(&mut env, &[empty]), RUNTIME_ERR_DIV_Z);
+        assert_eq!(__av_mode(&mut env, &[empty]), RUNTIME_ERR_DIV_Z);
+        assert_eq!(__av_sum(&mut env, &[empty]), num(0.0));
+        // Nothing repeats
+        let distinct = list(&mut env, &[num(1.0), num(2.0), num(3.0)]);
+        assert_eq!(__av_mode(&mut env, &[distinct]), RUNTIME_ERR_DOMAIN);
+        // Running totals are compensated the same way
+        let totals = __av_cumsum(&mut env, &[decimals]);
+        assert_eq!(list_values(&env, totals).unwrap()[2], num(0.6));
+    }
+
+    #[test]
+    fn test_policy() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let text = env.init_string(String::from("n/a"));
+        let values = list(&mut env, &[num(1.0), RUNTIME_ERR_DIV_Z, text, num(3.0)]);
+        assert_eq!(__av_sum(&mut env, &[values]), RUNTIME_ERR_DIV_Z);
+        let skip = env.init_string(String::from("skip"));
+        assert_eq!(__av_sum(&mut env, &[values, skip]), num(4.0));
+        assert_eq!(__av_percentile(&mut env, &[values, num(1.0), skip]), num(3.0));
+        assert_eq!(__av_count(&mut env, values), num(2.0));
+        let without_errors = list(&mut env, &[num(1.0), text]);
+        assert_eq!(__av_mean(&mut env, &[without_errors]), RUNTIME_ERR_EXPECTED_NUM);
+        let unknown = env.init_string(String::from("ignore"));
+        assert_eq!(__av_mean(&mut env, &[values, unknown]), RUNTIME_ERR_INVALID_ARG);
+    }
+
+    #[test]
+    fn test_count_if() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let done = env.init_string(String::from("Done"));
+        let todo = env.init_string(String::from("todo"));
+        let values = list(&mut env, &[num(10.0), num(50.0), num(75.0), done, todo, RUNTIME_ERR_DIV_Z]);
+        let at_least = env.init_string(String::from(">=50"));
+        assert_eq!(__av_count_if(&mut env, values, at_least), num(2.0));
+        assert_eq!(__av_count_if(&mut env, values, num(10.0)), num(1.0));
+        let is_done = env.init_string(String::from("done"));
+        assert_eq!(__av_count_if(&mut env, values, is_done), num(1.0));
+        // Text and numbers both count as not equal to "done"
+        let not_done = env.init_string(String::from("<>done"));
+        assert_eq!(__av_count_if(&mut env, values, not_done), num(4.0));
+    }
+
+    #[test]
+    fn test_min_max() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        assert_eq!(__av_min(&mut env, &[num(3.0), num(-1.0), num(2.0)]), num(-1.0));
+        let values = list(&mut env, &[num(3.0), num(8.0)]);
+        assert_eq!(__av_max(&mut env, &[values]), num(8.0));
+        assert_eq!(__av_max(&mut env, &[num(1.0), RUNTIME_ERR_DIV_Z]), RUNTIME_ERR_DIV_Z);
+        assert_eq!(__av_max(&mut env, &[]), RUNTIME_ERR_FN_ARITY);
+        let empty = list(&mut env, &[]);
+        assert_eq!(__av_max(&mut env, &[empty]), RUNTIME_ERR_DIV_Z);
+
+        let text = env.init_string(String::from("n/a"));
+        let mixed = list(&mut env, &[num(3.0), text, num(-2.0)]);
+        assert_eq!(__av_min(&mut env, &[mixed]), RUNTIME_ERR_EXPECTED_NUM);
+        let skip = env.init_string(String::from("skip"));
+        assert_eq!(__av_min(&mut env, &[mixed, skip]), num(-2.0));
+    }
+}
@@ -0,0 +1,304 @@
+use crate::environment::Environment;
+use crate::structs::Atom;
+use crate::constants::*;
+use crate::types::*;
+use crate::collections::build_list;
+use crate::format::repr_float;
+use crate::numeric::int_arg;
+use std::rc::Rc;
+
+/*
+String standard library.
+Arguments may be inline or heap strings. Both resolve to the same StringValue.
+Indexes and widths count characters rather than bytes, matching len().
+*/
+
+// Upper bound on strings built by repeat, padding, join and replace, so a typo can't exhaust memory.
+pub const MAX_STRING_CHARS: usize = 1 << 24;
+
+// Also used by host functions to read their text arguments.
+pub fn text_arg(env: &Environment, value: u64) -> Result<Rc<str>, u64> {
+    if is_error(value) {
+        return Err(value)
+    }
+    match resolve_atom!(env, value) {
+        Atom::StringValue(text) => Ok(text),
+        _ => Err(RUNTIME_ERR_EXPECTED_STR)
+    }
+}
+
+macro_rules! text_or_return {
+    ($env:expr, $val:expr) => ({
+        match text_arg($env, $val) {
+            Ok(text) => text,
+            Err(err) => return err
+        }
+    })
+}
+
+macro_rules! int_or_return {
+    ($val:expr) => ({
+        match int_arg($val) {
+            Ok(num) => num,
+            Err(err) => return err
+        }
+    })
+}
+
+pub fn __av_upper(env: &mut Environment, text: u64) -> u64 {
+    let text = text_or_return!(env, text);
+    return env.init_string(text.to_uppercase())
+}
+
+pub fn __av_lower(env: &mut Environment, text: u64) -> u64 {
+    let text = text_or_return!(env, text);
+    return env.init_string(text.to_lowercase())
+}
+
+pub fn __av_trim(env: &mut Environment, text: u64) -> u64 {
+    let text = text_or_return!(env, text);
+    return env.init_string(text.trim().to_string())
+}
+
+// split(text, separator). An empty separator splits into characters.
+pub fn __av_split(env: &mut Environment, text: u64, separator: u64) -> u64 {
+    let text = text_or_return!(env, text);
+    let separator = text_or_return!(env, separator);
+    let parts: Vec<String> = if separator.len() == 0 {
+        text.chars().map(|ch| ch.to_string()).collect()
+    } else {
+        text.split(&*separator).map(|part| part.to_string()).collect()
+    };
+    let mut values: Vec<u64> = Vec::with_capacity(parts.len());
+    for part in parts {
+        values.push(env.init_string(part));
+    }
+    return build_list(env, values)
+}
+
+// join(list, separator). Numbers are joined as they'd be displayed.
+pub fn __av_join(env: &mut Environment, list: u64, separator: u64) -> u64 {
+    if is_error(list) {
+        return list
+    }
+    let separator = text_or_return!(env, separator);
+    let values = match resolve_atom!(env, list) {
+        Atom::ListValue(values) => values,
+        _ => return RUNTIME_ERR_INVALID_TYPE
+    };
+    let mut parts: Vec<String> = Vec::with_capacity(values.len());
+    let mut length = separator.chars().count().saturating_mul(values.len().saturating_sub(1));
+    for value in values.iter() {
+        let f_value = f64::from_bits(*value);
+        if !is_nan(f_value) {
+            parts.push(repr_float(f_value));
+        } else {
+            parts.push(text_or_return!(env, *value).to_string());
+        }
+        length = length.saturating_add(parts[parts.len() - 1].chars().count());
+        if length > MAX_STRING_CHARS {
+            return INTERPRETER_ERR_LIMIT_MEMORY
+        }
+    }
+    return env.init_string(parts.join(&*separator))
+}
+
+// replace(text, find, replacement). Replaces every occurrence.
+pub fn __av_replace(env: &mut Environment, text: u64, find: u64, replacement: u64) -> u64 {
+    let text = text_or_return!(env, text);
+    let find = text_or_return!(env, find);
+    let replacement = text_or_return!(env, replacement);
+    if find.len() == 0 {
+        return RUNTIME_ERR_INVALID_ARG
+    }
+    let matches = text.matches(&*find).count();
+    let length = (text.chars().count() - matches * find.chars().count())
+        .saturating_add(matches.saturating_mul(replacement.chars().count()));
+    if length > MAX_STRING_CHARS {
+        return INTERPRETER_ERR_LIMIT_MEMORY
+    }
+    return env.init_string(text.replace(&*find, &replacement))
+}
+
+pub fn __av_contains(env: &mut Environment, text: u64, part: u64) -> u64 {
+    let text = text_or_return!(env, text);
+    let part = text_or_return!(env, part);
+    return __repr_bool(text.contains(&*part))
+}
+
+pub fn __av_starts_with(env: &mut Environment, text: u64, prefix: u64) -> u64 {
+    let text = text_or_return!(env, text);
+    let prefix = text_or_return!(env, prefix);
+    return __repr_bool(text.starts_with(&*prefix))
+}
+
+pub fn __av_ends_with(env: &mut Environment, text: u64, suffix: u64) -> u64 {
+    let text = text_or_return!(env, text);
+    let suffix = text_or_return!(env, suffix);
+    return __repr_bool(text.ends_with(&*suffix))
+}
+
+// Negative indexes count back from the end. Out of range indexes are clamped.
+fn char_position(index: i64, len: usize) -> usize {
+    let position = if index < 0 { len as i64 + index } else { index };
+    return position.max(0).min(len as i64) as usize
+}
+
+// slice(text, start) or slice(text, start, end). The end is exclusive.
+pub fn __av_slice(env: &mut Environment, args: &[u64]) -> u64 {
+    if args.len() < 2 || args.len() > 3 {
+        return RUNTIME_ERR_FN_ARITY
+    }
+    let text = text_or_return!(env, args[0]);
+    let chars: Vec<char> = text.chars().collect();
+    let start = char_position(int_or_return!(args[1]), chars.len());
+    let end = if args.len() == 3 { char_position(int_or_return!(args[2]), chars.len()) } else { chars.len() };
+    if end <= start {
+        return SYMBOL_EMPTY_STR
+    }
+    return env.init_string(chars[start..end].iter().collect())
+}
+
+pub fn __av_repeat(env: &mut Environment, text: u64, count: u64) -> u64 {
+    let text = text_or_return!(env, text);
+    let count = int_or_return!(count);
+    if count < 0 {
+        return RUNTIME_ERR_INVALID_ARG
+    }
+    if text.chars().count().saturating_mul(count as usize) > MAX_STRING_CHARS {
+        return INTERPRETER_ERR_LIMIT_MEMORY
+    }
+    return env.init_string(text.repeat(count as usize))
+}
+
+fn pad(env: &mut Environment, args: &[u64], at_start: bool) -> u64 {
+    if args.len() < 2 || args.len() > 3 {
+        return RUNTIME_ERR_FN_ARITY
+    }
+    let text = text_or_return!(env, args[0]);
+    let width = int_or_return!(args[1]);
+    let fill: char = if args.len() == 3 {
+        let fill_text = text_or_return!(env, args[2]);
+        let mut fill_chars = fill_text.chars();
+        match (fill_chars.next(), fill_chars.next()) {
+            (Some(ch), None) => ch,
+            _ => return RUNTIME_ERR_INVALID_ARG
+        }
+    } else {
+        ' '
+    };
+    if width < 0 {
+        return RUNTIME_ERR_INVALID_ARG
+    } else if width as usize > MAX_STRING_CHARS {
+        return INTERPRETER_ERR_LIMIT_MEMORY
+    }
+    let len = text.chars().count();
+    if len >= width as usize {
+        return args[0]
+    }
+    let padding: String = std::iter::repeat(fill).take(width as usize - len).collect();
+    if at_start {
+        return env.init_string(padding + &text)
+    }
+    return env.init_string(text.to_string() + &padding)
+}
+
+// pad_left(text, width) or pad_left(text, width, fill). Pads with spaces by default.
+pub fn __av_pad_left(env: &mut Environment, args: &[u64]) -> u64 {
+    return pad(env, args, true)
+}
+
+pub fn __av_pad_right(env: &mut Environment, args: &[u64]) -> u64 {
+    return pad(env, args, false)
+}
+
+// char(65) = "A"
+pub fn __av_char(env: &mut Environment, code: u64) -> u64 {
+    let code = int_or_return!(code);
+    if code < 0 || code > u32::MAX as i64 {
+        return RUNTIME_ERR_INVALID_ARG
+    }
+    match std::char::from_u32(code as u32) {
+        Some(ch) => env.init_string(ch.to_string()),
+        None => RUNTIME_ERR_INVALID_ARG
+    }
+}
+
+// codepoint("A") = 65. Uses the first character.
+pub fn __av_codepoint(env: &mut Environment, text: u64) -> u64 {
+    let text = text_or_return!(env, text);
+    match text.chars().next() {
+        Some(ch) => (ch as u32 as f64).to_bits(),
+        None => RUNTIME_ERR_INVALID_ARG
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn num(value: f64) -> u64 {
+        return value.to_bits()
+    }
+
+    fn text(env: &Environment, value: u64) -> String {
+        return text_arg(env, value).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_inline_and_heap() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let short = env.init_string(String::from(" Ab "));
+        let long = env.init_string(String::from("  Hello World  "));
+        let trimmed = __av_trim(&mut env, short);
+        assert_eq!(text(&env, trimmed), "Ab");
+        let upper = __av_upper(&mut env, long);
+        assert_eq!(text(&env, upper), "  HELLO WORLD  ");
+        let world = env.init_string(String::from("World"));
+        assert_eq!(__av_contains(&mut env, long, world), SYMBOL_TRUE.symbol);
+        assert_eq!(__av_starts_with(&mut env, long, world), SYMBOL_FALSE.symbol);
+        assert_eq!(__av_upper(&mut env, num(1.0)), RUNTIME_ERR_EXPECTED_STR);
+    }
+
+    #[test]
+    fn test_slice_chars() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let word = env.init_string(String::from("héllo wörld"));
+        let first = __av_slice(&mut env, &[word, num(0.0), num(5.0)]);
+        assert_eq!(text(&env, first), "héllo");
+        let last = __av_slice(&mut env, &[word, num(-5.0)]);
+        assert_eq!(text(&env, last), "wörld");
+        assert_eq!(__av_slice(&mut env, &[word, num(20.0)]), SYMBOL_EMPTY_STR);
+        assert_eq!(__av_slice(&mut env, &[word, num(1.5)]), RUNTIME_ERR_INVALID_ARG);
+        assert_eq!(__av_slice(&mut env, &[word]), RUNTIME_ERR_FN_ARITY);
+    }
+
+    #[test]
+    fn test_padding() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let digits = env.init_string(String::from("42"));
+        let zero = env.init_string(String::from("0"));
+        let padded = __av_pad_left(&mut env, &[digits, num(5.0), zero]);
+        assert_eq!(text(&env, padded), "00042");
+        let padded_right = __av_pad_right(&mut env, &[digits, num(4.0)]);
+        assert_eq!(text(&env, padded_right), "42  ");
+        assert_eq!(__av_pad_left(&mut env, &[digits, num(1.0)]), digits);
+        assert_eq!(__av_pad_left(&mut env, &[digits, num(5.0), digits]), RUNTIME_ERR_INVALID_ARG);
+        assert_eq!(__av_repeat(&mut env, digits, num(1e9)), INTERPRETER_ERR_LIMIT_MEMORY);
+    }
+
+    #[test]
+    fn test_length_limits() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let letter = env.init_string(String::from("a"));
+        let long = __av_repeat(&mut env, letter, num((1 << 20) as f64));
+        let wide = env.init_string(String::from("x".repeat(32)));
+        assert_eq!(__av_replace(&mut env, long, letter, wide), INTERPRETER_ERR_LIMIT_MEMORY);
+        let list = build_list(&mut env, vec![long; 20]);
+        assert_eq!(__av_join(&mut env, list, letter), INTERPRETER_ERR_LIMIT_MEMORY);
+        let short_list = build_list(&mut env, vec![letter, num(2.0)]);
+        let joined = __av_join(&mut env, short_list, wide);
+        assert_eq!(text(&env, joined).len(), 34);
+    }
+}
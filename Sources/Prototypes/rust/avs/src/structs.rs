@@ -2,6 +2,13 @@ use crate::environment::Environment;
 use crate::functions::NativeFn;
 use crate::utils::{create_string_pointer, create_pointer_symbol, truncate_symbol};
 use fnv::FnvHashMap;
+use std::rc::Rc;
+use num_bigint::BigInt;
+use crate::decimal::Decimal;
+use chrono::{NaiveDate, DateTime, FixedOffset, Duration};
+use im_rc::{Vector, OrdMap};
+use crate::collections::MapKey;
+use crate::units::Quantity;
 
 use crate::format::*;
 
@@ -18,8 +25,22 @@ pub enum ValueType {
 #[derive(PartialEq,Clone)]
 pub enum Atom {
     NumericValue(f64),
-    StringValue(String),
+    // Integers too large to represent exactly as f64
+    BigIntValue(BigInt),
+    // Base 10 numbers with a fixed number of decimal places
+    DecimalValue(Decimal),
+    DateValue(NaiveDate),
+    // Keeps the UTC offset it was created with
+    DateTimeValue(DateTime<FixedOffset>),
+    DurationValue(Duration),
+    // A number with a unit. i.e. 9.8 m/s^2
+    QuantityValue(Quantity),
+    // Shared rather than copied when read
+    StringValue(Rc<str>),
     SymbolValue(u64),
+    // Persistent. Clones share structure.
+    ListValue(Vector<u64>),
+    MapValue(OrdMap<MapKey, u64>),
     ObjectValue(AvObject),
     HashMapValue(FnvHashMap<u64, Atom>),
     FunctionValue(NativeFn)
@@ -110,6 +131,7 @@ impl AvObject {
 mod tests {
     use super::*;
     use crate::constants::*;
+    use crate::utils::string_pointer_len;
     extern crate test;
 
     use test::Bencher;
@@ -119,9 +141,25 @@ mod tests {
     fn test_symbol_type() {
         let mut env = Environment::new(APP_SYMBOL_START);
         // TODO: Test longer vs shorter string
-        let symbol_id = env.init_value(Atom::StringValue("Hello".to_string()));
+        let symbol_id = env.init_value(Atom::StringValue("Hello".into()));
         let symbol_header = symbol_id & VALHEAD_MASK;
         // assert_eq!(symbol_header, VALUE_T_PTR_STR);
         assert_eq!(symbol_header, VALUE_T_PTR_OBJ);
     }
+
+    #[test]
+    fn test_init_string() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        // Small strings are stored inline without allocating
+        let short_id = env.init_string("Hello".to_string());
+        assert_eq!(short_id & VALHEAD_MASK, VALUE_T_SYM_STR);
+        assert_eq!(env.init_string("".to_string()), SYMBOL_EMPTY_STR);
+        assert_eq!(env.budget.allocations, 0);
+
+        let long_id = env.init_string("Hello World".to_string());
+        assert_eq!(long_id & VALHEAD_MASK, VALUE_T_PTR_STR);
+        assert_eq!(string_pointer_len(long_id), Some(11));
+        assert_eq!(env.budget.allocations, 1);
+        assert_eq!(env.lookup(long_id).unwrap().value, Some(Atom::StringValue("Hello World".into())));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,243 @@
+use crate::structs::Identifier;
+use crate::constants::*;
+use fnv::FnvHashMap;
+
+/*
+Symbol table backends for Environment.
+Symbols are allocated sequentially from APP_SYMBOL_START, so most lookups can index
+directly into a vector rather than hashing. See benches/symbols.rs for the comparison.
+Builtins and anything outside the dense range fall back to a hash map.
+Ids freed by garbage collection are handed out again, so the vector only grows with the live set.
+*/
+
+pub trait SymbolStore {
+    fn get(&self, symbol: u64) -> Option<&Identifier>;
+    fn get_mut(&mut self, symbol: u64) -> Option<&mut Identifier>;
+    fn insert(&mut self, symbol: u64, identifier: Identifier);
+    // Keep only the symbols for which keep returns true
+    fn retain(&mut self, keep: &mut dyn FnMut(u64, &Identifier) -> bool);
+    fn len(&self) -> usize;
+    fn symbols(&self) -> Vec<u64>;
+    // An id freed by retain, to allocate again instead of growing the store
+    fn reuse_id(&mut self) -> Option<u64> {
+        return None
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct HashSymbolStore {
+    identifiers: FnvHashMap<u64, Identifier>
+}
+
+impl HashSymbolStore {
+    pub fn new() -> HashSymbolStore {
+        return HashSymbolStore::default()
+    }
+}
+
+impl SymbolStore for HashSymbolStore {
+    fn get(&self, symbol: u64) -> Option<&Identifier> {
+        return self.identifiers.get(&symbol)
+    }
+
+    fn get_mut(&mut self, symbol: u64) -> Option<&mut Identifier> {
+        return self.identifiers.get_mut(&symbol)
+    }
+
+    fn insert(&mut self, symbol: u64, identifier: Identifier) {
+        self.identifiers.insert(symbol, identifier);
+    }
+
+    fn retain(&mut self, keep: &mut dyn FnMut(u64, &Identifier) -> bool) {
+        self.identifiers.retain(|symbol, identifier| keep(*symbol, identifier));
+    }
+
+    fn len(&self) -> usize {
+        return self.identifiers.len()
+    }
+
+    fn symbols(&self) -> Vec<u64> {
+        return self.identifiers.keys().cloned().collect()
+    }
+}
+
+// Symbols past this many ids from the start go to the fallback map.
+pub const MAX_DENSE_SYMBOLS: u64 = 1 << 24;
+
+#[derive(Debug, Default)]
+pub struct DenseSymbolStore {
+    start: u64,
+    slots: Vec<Option<Identifier>>,
+    len: usize,
+    // Ids of collected slots
+    free: Vec<u64>,
+    fallback: HashSymbolStore
+}
+
+impl DenseSymbolStore {
+    pub fn new(start: u64) -> DenseSymbolStore {
+        return DenseSymbolStore {
+            start: start,
+            slots: Vec::new(),
+            len: 0,
+            free: Vec::new(),
+            fallback: HashSymbolStore::new()
+        }
+    }
+
+    // Object and string pointers share the id sequence, so index by the id in the low bits.
+    fn index(&self, symbol: u64) -> Option<usize> {
+        let header = symbol & VALHEAD_MASK;
+        if header != VALUE_T_PTR_OBJ && header != VALUE_T_PTR_STR {
+            return None
+        }
+        let id = symbol & LOW32_MASK;
+        if id < self.start || id - self.start >= MAX_DENSE_SYMBOLS {
+            return None
+        }
+        return Some((id - self.start) as usize)
+    }
+}
+
+impl SymbolStore for DenseSymbolStore {
+    fn get(&self, symbol: u64) -> Option<&Identifier> {
+        match self.index(symbol) {
+            Some(index) => {
+                match self.slots.get(index) {
+                    // Check the full symbol, since tags like the string length aren't part of the index
+                    Some(Some(identifier)) if identifier.symbol == symbol => Some(identifier),
+                    _ => None
+                }
+            },
+            None => self.fallback.get(symbol)
+        }
+    }
+
+    fn get_mut(&mut self, symbol: u64) -> Option<&mut Identifier> {
+        match self.index(symbol) {
+            Some(index) => {
+                match self.slots.get_mut(index) {
+                    Some(Some(identifier)) if identifier.symbol == symbol => Some(identifier),
+                    _ => None
+                }
+            },
+            None => self.fallback.get_mut(symbol)
+        }
+    }
+
+    fn insert(&mut self, symbol: u64, identifier: Identifier) {
+        match self.index(symbol) {
+            Some(index) => {
+                if index >= self.slots.len() {
+                    self.slots.resize_with(index + 1, || None);
+                }
+                if self.slots[index].is_none() {
+                    self.len += 1;
+                }
+                self.slots[index] = Some(identifier);
+            },
+            None => self.fallback.insert(symbol, identifier)
+        }
+    }
+
+    fn retain(&mut self, keep: &mut dyn FnMut(u64, &Identifier) -> bool) {
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            let is_kept = match slot {
+                Some(identifier) => keep(identifier.symbol, identifier),
+                None => true
+            };
+            if !is_kept {
+                *slot = None;
+                self.len -= 1;
+                self.free.push(self.start + index as u64);
+            }
+        }
+        self.fallback.retain(keep);
+    }
+
+    fn len(&self) -> usize {
+        return self.len + self.fallback.len()
+    }
+
+    fn symbols(&self) -> Vec<u64> {
+        let mut symbols: Vec<u64> = self.slots.iter()
+            .filter_map(|slot| slot.as_ref().map(|identifier| identifier.symbol))
+            .collect();
+        symbols.extend(self.fallback.symbols());
+        return symbols
+    }
+
+    fn reuse_id(&mut self) -> Option<u64> {
+        return self.free.pop()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{create_pointer_symbol, create_string_pointer};
+
+    fn identifier(symbol: u64) -> Identifier {
+        return Identifier {
+            symbol: symbol,
+            name: None,
+            value: None
+        }
+    }
+
+    fn check_store(store: &mut dyn SymbolStore) {
+        let first = create_pointer_symbol(APP_SYMBOL_START);
+        let string = create_string_pointer(create_pointer_symbol(APP_SYMBOL_START + 1), 12);
+        let builtin = AV_FN_MIN.symbol;
+        store.insert(first, identifier(first));
+        store.insert(string, identifier(string));
+        store.insert(builtin, identifier(builtin));
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.get(first).unwrap().symbol, first);
+        assert_eq!(store.get(string).unwrap().symbol, string);
+        assert_eq!(store.get(builtin).unwrap().symbol, builtin);
+        // Same id, without the string tag
+        assert!(store.get(create_pointer_symbol(APP_SYMBOL_START + 1)).is_none());
+        assert!(store.get(create_pointer_symbol(APP_SYMBOL_START + 100)).is_none());
+
+        store.get_mut(first).unwrap().name = Some(String::from("first"));
+        assert_eq!(store.get(first).unwrap().name, Some(String::from("first")));
+
+        store.retain(&mut |symbol, _| symbol != string);
+        assert_eq!(store.len(), 2);
+        assert!(store.get(string).is_none());
+        let mut symbols = store.symbols();
+        symbols.sort();
+        assert_eq!(symbols, vec![builtin, first]);
+    }
+
+    #[test]
+    fn test_hash_store() {
+        check_store(&mut HashSymbolStore::new());
+    }
+
+    #[test]
+    fn test_dense_store() {
+        check_store(&mut DenseSymbolStore::new(APP_SYMBOL_START));
+    }
+
+    #[test]
+    fn test_dense_store_reuse() {
+        let mut store = DenseSymbolStore::new(APP_SYMBOL_START);
+        let mut next_id = APP_SYMBOL_START;
+        for _cycle in 0..10 {
+            for _ in 0..100 {
+                let id = store.reuse_id().unwrap_or_else(|| { next_id += 1; next_id - 1 });
+                let symbol = create_pointer_symbol(id);
+                store.insert(symbol, identifier(symbol));
+            }
+            assert_eq!(store.len(), 100);
+            store.retain(&mut |_, _| false);
+            assert_eq!(store.len(), 0);
+        }
+        // Collected slots are reused rather than appended
+        assert_eq!(store.slots.len(), 100);
+        assert_eq!(next_id, APP_SYMBOL_START + 100);
+    }
+}
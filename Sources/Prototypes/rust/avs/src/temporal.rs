@@ -0,0 +1,265 @@
+use crate::environment::Environment;
+use crate::structs::Atom;
+use crate::constants::*;
+use crate::numeric::Arithmetic;
+use crate::types::*;
+use core::cmp::Ordering;
+use chrono::{NaiveDate, NaiveDateTime, DateTime, FixedOffset, Duration, Datelike, Timelike, TimeZone};
+
+/*
+Dates, datetimes and durations.
+Dates are calendar days without a time zone. Datetimes carry their UTC offset, so they
+print the way they were entered but compare as instants. Mixing a date with a datetime
+treats the date as midnight UTC.
+*/
+
+pub fn parse_date(text: &str) -> Option<NaiveDate> {
+    return NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d").ok()
+}
+
+// RFC 3339. i.e. 2020-01-31T09:30:00+05:30. Datetimes without an offset are treated as UTC.
+pub fn parse_datetime(text: &str) -> Option<DateTime<FixedOffset>> {
+    let text = text.trim();
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(text) {
+        return Some(datetime)
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"].iter() {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(text, format) {
+            return Some(utc().from_utc_datetime(&naive))
+        }
+    }
+    // A plain date is midnight
+    return parse_date(text).map(date_to_datetime)
+}
+
+fn utc() -> FixedOffset {
+    return FixedOffset::east_opt(0).unwrap()
+}
+
+pub fn date_to_datetime(date: NaiveDate) -> DateTime<FixedOffset> {
+    return utc().from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+}
+
+pub fn is_temporal(atom: &Atom) -> bool {
+    match atom {
+        Atom::DateValue(_) | Atom::DateTimeValue(_) | Atom::DurationValue(_) => true,
+        _ => false
+    }
+}
+
+fn whole_days(duration: &Duration) -> Option<i64> {
+    if *duration == Duration::days(duration.num_days()) {
+        return Some(duration.num_days())
+    }
+    return None
+}
+
+fn add_to_date(date: NaiveDate, duration: Duration) -> Option<Atom> {
+    // Stays a date unless the duration includes a time of day
+    if let Some(days) = whole_days(&duration) {
+        return date.checked_add_signed(Duration::days(days)).map(Atom::DateValue)
+    }
+    return date_to_datetime(date).checked_add_signed(duration).map(Atom::DateTimeValue)
+}
+
+fn as_datetime(atom: &Atom) -> Option<DateTime<FixedOffset>> {
+    match atom {
+        Atom::DateValue(date) => Some(date_to_datetime(*date)),
+        Atom::DateTimeValue(datetime) => Some(*datetime),
+        _ => None
+    }
+}
+
+// + and - for temporal values. Returns None if neither side is temporal.
+pub fn temporal_arithmetic(env: &mut Environment, op: Arithmetic, a: u64, b: u64) -> Option<u64> {
+    // Temporal values are always boxed
+    if !is_pointer(a) && !is_pointer(b) {
+        return None
+    }
+    let atom_a = resolve_atom!(env, a);
+    let atom_b = resolve_atom!(env, b);
+    if !is_temporal(&atom_a) && !is_temporal(&atom_b) {
+        return None
+    }
+    return Some(apply_temporal(env, op, &atom_a, &atom_b))
+}
+
+pub fn apply_temporal(env: &mut Environment, op: Arithmetic, atom_a: &Atom, atom_b: &Atom) -> u64 {
+    let result: Option<Atom> = match (op, atom_a, atom_b) {
+        (Arithmetic::Add, Atom::DurationValue(d_a), Atom::DurationValue(d_b)) => d_a.checked_add(d_b).map(Atom::DurationValue),
+        (Arithmetic::Sub, Atom::DurationValue(d_a), Atom::DurationValue(d_b)) => d_a.checked_sub(d_b).map(Atom::DurationValue),
+        (Arithmetic::Add, Atom::DateValue(date), Atom::DurationValue(duration)) |
+        (Arithmetic::Add, Atom::DurationValue(duration), Atom::DateValue(date)) => add_to_date(*date, *duration),
+        (Arithmetic::Sub, Atom::DateValue(date), Atom::DurationValue(duration)) => add_to_date(*date, -*duration),
+        (Arithmetic::Add, Atom::DateTimeValue(datetime), Atom::DurationValue(duration)) |
+        (Arithmetic::Add, Atom::DurationValue(duration), Atom::DateTimeValue(datetime)) => datetime.checked_add_signed(*duration).map(Atom::DateTimeValue),
+        (Arithmetic::Sub, Atom::DateTimeValue(datetime), Atom::DurationValue(duration)) => datetime.checked_sub_signed(*duration).map(Atom::DateTimeValue),
+        (Arithmetic::Sub, Atom::DateValue(date_a), Atom::DateValue(date_b)) => Some(Atom::DurationValue(date_a.signed_duration_since(*date_b))),
+        (Arithmetic::Sub, _, _) => {
+            match (as_datetime(atom_a), as_datetime(atom_b)) {
+                (Some(dt_a), Some(dt_b)) => Some(Atom::DurationValue(dt_a.signed_duration_since(dt_b))),
+                _ => return RUNTIME_ERR_INVALID_TYPE
+            }
+        },
+        _ => return RUNTIME_ERR_INVALID_TYPE
+    };
+    match result {
+        Some(atom) => env.init_value(atom),
+        // Out of the representable range
+        None => RUNTIME_ERR_INVALID_ARG
+    }
+}
+
+pub fn compare_temporal(a: &Atom, b: &Atom) -> Option<Ordering> {
+    match (a, b) {
+        (Atom::DateValue(date_a), Atom::DateValue(date_b)) => Some(date_a.cmp(date_b)),
+        (Atom::DurationValue(d_a), Atom::DurationValue(d_b)) => Some(d_a.cmp(d_b)),
+        _ => {
+            // Compares instants, independent of offset
+            let dt_a = as_datetime(a)?;
+            let dt_b = as_datetime(b)?;
+            Some(dt_a.cmp(&dt_b))
+        }
+    }
+}
+
+// Component access. i.e. deadline.year, (deadline - start).days
+pub fn temporal_field(atom: &Atom, field: &str) -> Option<Atom> {
+    let field = field.to_ascii_lowercase();
+    let value: i64 = match atom {
+        Atom::DateValue(date) => {
+            match field.as_ref() {
+                "year" => date.year() as i64,
+                "month" => date.month() as i64,
+                "day" => date.day() as i64,
+                // ISO weekday. Monday = 1, Sunday = 7
+                "weekday" => date.weekday().number_from_monday() as i64,
+                "day_of_year" => date.ordinal() as i64,
+                _ => return None
+            }
+        },
+        Atom::DateTimeValue(datetime) => {
+            match field.as_ref() {
+                "year" => datetime.year() as i64,
+                "month" => datetime.month() as i64,
+                "day" => datetime.day() as i64,
+                "weekday" => datetime.weekday().number_from_monday() as i64,
+                "day_of_year" => datetime.ordinal() as i64,
+                "hour" => datetime.hour() as i64,
+                "minute" => datetime.minute() as i64,
+                "second" => datetime.second() as i64,
+                // Minutes east of UTC
+                "offset" => (datetime.offset().local_minus_utc() / 60) as i64,
+                "date" => return Some(Atom::DateValue(datetime.date_naive())),
+                _ => return None
+            }
+        },
+        // Durations report their total length in whole units
+        Atom::DurationValue(duration) => {
+            match field.as_ref() {
+                "weeks" => duration.num_weeks(),
+                "days" => duration.num_days(),
+                "hours" => duration.num_hours(),
+                "minutes" => duration.num_minutes(),
+                "seconds" => duration.num_seconds(),
+                _ => return None
+            }
+        },
+        _ => return None
+    };
+    return Some(Atom::NumericValue(value as f64))
+}
+
+// i.e. "3 days", "1 day 02:30:00", "-00:00:05"
+pub fn format_duration(duration: &Duration) -> String {
+    let sign = if *duration < Duration::zero() { "-" } else { "" };
+    let total = duration.num_seconds().abs();
+    let days = total / 86400;
+    let seconds = total % 86400;
+    let time = format!("{:02}:{:02}:{:02}", seconds / 3600, (seconds % 3600) / 60, seconds % 60);
+    let day_label = if days == 1 { "day" } else { "days" };
+    if days == 0 {
+        return format!("{}{}", sign, time)
+    } else if seconds == 0 {
+        return format!("{}{} {}", sign, days, day_label)
+    }
+    return format!("{}{} {} {}", sign, days, day_label, time)
+}
+
+pub fn format_datetime(datetime: &DateTime<FixedOffset>) -> String {
+    return datetime.to_rfc3339()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> Atom {
+        return Atom::DateValue(parse_date(text).unwrap())
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse_date("2020-02-29"), NaiveDate::from_ymd_opt(2020, 2, 29));
+        assert_eq!(parse_date("2019-02-29"), None);
+        assert_eq!(parse_date("tomorrow"), None);
+
+        let datetime = parse_datetime("2020-01-31T09:30:00+05:30").unwrap();
+        assert_eq!(format_datetime(&datetime), "2020-01-31T09:30:00+05:30");
+        assert_eq!(format_datetime(&parse_datetime("2020-01-31 09:30").unwrap()), "2020-01-31T09:30:00+00:00");
+        assert_eq!(format_datetime(&parse_datetime("2020-01-31").unwrap()), "2020-01-31T00:00:00+00:00");
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let deadline = env.init_value(date("2020-03-01"));
+        let start = env.init_value(date("2020-02-01"));
+        let week = env.init_value(Atom::DurationValue(Duration::days(7)));
+        let hours = env.init_value(Atom::DurationValue(Duration::hours(36)));
+
+        let until = temporal_arithmetic(&mut env, Arithmetic::Sub, deadline, start).unwrap();
+        assert_eq!(env.lookup(until).unwrap().value, Some(Atom::DurationValue(Duration::days(29))));
+
+        let extended = temporal_arithmetic(&mut env, Arithmetic::Add, week, deadline).unwrap();
+        assert_eq!(env.lookup(extended).unwrap().value, Some(date("2020-03-08")));
+
+        // Partial days turn dates into datetimes
+        let later = temporal_arithmetic(&mut env, Arithmetic::Add, deadline, hours).unwrap();
+        assert_eq!(env.lookup(later).unwrap().value, Some(Atom::DateTimeValue(parse_datetime("2020-03-02T12:00:00Z").unwrap())));
+
+        assert_eq!(temporal_arithmetic(&mut env, Arithmetic::Add, deadline, start), Some(RUNTIME_ERR_INVALID_TYPE));
+        assert_eq!(temporal_arithmetic(&mut env, Arithmetic::Mul, week, week), Some(RUNTIME_ERR_INVALID_TYPE));
+        assert_eq!(temporal_arithmetic(&mut env, Arithmetic::Add, 1.0f64.to_bits(), 2.0f64.to_bits()), None);
+    }
+
+    #[test]
+    fn test_compare() {
+        let morning_ist = Atom::DateTimeValue(parse_datetime("2020-01-31T09:30:00+05:30").unwrap());
+        let morning_utc = Atom::DateTimeValue(parse_datetime("2020-01-31T04:00:00Z").unwrap());
+        assert_eq!(compare_temporal(&morning_ist, &morning_utc), Some(Ordering::Equal));
+        assert_eq!(compare_temporal(&date("2020-01-31"), &morning_utc), Some(Ordering::Less));
+        assert_eq!(compare_temporal(&date("2020-01-31"), &Atom::DurationValue(Duration::days(1))), None);
+    }
+
+    #[test]
+    fn test_fields() {
+        let datetime = Atom::DateTimeValue(parse_datetime("2020-01-31T09:30:15+05:30").unwrap());
+        assert_eq!(temporal_field(&date("2020-03-01"), "year"), Some(Atom::NumericValue(2020.0)));
+        assert_eq!(temporal_field(&date("2020-03-01"), "weekday"), Some(Atom::NumericValue(7.0)));
+        assert_eq!(temporal_field(&datetime, "Hour"), Some(Atom::NumericValue(9.0)));
+        assert_eq!(temporal_field(&datetime, "offset"), Some(Atom::NumericValue(330.0)));
+        assert_eq!(temporal_field(&datetime, "date"), Some(date("2020-01-31")));
+        assert_eq!(temporal_field(&Atom::DurationValue(Duration::hours(50)), "days"), Some(Atom::NumericValue(2.0)));
+        assert_eq!(temporal_field(&date("2020-03-01"), "hour"), None);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(&Duration::days(3)), "3 days");
+        assert_eq!(format_duration(&Duration::days(1)), "1 day");
+        assert_eq!(format_duration(&(Duration::days(1) + Duration::minutes(150))), "1 day 02:30:00");
+        assert_eq!(format_duration(&Duration::seconds(-5)), "-00:00:05");
+    }
+}
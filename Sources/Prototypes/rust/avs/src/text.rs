@@ -0,0 +1,199 @@
+use crate::environment::Environment;
+use crate::constants::*;
+use crate::collections::{build_list, build_map};
+use crate::strings::{text_arg, MAX_STRING_CHARS};
+use crate::types::__repr_bool;
+use regex::{Regex, RegexBuilder};
+
+/*
+Regular expressions over text. i.e. find_all("a1 b22", "[0-9]+") = ["1", "22"]
+Patterns use the regex crate's syntax, which has no backreferences or lookaround.
+In exchange, matching always takes linear time in the length of the text, so no pattern
+typed into a cell can hang evaluation with catastrophic backtracking.
+Compiled programs are capped in size, since a pattern like a{1000}{1000} is small to write but large to build.
+*/
+
+// Upper bound on the compiled size of a pattern, in bytes
+const MAX_REGEX_SIZE: usize = 1 << 20;
+
+fn compile(env: &Environment, pattern: u64) -> Result<Regex, u64> {
+    let pattern = text_arg(env, pattern)?;
+    return RegexBuilder::new(&pattern)
+        .size_limit(MAX_REGEX_SIZE)
+        .build()
+        .map_err(|_| RUNTIME_ERR_INVALID_ARG)
+}
+
+fn to_result(result: Result<u64, u64>) -> u64 {
+    match result {
+        Ok(value) => value,
+        Err(err) => err
+    }
+}
+
+fn matches(env: &mut Environment, text: u64, pattern: u64) -> Result<u64, u64> {
+    let text = text_arg(env, text)?;
+    return Ok(__repr_bool(compile(env, pattern)?.is_match(&text)))
+}
+
+fn find(env: &mut Environment, text: u64, pattern: u64) -> Result<u64, u64> {
+    let text = text_arg(env, text)?;
+    match compile(env, pattern)?.find(&text) {
+        Some(found) => Ok(env.init_string(found.as_str().to_string())),
+        None => Ok(SYMBOL_NONE.symbol)
+    }
+}
+
+fn find_all(env: &mut Environment, text: u64, pattern: u64) -> Result<u64, u64> {
+    let text = text_arg(env, text)?;
+    let regex = compile(env, pattern)?;
+    let mut values: Vec<u64> = Vec::new();
+    for found in regex.find_iter(&text) {
+        values.push(env.init_string(found.as_str().to_string()));
+    }
+    return Ok(build_list(env, values))
+}
+
+fn captures(env: &mut Environment, text: u64, pattern: u64) -> Result<u64, u64> {
+    let text = text_arg(env, text)?;
+    let regex = compile(env, pattern)?;
+    let groups = match regex.captures(&text) {
+        Some(groups) => groups,
+        None => return Ok(SYMBOL_NONE.symbol)
+    };
+    // Alternating keys and values. Groups which didn't participate in the match are left out.
+    let mut entries: Vec<u64> = Vec::new();
+    for (index, name) in regex.capture_names().enumerate() {
+        if let Some(group) = groups.get(index) {
+            let value = env.init_string(group.as_str().to_string());
+            entries.push((index as f64).to_bits());
+            entries.push(value);
+            if let Some(name) = name {
+                let key = env.init_string(name.to_string());
+                entries.push(key);
+                entries.push(value);
+            }
+        }
+    }
+    return Ok(build_map(env, entries))
+}
+
+fn replace_regex(env: &mut Environment, text: u64, pattern: u64, replacement: u64) -> Result<u64, u64> {
+    let text = text_arg(env, text)?;
+    let regex = compile(env, pattern)?;
+    let replacement = text_arg(env, replacement)?;
+    // Built match by match, so an empty pattern with a long replacement stops at the cap
+    let mut replaced = String::new();
+    let mut length = 0;
+    let mut last = 0;
+    for groups in regex.captures_iter(&text) {
+        let found = groups.get(0).unwrap();
+        let start = replaced.len();
+        replaced.push_str(&text[last..found.start()]);
+        groups.expand(&replacement, &mut replaced);
+        length += replaced[start..].chars().count();
+        if length > MAX_STRING_CHARS {
+            return Err(INTERPRETER_ERR_LIMIT_MEMORY)
+        }
+        last = found.end();
+    }
+    replaced.push_str(&text[last..]);
+    return Ok(env.init_string(replaced))
+}
+
+// matches(text, pattern). True if the pattern matches anywhere. Use ^ and $ to match the whole text.
+pub fn __av_matches(env: &mut Environment, text: u64, pattern: u64) -> u64 {
+    return to_result(matches(env, text, pattern))
+}
+
+// find(text, pattern). The first match, or none.
+pub fn __av_find(env: &mut Environment, text: u64, pattern: u64) -> u64 {
+    return to_result(find(env, text, pattern))
+}
+
+// find_all(text, pattern). A list of every non-overlapping match.
+pub fn __av_find_all(env: &mut Environment, text: u64, pattern: u64) -> u64 {
+    return to_result(find_all(env, text, pattern))
+}
+
+// captures(text, pattern). A map of the groups in the first match, or none.
+// Groups are keyed by number (0 is the whole match) and also by name if they have one.
+pub fn __av_captures(env: &mut Environment, text: u64, pattern: u64) -> u64 {
+    return to_result(captures(env, text, pattern))
+}
+
+// replace_regex(text, pattern, replacement). Replaces every match.
+// The replacement can refer to groups with $1 or $name.
+pub fn __av_replace_regex(env: &mut Environment, text: u64, pattern: u64, replacement: u64) -> u64 {
+    return to_result(replace_regex(env, text, pattern, replacement))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::__av_get;
+
+    fn text(env: &Environment, value: u64) -> String {
+        return text_arg(env, value).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_find() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let line = env.init_string(String::from("GET /index 200 12ms"));
+        let digits = env.init_string(String::from("[0-9]+"));
+        let missing = env.init_string(String::from("POST"));
+        assert_eq!(__av_matches(&mut env, line, digits), SYMBOL_TRUE.symbol);
+        assert_eq!(__av_matches(&mut env, line, missing), SYMBOL_FALSE.symbol);
+        let first = __av_find(&mut env, line, digits);
+        assert_eq!(text(&env, first), "200");
+        assert_eq!(__av_find(&mut env, line, missing), SYMBOL_NONE.symbol);
+
+        let all = __av_find_all(&mut env, line, digits);
+        let second = __av_get(&mut env, all, (1.0 as f64).to_bits());
+        assert_eq!(text(&env, second), "12");
+
+        let invalid = env.init_string(String::from("(unclosed"));
+        assert_eq!(__av_find(&mut env, line, invalid), RUNTIME_ERR_INVALID_ARG);
+        // Too large to compile, rather than slow to run
+        let huge = env.init_string(String::from("(a{1000}){1000}"));
+        assert_eq!(__av_matches(&mut env, line, huge), RUNTIME_ERR_INVALID_ARG);
+    }
+
+    #[test]
+    fn test_replace_limit() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let line = env.init_string(String::from("GET /index 200 12ms"));
+        let digits = env.init_string(String::from("([0-9])[0-9]*"));
+        let first_digit = env.init_string(String::from("<$1>"));
+        let replaced = __av_replace_regex(&mut env, line, digits, first_digit);
+        assert_eq!(text(&env, replaced), "GET /index <2> <1>ms");
+
+        // An empty pattern matches between every character
+        let letter = env.init_string(String::from("a"));
+        let long = crate::strings::__av_repeat(&mut env, letter, ((1 << 20) as f64).to_bits());
+        let empty = env.init_string(String::from(""));
+        let wide = env.init_string(String::from("x".repeat(32)));
+        assert_eq!(__av_replace_regex(&mut env, long, empty, wide), INTERPRETER_ERR_LIMIT_MEMORY);
+    }
+
+    #[test]
+    fn test_captures() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let line = env.init_string(String::from("2020-01-31 ERROR disk full"));
+        let pattern = env.init_string(String::from(r"(?P<date>\S+) (?P<level>[A-Z]+)( \[(\w+)\])?"));
+        let groups = __av_captures(&mut env, line, pattern);
+        let level_key = env.init_string(String::from("level"));
+        let level = __av_get(&mut env, groups, level_key);
+        assert_eq!(text(&env, level), "ERROR");
+        let whole = __av_get(&mut env, groups, (0.0 as f64).to_bits());
+        assert_eq!(text(&env, whole), "2020-01-31 ERROR");
+        // The optional group didn't match
+        assert_eq!(__av_get(&mut env, groups, (3.0 as f64).to_bits()), RUNTIME_ERR_UNK_FIELD);
+
+        let replacement = env.init_string(String::from("$level on $date"));
+        let replaced = __av_replace_regex(&mut env, line, pattern, replacement);
+        assert_eq!(text(&env, replaced), "ERROR on 2020-01-31 disk full");
+    }
+}
@@ -0,0 +1,106 @@
+use std::cell::Cell;
+
+// Pluggable tracing for the interpreter. Off by default (no tracer on the environment),
+// in which case tracing costs a single branch and messages are never formatted.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TraceLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Span {
+    // Cell ID
+    Cell(u64),
+    // Cell symbol of the expression
+    Expression(u64),
+    // Operator symbol
+    Operator(u64)
+}
+
+pub trait Tracer {
+    fn enabled(&self, level: TraceLevel) -> bool;
+    fn enter(&self, _span: Span) {}
+    fn exit(&self, _span: Span) {}
+    fn event(&self, level: TraceLevel, message: &str);
+}
+
+// Simple tracer which writes indented events to stdout. Useful during local debugging.
+pub struct StdoutTracer {
+    pub level: TraceLevel,
+    depth: Cell<usize>
+}
+
+impl StdoutTracer {
+    pub fn new(level: TraceLevel) -> StdoutTracer {
+        return StdoutTracer {
+            level: level,
+            depth: Cell::new(0)
+        }
+    }
+}
+
+impl Tracer for StdoutTracer {
+    fn enabled(&self, level: TraceLevel) -> bool {
+        return level <= self.level
+    }
+
+    fn enter(&self, span: Span) {
+        if self.enabled(TraceLevel::Debug) {
+            println!("{}> {:X?}", "  ".repeat(self.depth.get()), span);
+        }
+        self.depth.set(self.depth.get() + 1);
+    }
+
+    fn exit(&self, _span: Span) {
+        self.depth.set(self.depth.get().saturating_sub(1));
+    }
+
+    fn event(&self, level: TraceLevel, message: &str) {
+        println!("{}[{:?}] {}", "  ".repeat(self.depth.get()), level, message);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use crate::environment::Environment;
+    use crate::constants::APP_SYMBOL_START;
+
+    struct RecordingTracer {
+        events: Rc<RefCell<Vec<String>>>
+    }
+
+    impl Tracer for RecordingTracer {
+        fn enabled(&self, level: TraceLevel) -> bool {
+            return level <= TraceLevel::Info
+        }
+
+        fn event(&self, _level: TraceLevel, message: &str) {
+            self.events.borrow_mut().push(message.to_string());
+        }
+    }
+
+    #[test]
+    fn test_trace_levels() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        // Disabled by default
+        av_trace!(env, TraceLevel::Error, "error {}", 0);
+
+        let events = Rc::new(RefCell::new(vec![]));
+        env.tracer = Some(Box::new(RecordingTracer { events: events.clone() }));
+        av_trace!(env, TraceLevel::Info, "info {}", 1);
+        av_trace!(env, TraceLevel::Trace, "trace {}", 2);
+        av_trace!(env, TraceLevel::Warn, "warn {}", 3);
+
+        // Only the levels the tracer asked for are recorded.
+        assert_eq!(*events.borrow(), vec!["info 1".to_string(), "warn 3".to_string()]);
+    }
+}
@@ -0,0 +1,402 @@
+use crate::environment::Environment;
+use crate::structs::Atom;
+use crate::constants::*;
+use crate::numeric::{Arithmetic, atom_number};
+use crate::types::*;
+use crate::format::repr_float;
+use core::cmp::Ordering;
+use std::rc::Rc;
+
+/*
+Numbers with units. i.e. 5 km + 300 m = 5.3 km
+A quantity stores its magnitude in SI base units alongside the powers of each base unit (its dimension),
+so quantities of the same dimension combine directly, whatever units they were written in.
+Each quantity also remembers a unit to display in. Sums keep the unit of the left side.
+Products and quotients display in a named SI unit if one matches (i.e. kg * m/s^2 = N), or in base units.
+Only units which are a multiple of SI are supported, so there's no °C or °F.
+*/
+
+// Powers of m, kg, s, A, K, mol, cd
+pub type Dimension = [i8; 7];
+pub const DIMENSIONLESS: Dimension = [0; 7];
+const BASE_UNITS: [&str; 7] = ["m", "kg", "s", "A", "K", "mol", "cd"];
+
+const LENGTH: Dimension = [1, 0, 0, 0, 0, 0, 0];
+const MASS: Dimension = [0, 1, 0, 0, 0, 0, 0];
+const TIME: Dimension = [0, 0, 1, 0, 0, 0, 0];
+const CURRENT: Dimension = [0, 0, 0, 1, 0, 0, 0];
+const TEMPERATURE: Dimension = [0, 0, 0, 0, 1, 0, 0];
+const AMOUNT: Dimension = [0, 0, 0, 0, 0, 1, 0];
+const LUMINOSITY: Dimension = [0, 0, 0, 0, 0, 0, 1];
+const AREA: Dimension = [2, 0, 0, 0, 0, 0, 0];
+const VOLUME: Dimension = [3, 0, 0, 0, 0, 0, 0];
+const SPEED: Dimension = [1, 0, -1, 0, 0, 0, 0];
+const FREQUENCY: Dimension = [0, 0, -1, 0, 0, 0, 0];
+const FORCE: Dimension = [1, 1, -2, 0, 0, 0, 0];
+const ENERGY: Dimension = [2, 1, -2, 0, 0, 0, 0];
+const POWER: Dimension = [2, 1, -3, 0, 0, 0, 0];
+const PRESSURE: Dimension = [-1, 1, -2, 0, 0, 0, 0];
+const CHARGE: Dimension = [0, 0, 1, 1, 0, 0, 0];
+const VOLTAGE: Dimension = [2, 1, -3, -1, 0, 0, 0];
+const RESISTANCE: Dimension = [2, 1, -3, -2, 0, 0, 0];
+
+// Name, size in SI base units, dimension. Names are case sensitive, since mm and Mm differ.
+const UNITS: [(&str, f64, Dimension); 57] = [
+    ("m", 1.0, LENGTH), ("km", 1e3, LENGTH), ("cm", 1e-2, LENGTH), ("mm", 1e-3, LENGTH),
+    ("um", 1e-6, LENGTH), ("nm", 1e-9, LENGTH),
+    ("in", 0.0254, LENGTH), ("ft", 0.3048, LENGTH), ("yd", 0.9144, LENGTH), ("mi", 1609.344, LENGTH), ("nmi", 1852.0, LENGTH),
+    ("kg", 1.0, MASS), ("g", 1e-3, MASS), ("mg", 1e-6, MASS), ("t", 1e3, MASS),
+    ("lb", 0.45359237, MASS), ("oz", 0.028349523125, MASS),
+    ("s", 1.0, TIME), ("ms", 1e-3, TIME), ("us", 1e-6, TIME), ("ns", 1e-9, TIME),
+    ("min", 60.0, TIME), ("h", 3600.0, TIME), ("hr", 3600.0, TIME), ("day", 86400.0, TIME),
+    ("A", 1.0, CURRENT), ("mA", 1e-3, CURRENT),
+    ("K", 1.0, TEMPERATURE), ("mol", 1.0, AMOUNT), ("cd", 1.0, LUMINOSITY),
+    ("ha", 1e4, AREA), ("acre", 4046.8564224, AREA),
+    ("L", 1e-3, VOLUME), ("mL", 1e-6, VOLUME), ("gal", 3.785411784e-3, VOLUME),
+    ("mph", 0.44704, SPEED), ("kn", 1852.0 / 3600.0, SPEED),
+    ("Hz", 1.0, FREQUENCY), ("kHz", 1e3, FREQUENCY), ("MHz", 1e6, FREQUENCY),
+    ("N", 1.0, FORCE), ("kN", 1e3, FORCE), ("lbf", 4.4482216152605, FORCE),
+    ("J", 1.0, ENERGY), ("kJ", 1e3, ENERGY), ("cal", 4.184, ENERGY), ("kcal", 4184.0, ENERGY),
+    ("Wh", 3600.0, ENERGY), ("kWh", 3.6e6, ENERGY),
+    ("W", 1.0, POWER), ("kW", 1e3, POWER), ("hp", 745.69987158227022, POWER),
+    ("Pa", 1.0, PRESSURE), ("kPa", 1e3, PRESSURE), ("bar", 1e5, PRESSURE), ("psi", 6894.757293168, PRESSURE),
+    ("C", 1.0, CHARGE)
+];
+
+// Named units for displaying products and quotients. Other dimensions are shown in base units.
+const DERIVED_UNITS: [(&str, Dimension); 7] = [
+    ("N", FORCE), ("J", ENERGY), ("W", POWER), ("Pa", PRESSURE), ("C", CHARGE), ("V", VOLTAGE), ("ohm", RESISTANCE)
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unit {
+    pub name: Rc<str>,
+    // Size of one of this unit in SI base units
+    pub scale: f64,
+    pub dimension: Dimension
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantity {
+    // Magnitude in SI base units
+    pub value: f64,
+    pub unit: Unit
+}
+
+impl Quantity {
+    // i.e. 5.3 for 5300 m shown in km
+    pub fn display_value(&self) -> f64 {
+        return self.value / self.unit.scale
+    }
+}
+
+// Conversion factors rarely divide exactly, so 60 mph in km/h would otherwise show as 96.56063999999999
+const DISPLAY_DIGITS: i32 = 12;
+
+fn round_significant(value: f64) -> f64 {
+    if value == 0.0 || !value.is_finite() {
+        return value
+    }
+    let scale = (10.0 as f64).powi(DISPLAY_DIGITS - value.abs().log10().ceil() as i32);
+    if !scale.is_finite() || scale == 0.0 {
+        return value
+    }
+    return (value * scale).round() / scale
+}
+
+pub fn format_quantity(quantity: &Quantity) -> String {
+    return format!("{} {}", repr_float(round_significant(quantity.display_value())), quantity.unit.name)
+}
+
+fn named_unit(name: &str) -> Option<(f64, Dimension)> {
+    if let Some((_, scale, dimension)) = UNITS.iter().find(|(unit, _, _)| *unit == name) {
+        return Some((*scale, *dimension))
+    }
+    // Derived units not in the main table
+    return DERIVED_UNITS.iter().find(|(unit, _)| *unit == name).map(|(_, dimension)| (1.0, *dimension))
+}
+
+// None when an exponent leaves the i8 range. i.e. m^100 * m^100
+fn combine(a: Dimension, b: Dimension, sign: i8) -> Option<Dimension> {
+    let mut result = DIMENSIONLESS;
+    for index in 0..result.len() {
+        result[index] = a[index].checked_add(sign.checked_mul(b[index])?)?;
+    }
+    return Some(result)
+}
+
+// Parse a unit expression. i.e. "km", "m/s^2" or "kg*m^2/s^2"
+// Everything after a / is in the denominator.
+pub fn parse_unit(text: &str) -> Option<Unit> {
+    let mut scale = 1.0;
+    let mut dimension = DIMENSIONLESS;
+    let mut sign: i8 = 1;
+    let mut rest = text;
+    loop {
+        let end = rest.find(|ch| ch == '*' || ch == '/').unwrap_or(rest.len());
+        let term = &rest[..end];
+        let (name, power) = match term.find('^') {
+            Some(caret) => (&term[..caret], term[caret + 1..].parse::<i8>().ok()?),
+            None => (term, 1)
+        };
+        let (unit_scale, unit_dimension) = named_unit(name)?;
+        // Limited to +/- i8::MAX, like quantity_power, so every exponent can be negated
+        let power = sign.checked_mul(power)?;
+        power.checked_neg()?;
+        scale *= unit_scale.powi(power as i32);
+        for _ in 0..power.abs() {
+            dimension = combine(dimension, unit_dimension, power.signum())?;
+        }
+        if end == rest.len() {
+            break
+        }
+        if &rest[end..end + 1] == "/" {
+            sign = -1;
+        }
+        rest = &rest[end + 1..];
+    }
+    return Some(Unit { name: text.into(), scale: scale, dimension: dimension })
+}
+
+// i.e. kg*m/s^2
+fn base_unit_name(dimension: &Dimension) -> String {
+    let term = |index: usize, power: i8| -> String {
+        if power == 1 { BASE_UNITS[index].to_string() } else { format!("{}^{}", BASE_UNITS[index], power) }
+    };
+    let numerator: Vec<String> = (0..7).filter(|i| dimension[*i] > 0).map(|i| term(i, dimension[i])).collect();
+    let denominator: Vec<String> = (0..7).filter(|i| dimension[*i] < 0).map(|i| term(i, -dimension[i])).collect();
+    let top = if numerator.len() > 0 { numerator.join("*") } else { String::from("1") };
+    if denominator.len() == 0 {
+        return top
+    }
+    return format!("{}/{}", top, denominator.join("/"))
+}
+
+// SI display unit for a computed dimension
+fn si_unit(dimension: Dimension) -> Unit {
+    let name = match DERIVED_UNITS.iter().find(|(_, derived)| *derived == dimension) {
+        Some((name, _)) => name.to_string(),
+        None => base_unit_name(&dimension)
+    };
+    return Unit { name: name.into(), scale: 1.0, dimension: dimension }
+}
+
+// Quantities with no dimension left (i.e. m/m) are plain numbers
+fn init_quantity(env: &mut Environment, value: f64, unit: Unit) -> u64 {
+    if unit.dimension == DIMENSIONLESS {
+        return (value / unit.scale).to_bits()
+    }
+    return env.init_value(Atom::QuantityValue(Quantity { value: value, unit: unit }))
+}
+
+pub fn is_quantity(atom: &Atom) -> bool {
+    match atom {
+        Atom::QuantityValue(_) => true,
+        _ => false
+    }
+}
+
+// Arithmetic for quantities. Returns None if neither side is a quantity.
+pub fn quantity_arithmetic(env: &mut Environment, op: Arithmetic, a: u64, b: u64) -> Option<u64> {
+    // Quantities are always boxed
+    if !is_pointer(a) && !is_pointer(b) {
+        return None
+    }
+    if is_error(a) || is_error(b) {
+        return None
+    }
+    let atom_a = resolve_atom!(env, a);
+    let atom_b = resolve_atom!(env, b);
+    if !is_quantity(&atom_a) && !is_quantity(&atom_b) {
+        return None
+    }
+    return Some(apply_quantity(env, op, &atom_a, &atom_b))
+}
+
+pub fn apply_quantity(env: &mut Environment, op: Arithmetic, atom_a: &Atom, atom_b: &Atom) -> u64 {
+    match (op, atom_a, atom_b) {
+        (Arithmetic::Add, Atom::QuantityValue(q_a), Atom::QuantityValue(q_b)) |
+        (Arithmetic::Sub, Atom::QuantityValue(q_a), Atom::QuantityValue(q_b)) => {
+            if q_a.unit.dimension != q_b.unit.dimension {
+                return RUNTIME_ERR_DIMENSION
+            }
+            let value = if op == Arithmetic::Add { q_a.value + q_b.value } else { q_a.value - q_b.value };
+            init_quantity(env, value, q_a.unit.clone())
+        },
+        // A plain number has no dimension, so it can't be added to one with a dimension
+        (Arithmetic::Add, _, _) | (Arithmetic::Sub, _, _) => RUNTIME_ERR_DIMENSION,
+        (_, Atom::QuantityValue(q_a), Atom::QuantityValue(q_b)) => {
+            let sign = if op == Arithmetic::Mul { 1 } else { -1 };
+            if op == Arithmetic::Div && q_b.value == 0.0 {
+                return RUNTIME_ERR_DIV_Z
+            }
+            let dimension = match combine(q_a.unit.dimension, q_b.unit.dimension, sign) {
+                Some(dimension) => dimension,
+                None => return RUNTIME_ERR_INVALID_ARG
+            };
+            let value = if op == Arithmetic::Mul { q_a.value * q_b.value } else { q_a.value / q_b.value };
+            init_quantity(env, value, si_unit(dimension))
+        },
+        // Scaling keeps the unit. i.e. 2 * 3 km = 6 km
+        (_, Atom::QuantityValue(quantity), other) | (Arithmetic::Mul, other, Atom::QuantityValue(quantity)) => {
+            let factor = match atom_number(other) {
+                Some(number) => number.to_f64(),
+                None => return RUNTIME_ERR_EXPECTED_NUM
+            };
+            if op == Arithmetic::Div && factor == 0.0 {
+                return RUNTIME_ERR_DIV_Z
+            }
+            let value = if op == Arithmetic::Mul { quantity.value * factor } else { quantity.value / factor };
+            init_quantity(env, value, quantity.unit.clone())
+        },
+        // Dividing a number by a quantity. i.e. 1 / (2 s) = 0.5 Hz
+        (Arithmetic::Div, other, Atom::QuantityValue(quantity)) => {
+            let numerator = match atom_number(other) {
+                Some(number) => number.to_f64(),
+                None => return RUNTIME_ERR_EXPECTED_NUM
+            };
+            if quantity.value == 0.0 {
+                return RUNTIME_ERR_DIV_Z
+            }
+            let dimension = match combine(DIMENSIONLESS, quantity.unit.dimension, -1) {
+                Some(dimension) => dimension,
+                None => return RUNTIME_ERR_INVALID_ARG
+            };
+            init_quantity(env, numerator / quantity.value, si_unit(dimension))
+        },
+        _ => RUNTIME_ERR_EXPECTED_NUM
+    }
+}
+
+// Integer powers. i.e. (3 m)^2 = 9 m^2
+pub fn quantity_power(env: &mut Environment, quantity: &Quantity, power: f64) -> u64 {
+    if power.fract() != 0.0 {
+        return RUNTIME_ERR_DIMENSION
+    } else if power.abs() > i8::MAX as f64 {
+        return RUNTIME_ERR_INVALID_ARG
+    }
+    let mut dimension = DIMENSIONLESS;
+    for index in 0..dimension.len() {
+        dimension[index] = match quantity.unit.dimension[index].checked_mul(power as i8) {
+            Some(exponent) => exponent,
+            None => return RUNTIME_ERR_INVALID_ARG
+        };
+    }
+    return init_quantity(env, quantity.value.powi(power as i32), si_unit(dimension))
+}
+
+// Quantities of the same dimension compare by magnitude. i.e. 1 km == 1000 m
+pub fn compare_quantity(a: &Atom, b: &Atom) -> Option<Ordering> {
+    match (a, b) {
+        (Atom::QuantityValue(q_a), Atom::QuantityValue(q_b)) if q_a.unit.dimension == q_b.unit.dimension => {
+            q_a.value.partial_cmp(&q_b.value)
+        },
+        _ => None
+    }
+}
+
+// convert(quantity, "mi"). Numbers are given the unit. i.e. convert(5, "km") = 5 km
+pub fn __av_convert(env: &mut Environment, value: u64, unit: u64) -> u64 {
+    if is_error(value) {
+        return value
+    } else if is_error(unit) {
+        return unit
+    }
+    let unit = match resolve_atom!(env, unit) {
+        Atom::StringValue(name) => match parse_unit(name.trim()) {
+            Some(unit) => unit,
+            None => return RUNTIME_ERR_INVALID_ARG
+        },
+        _ => return RUNTIME_ERR_EXPECTED_STR
+    };
+    match resolve_atom!(env, value) {
+        Atom::QuantityValue(quantity) => {
+            if quantity.unit.dimension != unit.dimension {
+                return RUNTIME_ERR_DIMENSION
+            }
+            init_quantity(env, quantity.value, unit)
+        },
+        atom => match atom_number(&atom) {
+            Some(number) => init_quantity(env, number.to_f64() * unit.scale, unit),
+            None => RUNTIME_ERR_EXPECTED_NUM
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quantity(env: &mut Environment, value: f64, unit: &str) -> u64 {
+        let unit = parse_unit(unit).unwrap();
+        return init_quantity(env, value * unit.scale, unit)
+    }
+
+    fn shown(env: &Environment, value: u64) -> String {
+        match resolve_atom!(env, value) {
+            Atom::QuantityValue(quantity) => format_quantity(&quantity),
+            _ => panic!("Expected a quantity")
+        }
+    }
+
+    #[test]
+    fn test_parse_unit() {
+        let acceleration = parse_unit("m/s^2").unwrap();
+        assert_eq!(acceleration.dimension, [1, 0, -2, 0, 0, 0, 0]);
+        assert_eq!(parse_unit("km/h").unwrap().scale, 1000.0 / 3600.0);
+        assert_eq!(parse_unit("kg*m/s^2").unwrap().dimension, FORCE);
+        assert_eq!(parse_unit("s^-1").unwrap().dimension, FREQUENCY);
+        assert_eq!(parse_unit("parsec"), None);
+        assert_eq!(parse_unit("m/"), None);
+        assert_eq!(parse_unit("m^x"), None);
+        // Exponents outside the i8 range
+        assert_eq!(parse_unit("m^-128"), None);
+        assert_eq!(parse_unit("m^100*m^100"), None);
+        assert_eq!(parse_unit("s/m^-128"), None);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let km = quantity(&mut env, 5.0, "km");
+        let km = resolve_atom!(env, km);
+        let m = quantity(&mut env, 300.0, "m");
+        let m = resolve_atom!(env, m);
+        let total = apply_quantity(&mut env, Arithmetic::Add, &km, &m);
+        assert_eq!(shown(&env, total), "5.3 km");
+
+        let mass = quantity(&mut env, 10.0, "kg");
+        let mass = resolve_atom!(env, mass);
+        let gravity = quantity(&mut env, 9.8, "m/s^2");
+        let gravity = resolve_atom!(env, gravity);
+        let force = apply_quantity(&mut env, Arithmetic::Mul, &mass, &gravity);
+        assert_eq!(shown(&env, force), "98 N");
+
+        let ratio = apply_quantity(&mut env, Arithmetic::Div, &km, &m);
+        assert_eq!(f64::from_bits(ratio), 5000.0 / 300.0);
+        assert_eq!(apply_quantity(&mut env, Arithmetic::Add, &km, &mass), RUNTIME_ERR_DIMENSION);
+        assert_eq!(apply_quantity(&mut env, Arithmetic::Sub, &km, &Atom::NumericValue(1.0)), RUNTIME_ERR_DIMENSION);
+        let doubled = apply_quantity(&mut env, Arithmetic::Mul, &Atom::NumericValue(2.0), &km);
+        assert_eq!(shown(&env, doubled), "10 km");
+
+        let large = quantity(&mut env, 1.0, "m^100");
+        let large = resolve_atom!(env, large);
+        assert_eq!(apply_quantity(&mut env, Arithmetic::Mul, &large, &large), RUNTIME_ERR_INVALID_ARG);
+    }
+
+    #[test]
+    fn test_convert() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        let distance = quantity(&mut env, 1609.344, "m");
+        let miles = env.init_string(String::from("mi"));
+        let converted = __av_convert(&mut env, distance, miles);
+        assert_eq!(shown(&env, converted), "1 mi");
+        let seconds = env.init_string(String::from("s"));
+        assert_eq!(__av_convert(&mut env, distance, seconds), RUNTIME_ERR_DIMENSION);
+        let unknown = env.init_string(String::from("furlong"));
+        assert_eq!(__av_convert(&mut env, distance, unknown), RUNTIME_ERR_INVALID_ARG);
+        let attached = __av_convert(&mut env, (3.0 as f64).to_bits(), miles);
+        assert_eq!(compare_quantity(&resolve_atom!(env, attached), &resolve_atom!(env, converted)), Some(Ordering::Greater));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::constants::{VALUE_T_SYM_OBJ, VALUE_T_PTR_OBJ, VALUE_T_PTR_STR, LOW32_MASK};
+use crate::constants::{VALUE_T_SYM_OBJ, VALUE_T_PTR_OBJ, VALUE_T_PTR_STR, VALUE_T_SYM_STR, LOW32_MASK, VALHEAD_MASK, PAYLOAD_MASK, SYMBOL_EMPTY_STR};
 
 // Unwrap pointer
 #[inline(always)]
@@ -19,9 +19,200 @@ pub fn create_pointer_symbol(raw: u64) -> u64 {
     return raw | VALUE_T_PTR_OBJ;
 }
 
+// String pointers store the character count in the upper 16 bits of the payload.
+// Longer strings store the sentinel and have to be dereferenced for their length.
+pub const STR_LEN_SHIFT: u64 = 32;
+pub const STR_LEN_MASK: u64 = 0x0000_FFFF_0000_0000;
+pub const STR_LEN_LONG: u64 = 0xFFFF;
+
+#[inline(always)]
+pub fn create_string_pointer(raw: u64, length: usize) -> u64 {
+    let length = if (length as u64) < STR_LEN_LONG { length as u64 } else { STR_LEN_LONG };
+    return (raw & LOW32_MASK) | (length << STR_LEN_SHIFT) | VALUE_T_PTR_STR;
+}
+
+#[inline(always)]
+pub fn is_string_pointer(value: u64) -> bool {
+    return (value & VALHEAD_MASK) == VALUE_T_PTR_STR;
+}
+
+// Character count of a string pointer, if it fits in the length tag.
+pub fn string_pointer_len(value: u64) -> Option<usize> {
+    if !is_string_pointer(value) {
+        return None
+    }
+    let length = (value & STR_LEN_MASK) >> STR_LEN_SHIFT;
+    if length == STR_LEN_LONG {
+        return None
+    }
+    return Some(length as usize)
+}
+
+// Character count of a string value without dereferencing, where possible.
+pub fn known_str_len(value: u64) -> Option<usize> {
+    if is_inline_str(value) {
+        return Some(inline_str_char_len(value))
+    }
+    return string_pointer_len(value)
+}
+
+
+// Strings up to 6 bytes are stored directly in the payload without a heap allocation.
+pub const INLINE_STR_MAX_LEN: usize = 6;
+
 #[inline(always)]
-pub fn create_string_pointer(raw: u64) -> u64 {
-    // TODO: Pointer, length in the future.
-    return raw | VALUE_T_PTR_STR;
+pub fn is_inline_str(value: u64) -> bool {
+    return (value & VALHEAD_MASK) == VALUE_T_SYM_STR || value == SYMBOL_EMPTY_STR;
 }
 
+// Bytes are packed big-endian and zero padded, so comparing two payloads
+// compares the strings lexicographically.
+pub fn encode_inline_str(value: &str) -> Option<u64> {
+    let bytes = value.as_bytes();
+    if bytes.len() == 0 {
+        return Some(SYMBOL_EMPTY_STR)
+    }
+    // Null bytes are reserved as padding
+    if bytes.len() > INLINE_STR_MAX_LEN || bytes.contains(&0) {
+        return None
+    }
+
+    let mut payload: u64 = 0;
+    for (index, byte) in bytes.iter().enumerate() {
+        payload |= (*byte as u64) << (8 * (INLINE_STR_MAX_LEN - 1 - index));
+    }
+    return Some(VALUE_T_SYM_STR | payload)
+}
+
+pub fn decode_inline_str(value: u64) -> Option<String> {
+    if value == SYMBOL_EMPTY_STR {
+        return Some(String::from(""))
+    }
+    if (value & VALHEAD_MASK) != VALUE_T_SYM_STR {
+        return None
+    }
+
+    let payload = value & PAYLOAD_MASK;
+    let mut bytes: Vec<u8> = Vec::with_capacity(INLINE_STR_MAX_LEN);
+    for index in 0..INLINE_STR_MAX_LEN {
+        let byte = ((payload >> (8 * (INLINE_STR_MAX_LEN - 1 - index))) & 0xFF) as u8;
+        if byte == 0 {
+            break;
+        }
+        bytes.push(byte);
+    }
+    return String::from_utf8(bytes).ok()
+}
+
+// Byte length of an inline string without decoding it.
+pub fn inline_str_len(value: u64) -> usize {
+    if value == SYMBOL_EMPTY_STR {
+        return 0
+    }
+    let payload = value & PAYLOAD_MASK;
+    let mut len = INLINE_STR_MAX_LEN;
+    while len > 0 && (payload >> (8 * (INLINE_STR_MAX_LEN - len))) & 0xFF == 0 {
+        len -= 1;
+    }
+    return len
+}
+
+// Character count of an inline string. Counts every byte except UTF-8 continuation bytes.
+pub fn inline_str_char_len(value: u64) -> usize {
+    let len = inline_str_len(value);
+    let payload = value & PAYLOAD_MASK;
+    let mut count = 0;
+    for index in 0..len {
+        let byte = (payload >> (8 * (INLINE_STR_MAX_LEN - 1 - index))) & 0xFF;
+        if byte & 0xC0 != 0x80 {
+            count += 1;
+        }
+    }
+    return count
+}
+
+// Ordering key for inline strings. The empty string has a distinct header, so compare payloads.
+#[inline(always)]
+pub fn inline_str_key(value: u64) -> u64 {
+    if value == SYMBOL_EMPTY_STR {
+        return 0
+    }
+    return value & PAYLOAD_MASK
+}
+
+// Concatenate two inline strings if the result still fits inline.
+pub fn concat_inline_str(a: u64, b: u64) -> Option<u64> {
+    let len_a = inline_str_len(a);
+    let len_b = inline_str_len(b);
+    if len_a + len_b > INLINE_STR_MAX_LEN {
+        return None
+    }
+    if len_b == 0 {
+        return Some(a)
+    } else if len_a == 0 {
+        return Some(b)
+    }
+    // Shift b's bytes over to start right after a's.
+    let payload = (a & PAYLOAD_MASK) | ((b & PAYLOAD_MASK) >> (8 * len_a));
+    return Some(VALUE_T_SYM_STR | payload)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::APP_SYMBOL_START;
+
+    #[test]
+    fn test_inline_str() {
+        for value in ["", "a", "Hello", "abcdef", "\u{e9}t\u{e9}"].iter() {
+            let encoded = encode_inline_str(value).unwrap();
+            assert_eq!(is_inline_str(encoded), true);
+            assert_eq!(inline_str_len(encoded), value.len());
+            assert_eq!(inline_str_char_len(encoded), value.chars().count());
+            assert_eq!(decode_inline_str(encoded).unwrap(), value.to_string());
+        }
+        assert_eq!(encode_inline_str(""), Some(SYMBOL_EMPTY_STR));
+        // Too long to fit inline
+        assert_eq!(encode_inline_str("abcdefg"), None);
+        assert_eq!(decode_inline_str(VALUE_T_PTR_OBJ), None);
+    }
+
+    #[test]
+    fn test_inline_str_order() {
+        let a = encode_inline_str("abc").unwrap();
+        let b = encode_inline_str("abd").unwrap();
+        let c = encode_inline_str("ab").unwrap();
+        let empty = encode_inline_str("").unwrap();
+        assert!(inline_str_key(a) < inline_str_key(b));
+        assert!(inline_str_key(c) < inline_str_key(a));
+        assert!(inline_str_key(empty) < inline_str_key(c));
+    }
+
+    #[test]
+    fn test_string_pointer_len() {
+        let pointer = create_string_pointer(APP_SYMBOL_START + 3, 12);
+        assert_eq!(is_string_pointer(pointer), true);
+        assert_eq!(string_pointer_len(pointer), Some(12));
+        assert_eq!(known_str_len(pointer), Some(12));
+        assert_eq!(truncate_symbol(pointer) as u64, APP_SYMBOL_START + 3);
+
+        // Too long for the length tag
+        let long_pointer = create_string_pointer(APP_SYMBOL_START, 70_000);
+        assert_eq!(string_pointer_len(long_pointer), None);
+        assert_eq!(string_pointer_len(create_pointer_symbol(APP_SYMBOL_START)), None);
+        assert_eq!(known_str_len(encode_inline_str("\u{e9}t\u{e9}").unwrap()), Some(3));
+    }
+
+    #[test]
+    fn test_concat_inline_str() {
+        let hel = encode_inline_str("Hel").unwrap();
+        let lo = encode_inline_str("lo").unwrap();
+        let empty = encode_inline_str("").unwrap();
+        assert_eq!(concat_inline_str(hel, lo), encode_inline_str("Hello"));
+        assert_eq!(concat_inline_str(empty, lo), Some(lo));
+        assert_eq!(concat_inline_str(lo, empty), Some(lo));
+        assert_eq!(concat_inline_str(hel, hel), encode_inline_str("HelHel"));
+        assert_eq!(concat_inline_str(hel, encode_inline_str("Hell").unwrap()), None);
+    }
+}
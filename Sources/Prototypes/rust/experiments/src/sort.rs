@@ -342,4 +342,7 @@ test sort::tests::bench_graph_sort      ... bench:     758,606 ns/iter (+/- 96,4
 test sort::tests::bench_heap_array_sort ... bench:   1,507,595 ns/iter (+/- 260,397)
 test sort::tests::bench_std_sort        ... bench:     356,525 ns/iter (+/- 29,152)
 
+Not used for the language's sort builtins. heap_array_sort inserts list[0] twice (as the pivot and
+again as an element), and neither sort is stable or takes a comparator, so avs sorts with std sort_by.
+
 */
\ No newline at end of file
@@ -1,15 +1,19 @@
 use avs::runtime::BUILTIN_MODULES;
+use avs::registry::FunctionRegistry;
 use avs::constants::APP_SYMBOL_START;
 use avs::environment::Environment;
 use avs::expression::Expression;
 use fnv::FnvHashMap;
 use super::dependency::{get_eval_order};
 use super::structs::*;
-use super::lexer::lex;
+use super::lexer::{lex, lex_declaration};
+use avs::objects::declare_class;
 use super::parser::{apply_operator_precedence};
-use avs::constants::{RUNTIME_ERR_UNK_VAL};
+use avs::constants::{RUNTIME_ERR_UNK_VAL, RUNTIME_ERR_CIRCULAR_DEP};
+use avs::trace::{Tracer, TraceLevel};
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::time::Instant;
 
 
 pub fn update_used_by(expr_map: &FnvHashMap<u64, Rc<RefCell<Expression>>>, expr: &Expression) {
@@ -44,6 +48,10 @@ pub fn define_symbols(request: &mut EvalRequest, ast: &mut Environment) -> FnvHa
                 // TODO: Handling duplicate names
             }
         }
+        // Record declarations name the cell. i.e. "DateString:\n    year, month, day"
+        if let Some(Ok((class_name, _fields))) = lex_declaration(&cell.input) {
+            ast.bind_name(node.symbol, class_name);
+        }
         
         let wrapper = Rc::new(RefCell::new(node));
 
@@ -63,9 +71,13 @@ pub fn init_builtin(ast: &mut Environment) {
 }
 
 
-pub fn construct_ast(mut request: &mut EvalRequest) -> Environment {
+// Pass in a metrics map to record lex and parse timings by cell ID.
+// Host functions are bound after the builtins, so cells can refer to them by name.
+pub fn construct_ast(mut request: &mut EvalRequest, tracer: Option<Box<dyn Tracer>>, functions: &FunctionRegistry, mut metrics: Option<&mut FnvHashMap<u64, CellMetrics>>) -> Environment {
     let mut ast = Environment::new(APP_SYMBOL_START);
+    ast.tracer = tracer;
     init_builtin(&mut ast);
+    functions.bind(&mut ast);
 
     let mut expr_map = define_symbols(&mut request, &mut ast);
 
@@ -73,18 +85,45 @@ pub fn construct_ast(mut request: &mut EvalRequest) -> Environment {
     // So it should just return used by as well in a single pass.
     for (mut id, mut expr_wrapper) in expr_map.iter() {
         let mut expr = expr_wrapper.borrow_mut();
+        let mut cell_metrics = CellMetrics::default();
+        let lex_start = Instant::now();
+        // Declarations are evaluated up front, since they don't depend on other cells.
+        if let Some(declaration) = lex_declaration(&expr.input) {
+            let symbol = expr.symbol;
+            match declaration {
+                Ok((_class_name, fields)) => expr.set_result(declare_class(&mut ast, symbol, &fields)),
+                Err(err) => expr.set_result(err)
+            }
+            cell_metrics.lex_ns = lex_start.elapsed().as_nanos() as u64;
+            if let Some(metrics_map) = metrics.as_mut() {
+                metrics_map.insert(expr.cell_id, cell_metrics);
+            }
+            continue;
+        }
         let lex_result = lex(&mut ast, &expr.input);
+        cell_metrics.lex_ns = lex_start.elapsed().as_nanos() as u64;
         
         if lex_result.is_ok() {
             let mut lexed = lex_result.unwrap();
+            let parse_start = Instant::now();
             apply_operator_precedence(&mut expr, &mut lexed);
+            cell_metrics.parse_ns = parse_start.elapsed().as_nanos() as u64;
             update_used_by(&expr_map, &expr);
         } else {
             expr.set_result(lex_result.err().unwrap());
         };
+
+        if let Some(metrics_map) = metrics.as_mut() {
+            metrics_map.insert(expr.cell_id, cell_metrics);
+        }
     }
 
     let ordered = get_eval_order(&mut expr_map);
-    ast.body = ordered;
+    for expr in ordered.iter() {
+        if expr.result == Some(RUNTIME_ERR_CIRCULAR_DEP) {
+            av_trace!(ast, TraceLevel::Info, "Found unmet dependency in cell {}", expr.cell_id);
+        }
+    }
+    ast.body = Rc::new(ordered);
     return ast;
 }
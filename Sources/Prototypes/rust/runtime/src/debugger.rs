@@ -0,0 +1,306 @@
+/*
+Step-through debugging for cells.
+A debug session evaluates the same postfix code as the interpreter, one token at a time,
+recording the stack before and after every step. It pauses on breakpoints so the
+intermediate state can be inspected. i.e. 1 + 2 * 3 = 1 2 3 * +
+shows 2 * 3 being applied before the addition.
+*/
+
+use avs::environment::Environment;
+use avs::expression::Expression;
+use avs::structs::Atom;
+use avs::constants::*;
+use super::interpreter::{interpret_token, format_result, is_keyword};
+use super::ast::construct_ast;
+use avs::registry::FunctionRegistry;
+use super::format::{repr, fmt_stack};
+use super::structs::*;
+use std::rc::Rc;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceStep {
+    pub cell_id: u64,
+    pub token: Atom,
+    // Set if the token was an operator or function call
+    pub operator: Option<u64>,
+    pub stack_before: Vec<u64>,
+    pub stack_after: Vec<u64>
+}
+
+impl TraceStep {
+    // Human readable version of this step. i.e. [1, 2, 3] * -> [1, 6]
+    pub fn describe(&self, env: &Environment) -> String {
+        let token = match self.token {
+            Atom::SymbolValue(symbol) => repr(env, symbol),
+            _ => format!("{:?}", self.token)
+        };
+        return format!("{} {} -> {}", fmt_stack(env, &self.stack_before), token, fmt_stack(env, &self.stack_after));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Breakpoint {
+    // Pause before the first token of a cell
+    Cell(u64),
+    // Pause before any token referencing this symbol (operators, functions, cell names)
+    Symbol(u64)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugEvent {
+    Stepped,
+    Breakpoint(Breakpoint),
+    CellFinished(u64),
+    Finished
+}
+
+fn is_operator_token(token: &Atom) -> Option<u64> {
+    if let Atom::SymbolValue(symbol) = token {
+        if is_keyword(*symbol) {
+            return Some(*symbol)
+        }
+    }
+    return None
+}
+
+
+pub struct DebugSession {
+    pub env: Environment,
+    body: Rc<Vec<Expression>>,
+    // Position of the next token to evaluate
+    cell_index: usize,
+    token_index: usize,
+    stack: Vec<u64>,
+    breakpoints: Vec<Breakpoint>,
+    // Set when paused on a breakpoint, so continuing doesn't immediately stop on it again.
+    paused_at: Option<(usize, usize)>,
+    pub trace: Vec<TraceStep>,
+    pub results: Vec<CellResponse>
+}
+
+impl DebugSession {
+    pub fn new(request: EvalRequest) -> DebugSession {
+        return DebugSession::with_functions(request, &FunctionRegistry::new())
+    }
+
+    pub fn with_functions(mut request: EvalRequest, functions: &FunctionRegistry) -> DebugSession {
+        let mut env = construct_ast(&mut request, None, functions, None);
+        if let Some(limits) = &request.limits {
+            env.budget.limits = limits.to_limits();
+        }
+        if let Some(random) = &request.random {
+            env.random = random.to_source();
+        }
+        let body = env.body.clone();
+        env.budget.reset();
+        return DebugSession {
+            env: env,
+            body: body,
+            cell_index: 0,
+            token_index: 0,
+            stack: Vec::new(),
+            breakpoints: Vec::new(),
+            paused_at: None,
+            trace: Vec::new(),
+            results: Vec::new()
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    pub fn remove_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.retain(|b| *b != breakpoint);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        return self.cell_index >= self.body.len()
+    }
+
+    // The ID of the cell that will be evaluated next
+    pub fn current_cell(&self) -> Option<u64> {
+        return self.body.get(self.cell_index).map(|node| node.cell_id)
+    }
+
+    pub fn stack(&self) -> &Vec<u64> {
+        return &self.stack
+    }
+
+    // Current value bound to a symbol (cell results, builtins, etc.)
+    pub fn inspect(&self, symbol: u64) -> Option<String> {
+        if let Some(identifier) = self.env.deep_resolve(symbol) {
+            if let Some(value) = &identifier.value {
+                return Some(format!("{:?}", value))
+            }
+        }
+        return None
+    }
+
+    pub fn inspect_name(&self, name: &str) -> Option<String> {
+        if let Some(symbol) = self.env.lookup_by_name(name.to_string()) {
+            return self.inspect(*symbol)
+        }
+        return None
+    }
+
+    pub fn inspect_stack(&self) -> String {
+        return fmt_stack(&self.env, &self.stack)
+    }
+
+    fn breakpoint_hit(&self) -> Option<Breakpoint> {
+        if self.paused_at == Some((self.cell_index, self.token_index)) {
+            return None
+        }
+        let node = &self.body[self.cell_index];
+        for breakpoint in self.breakpoints.iter() {
+            match breakpoint {
+                Breakpoint::Cell(cell_id) => {
+                    if *cell_id == node.cell_id && self.token_index == 0 {
+                        return Some(*breakpoint)
+                    }
+                },
+                Breakpoint::Symbol(symbol) => {
+                    if let Some(Atom::SymbolValue(token_symbol)) = node.parsed.get(self.token_index) {
+                        if token_symbol == symbol {
+                            return Some(*breakpoint)
+                        }
+                    }
+                }
+            }
+        }
+        return None
+    }
+
+    fn finish_cell(&mut self, result: u64) -> DebugEvent {
+        let node = &self.body[self.cell_index];
+        let cell_id = node.cell_id;
+        self.env.bind_result(node.symbol, result);
+        let (output, err) = format_result(&mut self.env, cell_id, result);
+        self.results.push(CellResponse {
+            id: cell_id,
+            output: output,
+            error: err,
+            metrics: None
+        });
+
+        self.cell_index += 1;
+        self.token_index = 0;
+        self.stack.clear();
+        self.env.budget.reset();
+        return DebugEvent::CellFinished(cell_id)
+    }
+
+    // Evaluate the next token. Finishes the current cell once its tokens are exhausted.
+    pub fn step(&mut self) -> DebugEvent {
+        if self.is_finished() {
+            return DebugEvent::Finished
+        }
+        self.paused_at = None;
+
+        let node = &self.body[self.cell_index];
+        // Cells with parse or dependency errors have nothing to step through.
+        if let Some(prior_err) = node.result {
+            return self.finish_cell(prior_err)
+        }
+        if self.token_index >= node.parsed.len() {
+            let result = self.stack.pop().unwrap_or(SYMBOL_NONE.symbol);
+            return self.finish_cell(result)
+        }
+
+        let token = node.parsed[self.token_index].clone();
+        let cell_id = node.cell_id;
+        if self.token_index == 0 {
+            self.env.random.start_cell(cell_id);
+        }
+        let stack_before = self.stack.clone();
+        let step_result = interpret_token(&mut self.env, &token, &mut self.stack);
+
+        self.trace.push(TraceStep {
+            cell_id: cell_id,
+            operator: is_operator_token(&token),
+            token: token,
+            stack_before: stack_before,
+            stack_after: self.stack.clone()
+        });
+        self.token_index += 1;
+
+        if let Err(err) = step_result {
+            return self.finish_cell(err)
+        }
+        return DebugEvent::Stepped
+    }
+
+    // Run until the next breakpoint or until all cells are evaluated.
+    pub fn resume(&mut self) -> DebugEvent {
+        while !self.is_finished() {
+            if let Some(breakpoint) = self.breakpoint_hit() {
+                self.paused_at = Some((self.cell_index, self.token_index));
+                return DebugEvent::Breakpoint(breakpoint)
+            }
+            self.step();
+        }
+        return DebugEvent::Finished
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_steps() {
+        let request = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: None, input: String::from("1 + 2 * 3")},
+            ],
+            input: None,
+            ..Default::default()
+        };
+        let mut session = DebugSession::new(request);
+        assert_eq!(session.resume(), DebugEvent::Finished);
+        assert_eq!(session.results[0].output, "7");
+
+        let steps: Vec<String> = session.trace.iter().map(|step| step.describe(&session.env)).collect();
+        assert_eq!(steps, vec![
+            "[] 1 -> [1]",
+            "[1] 2 -> [1, 2]",
+            "[1, 2] 3 -> [1, 2, 3]",
+            "[1, 2, 3] * -> [1, 6]",
+            "[1, 6] + -> [7]",
+        ]);
+        assert_eq!(session.trace[3].operator, Some(SYMBOL_MULTIPLY.symbol));
+        assert_eq!(session.trace[0].operator, None);
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let request = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: Some(String::from("one")), input: String::from("4 - 1")},
+                CellRequest {id: 2, name: None, input: String::from("one * 2 + 1")},
+            ],
+            input: None,
+            ..Default::default()
+        };
+        let mut session = DebugSession::new(request);
+        session.add_breakpoint(Breakpoint::Cell(2));
+        session.add_breakpoint(Breakpoint::Symbol(SYMBOL_PLUS.symbol));
+
+        assert_eq!(session.resume(), DebugEvent::Breakpoint(Breakpoint::Cell(2)));
+        assert_eq!(session.current_cell(), Some(2));
+        assert_eq!(session.inspect_name("one"), Some(String::from("3")));
+
+        // Stops before the addition is applied
+        assert_eq!(session.resume(), DebugEvent::Breakpoint(Breakpoint::Symbol(SYMBOL_PLUS.symbol)));
+        assert_eq!(session.inspect_stack(), "[6, 1]");
+
+        assert_eq!(session.step(), DebugEvent::Stepped);
+        assert_eq!(session.inspect_stack(), "[7]");
+        assert_eq!(session.step(), DebugEvent::CellFinished(2));
+        assert_eq!(session.resume(), DebugEvent::Finished);
+        assert_eq!(session.results[1].output, "7");
+    }
+}
@@ -53,7 +53,6 @@ pub fn get_eval_order(cells: &mut FnvHashMap<u64, Rc<RefCell<Expression>>>) -> V
     // Mark any elements remaining with unmet dependencies as having circular dependencies.
     // TODO: Recursion support
     for (dep_count, mut unmet_dep) in depend_count.drain() {
-        println!("Found unmet dep");
         // Only treat other cells/pointers as unmet dependency
         // Note that any depdency not present in the original cell list may be marked as unmet.
         unmet_dep.set_result(RUNTIME_ERR_CIRCULAR_DEP);
@@ -9,7 +9,11 @@ use avs::expression::Expression;
 use avs::environment::Environment;
 use avs::runtime::RESERVED_SYMBOLS;
 use avs::runtime::ID_SYMBOL_MAP;
-use avs::functions::NativeFn;
+use avs::functions::call_native;
+use avs::registry::FunctionRegistry;
+use avs::objects::{is_class, construct, field_count};
+use avs::collections::{build_list, build_map};
+use avs::gc::collect_garbage;
 use super::parser;
 use super::lexer::*;
 use super::structs::*;
@@ -19,6 +23,9 @@ use avs::operators::*;
 use avs::types::*;
 use avs::constants::*;
 use avs::structs::{ValueType, AvObject, Atom};
+use avs::trace::{Tracer, TraceLevel, Span};
+use fnv::FnvHashMap;
+use std::time::Instant;
 
 
 macro_rules! apply_bin_op {
@@ -31,29 +38,27 @@ macro_rules! apply_bin_op {
 }
 
 pub fn call_function(mut env: &mut Environment, stack: &mut Vec<u64>) -> u64 {
-    // TODO: Stack bounds checks
-    let func_symbol = stack.pop().unwrap();
+    // Postfix order: arguments, argument count, function
+    let (func_symbol, arg_count) = match (stack.pop(), stack.pop()) {
+        (Some(func_symbol), Some(arg_count)) => (func_symbol, f64::from_bits(arg_count) as usize),
+        _ => return RUNTIME_ERR_FN_ARITY
+    };
+    if stack.len() < arg_count {
+        return RUNTIME_ERR_FN_ARITY
+    }
+    let args = stack.split_off(stack.len() - arg_count);
+    if is_class(env, func_symbol) {
+        // Constructor. Takes one argument per field. i.e. DateString(2023, "03", "05")
+        if arg_count != field_count(env, func_symbol) {
+            return RUNTIME_ERR_FN_ARITY
+        }
+        return construct(&mut env, func_symbol, args)
+    }
     if let Some(func_id) = env.lookup(func_symbol) {
         match &func_id.value {
             Some(Atom::FunctionValue(fval)) => {
-                match fval {
-                    NativeFn::Fn2(f2) => {
-                        // TODO: Verify stack size
-                        // Pop in reverse order since stack is in postfix
-                        let b = stack.pop().unwrap();
-                        let a = stack.pop().unwrap();
-                        let fn_result = (f2.func)(&mut env, a, b);
-                        return fn_result
-                    }
-                    NativeFn::Fn1(f1) => {
-                        let a = stack.pop().unwrap();
-                        let fn_result = (f1.func)(&mut env, a);
-                        return fn_result
-                    },
-                    _ => {
-                        return RUNTIME_ERR_FN_UNK
-                    }
-                }
+                let fval = fval.clone();
+                return call_native(&mut env, func_symbol, &fval, &args)
             }
             _ => {
                 return RUNTIME_ERR_FN_EXPECTED
@@ -65,13 +70,20 @@ pub fn call_function(mut env: &mut Environment, stack: &mut Vec<u64>) -> u64 {
 }
 
 pub fn apply_operator(mut env: &mut Environment, operator: u64, mut stack: &mut Vec<u64>) -> u64 {
-    println!("Operator: {}", repr(&env, operator));
-    // print_stacktrace(env, &stack);
+    env.trace_enter(Span::Operator(operator));
+    av_trace!(env, TraceLevel::Debug, "Operator: {}", repr(&env, operator));
+    av_trace!(env, TraceLevel::Trace, "Stack: {}", fmt_stack(&env, &stack));
+    let result = apply_operator_inner(&mut env, operator, &mut stack);
+    env.trace_exit(Span::Operator(operator));
+    return result
+}
 
+fn apply_operator_inner(mut env: &mut Environment, operator: u64, mut stack: &mut Vec<u64>) -> u64 {
     let symbol_index = operator & PAYLOAD_MASK;
     if symbol_index < (RESERVED_SYMBOLS.len() as u64) {
         let symbol = RESERVED_SYMBOLS[symbol_index as usize];
         if symbol.operation.is_some() {
+            env.budget.operators += 1;
             let op_func = symbol.operation.unwrap();
             let b = stack.pop().unwrap();
             let a = stack.pop().unwrap();
@@ -79,10 +91,15 @@ pub fn apply_operator(mut env: &mut Environment, operator: u64, mut stack: &mut
         } else {
             // Handle unary functions and other special cases
             if *symbol == SYMBOL_CALL_FN {
+                env.budget.operators += 1;
                 return call_function(&mut env, &mut stack)
             }
             else if *symbol == SYMBOL_NOT {
+                env.budget.operators += 1;
                 return __av_not(&mut env, stack.pop().unwrap())
+            } else if *symbol == SYMBOL_CLOSE_SQBR || *symbol == SYMBOL_CLOSE_BRACE {
+                env.budget.operators += 1;
+                return collect_elements(&mut env, operator, &mut stack)
             } else {
                 // Emit as value. Ex. True, None, etc.
                 return operator
@@ -90,17 +107,42 @@ pub fn apply_operator(mut env: &mut Environment, operator: u64, mut stack: &mut
         }
     } else {
         // Symbol not found in operators. Emit as-is as a symbol.
-        println!("Unknown symbol {:X}", operator);
+        av_trace!(env, TraceLevel::Warn, "Unknown symbol {:X}", operator);
         return operator
     };
 }
 
-fn is_keyword(symbol: u64) -> bool {
+// Pop the elements pushed since the opening bracket into a list or map.
+fn collect_elements(mut env: &mut Environment, closing: u64, stack: &mut Vec<u64>) -> u64 {
+    let opening = if closing == SYMBOL_CLOSE_SQBR.symbol { SYMBOL_OPEN_SQBR.symbol } else { SYMBOL_OPEN_BRACE.symbol };
+    match stack.iter().rposition(|value| *value == opening) {
+        Some(start) => {
+            let values = stack.split_off(start + 1);
+            stack.pop();
+            if closing == SYMBOL_CLOSE_SQBR.symbol {
+                return build_list(&mut env, values)
+            } else {
+                return build_map(&mut env, values)
+            }
+        },
+        None => return PARSE_ERR_UNMATCHED_PARENS
+    }
+}
+
+// Reserved symbols. Operators and built in function calls are applied rather than looked up.
+pub fn is_keyword(symbol: u64) -> bool {
     return (symbol & PAYLOAD_MASK) < 255;
 }
 
 
 pub fn interpret_expr(mut env: &mut Environment, expression: &Expression) -> u64 {
+    env.trace_enter(Span::Expression(expression.symbol));
+    let result = interpret_expr_inner(&mut env, expression);
+    env.trace_exit(Span::Expression(expression.symbol));
+    return result
+}
+
+fn interpret_expr_inner(mut env: &mut Environment, expression: &Expression) -> u64 {
     // Propagate prior errors up.
     if expression.result.is_some() {
         return expression.result.unwrap();
@@ -110,19 +152,34 @@ pub fn interpret_expr(mut env: &mut Environment, expression: &Expression) -> u64
     let mut expr_stack: Vec<u64> = Vec::with_capacity(expression.parsed.len());
 
     for token in expression.parsed.iter() {
-        println!("token {:?}", token);
-        match token {
-            Atom::SymbolValue(kw) => {
-                // TODO: Check if built in operator or an identifier
-                if is_keyword(*kw) {
-                    let result = apply_operator(&mut env, *kw, &mut expr_stack);
-                    expr_stack.push(result);
-                } else {
-                    // println!("Looking up symbol {:X}", kw);
-                    // Lookup result of symbol
-                    // TODO: Pointer vs symbols
-                    // TODO: Scoping rules
-                    if let Some(identifier) = env.deep_resolve(*kw) {
+        if let Err(err) = interpret_token(&mut env, token, &mut expr_stack) {
+            return err
+        }
+    }
+    av_trace!(env, TraceLevel::Trace, "expr stack {:?} ", expr_stack);
+    // Assert - only one value on expr stack
+    return expr_stack.pop().unwrap();
+}
+
+// Evaluate a single postfix token against the expression stack.
+// Errors which should abort the whole expression are returned as Err.
+pub fn interpret_token(mut env: &mut Environment, token: &Atom, expr_stack: &mut Vec<u64>) -> Result<(), u64> {
+    av_trace!(env, TraceLevel::Trace, "token {:?}", token);
+    env.budget.step()?;
+
+    match token {
+        Atom::SymbolValue(kw) => {
+            // TODO: Check if built in operator or an identifier
+            if is_keyword(*kw) {
+                let result = apply_operator(&mut env, *kw, expr_stack);
+                expr_stack.push(result);
+            } else {
+                // println!("Looking up symbol {:X}", kw);
+                // Lookup result of symbol
+                // TODO: Pointer vs symbols
+                // TODO: Scoping rules
+                match env.resolve_chain(*kw) {
+                    Ok(identifier) => {
                         // println!("Resolved to {:?}", atom);
                         match identifier.value {
                             Some(Atom::NumericValue(num)) => {
@@ -135,30 +192,38 @@ pub fn interpret_expr(mut env: &mut Environment, expression: &Expression) -> u64
                                 expr_stack.push(*kw);
                             }
                         }
-                    } else {
+                    },
+                    Err(INTERPRETER_ERR_LIMIT_DEPTH) => {
+                        return Err(INTERPRETER_ERR_LIMIT_DEPTH)
+                    },
+                    Err(_) => {
                         // println!("Unresolved {:X}", kw);
                         expr_stack.push(*kw);
                     }
                 }
-            }, 
-            Atom::NumericValue(num) => {
-                // f64 -> u64
-                expr_stack.push(num.to_bits());
-            },
-            Atom::StringValue(val) => {
-                // Save object to heap and return pointer
-                // TODO: Non-copying version
-                let symbol_id = env.init_value(Atom::StringValue(val.to_string()));
-                expr_stack.push(symbol_id);
-            },
-            _ => {
-                println!("Unexpected Object/hashmap Atom found?");
             }
+        }, 
+        Atom::NumericValue(num) => {
+            // f64 -> u64
+            expr_stack.push(num.to_bits());
+        },
+        Atom::BigIntValue(_) | Atom::DecimalValue(_) |
+        Atom::DateValue(_) | Atom::DateTimeValue(_) | Atom::DurationValue(_) | Atom::QuantityValue(_) => {
+            // Integer literals too large for a float and decimals are boxed
+            let symbol_id = env.init_value(token.clone());
+            expr_stack.push(symbol_id);
+        },
+        Atom::StringValue(val) => {
+            // Small strings are encoded inline. Others are saved to the heap as a pointer.
+            // TODO: Non-copying version
+            let symbol_id = env.init_string(val.to_string());
+            expr_stack.push(symbol_id);
+        },
+        _ => {
+            av_trace!(env, TraceLevel::Error, "Unexpected Object/hashmap Atom found?");
         }
     }
-    println!("expr stack {:?} ", expr_stack);
-    // Assert - only one value on expr stack
-    return expr_stack.pop().unwrap();
+    return Ok(())
 }
 
 // pub fn init_runtime_input(runtime: &mut Runtime, input: &Option<AvHttpRequest>) {
@@ -168,52 +233,115 @@ pub fn interpret_expr(mut env: &mut Environment, expression: &Expression) -> u64
 // }
 
 
-pub fn interpret_all(mut request: EvalRequest) -> EvalResponse {
+pub fn interpret_all(request: EvalRequest) -> EvalResponse {
+    return interpret_all_with(request, None, &FunctionRegistry::new())
+}
+
+pub fn interpret_all_with_tracer(request: EvalRequest, tracer: Option<Box<dyn Tracer>>) -> EvalResponse {
+    return interpret_all_with(request, tracer, &FunctionRegistry::new())
+}
+
+// Makes the host's registered functions available to cells
+pub fn interpret_all_with_functions(request: EvalRequest, functions: &FunctionRegistry) -> EvalResponse {
+    return interpret_all_with(request, None, functions)
+}
+
+pub fn interpret_all_with(mut request: EvalRequest, tracer: Option<Box<dyn Tracer>>, functions: &FunctionRegistry) -> EvalResponse {
+    let request_start = Instant::now();
     let mut results: Vec<CellResponse> = Vec::with_capacity(request.body.len());
+    // Cell ID -> Metrics. Only populated if requested.
+    let mut cell_metrics: FnvHashMap<u64, CellMetrics> = FnvHashMap::default();
+    let mut totals: Option<EvalMetrics> = None;
+    if request.collect_metrics {
+        totals = Some(EvalMetrics::default());
+    }
+
     // External Global ID -> Internal ID
-    let mut env = construct_ast(&mut request);
+    let metrics_map = if request.collect_metrics { Some(&mut cell_metrics) } else { None };
+    let mut env = construct_ast(&mut request, tracer, functions, metrics_map);
+    if let Some(limits) = &request.limits {
+        env.budget.limits = limits.to_limits();
+    }
+    if let Some(random) = &request.random {
+        env.random = random.to_source();
+    }
 
     // TODO: re-enable
     // init_runtime_input(&mut global_env, &request.input);
 
-    println!("AST: {:?}", env);
+    av_trace!(env, TraceLevel::Trace, "AST: {:?}", env);
 
-    // Need a clone here to avoid multiple references to env.body - though it's kinda silly.
-    for node in env.body.clone().iter() {
+    // Only clones the reference, so the environment can be mutated while iterating.
+    let body = env.body.clone();
+    for node in body.iter() {
+        // Each cell gets its own budget so one runaway cell doesn't starve the rest.
+        env.budget.reset();
+        env.random.start_cell(node.cell_id);
+        env.trace_enter(Span::Cell(node.cell_id));
+        let eval_start = Instant::now();
         let result = interpret_expr(&mut env, &node);
-        println!("Got result {:?} {:?}", result, repr(&env, result));
+        let eval_ns = eval_start.elapsed().as_nanos() as u64;
+        av_trace!(env, TraceLevel::Debug, "Got result {:X} {:?}", result, repr(&env, result));
         
         // Don't double-encode symbols
         // let symbol_id = ast.cell_symbols.as_ref().unwrap().get(&node.id).unwrap();
         let symbol_id = node.symbol;
         env.bind_result(symbol_id, result);
+        let (output, err) = format_result(&mut env, node.cell_id, result);
+        // The cell's intermediate values are unreachable once its result is bound and formatted.
+        if env.gc.should_collect() {
+            let stats = collect_garbage(&mut env, &[]);
+            av_trace!(env, TraceLevel::Debug, "Collected {} values. {} live", stats.freed, stats.live);
+        }
 
-        let mut output = String::from("");
-        let mut err = String::from("");
-        
-        match __av_typeof(result){
-            ValueType::NumericType | ValueType::StringType | ValueType::SymbolType => {
-                output = repr(&mut env, result);
-            },
-            ValueType::ObjectType => {
-                if is_error(result) {
-                    // Errors returned in different field.
-                    err = repr_error(result);
-                } else {
-                    output = repr(&mut env, result);
-                }
-            },
-            ValueType::HashMapType => {}
+        let mut metrics: Option<CellMetrics> = None;
+        if let Some(request_totals) = totals.as_mut() {
+            let mut node_metrics = cell_metrics.remove(&node.cell_id).unwrap_or_default();
+            node_metrics.eval_ns = eval_ns;
+            node_metrics.operators = env.budget.operators;
+            node_metrics.allocations = env.budget.allocations;
+            request_totals.add_cell(&node_metrics);
+            metrics = Some(node_metrics);
         }
 
+        env.trace_exit(Span::Cell(node.cell_id));
         results.push(CellResponse {
             id: node.cell_id,
             output: output,
-            error: err
+            error: err,
+            metrics: metrics
         });
     }
 
+    if let Some(request_totals) = totals.as_mut() {
+        request_totals.total_ns = request_start.elapsed().as_nanos() as u64;
+    }
+
     return EvalResponse {
-        results: results
+        results: results,
+        metrics: totals
+    }
+}
+
+// Format a cell's result into its (output, error) response fields.
+pub fn format_result(mut env: &mut Environment, cell_id: u64, result: u64) -> (String, String) {
+    let mut output = String::from("");
+    let mut err = String::from("");
+    
+    match __av_typeof(result){
+        ValueType::NumericType | ValueType::StringType | ValueType::SymbolType => {
+            output = repr(&mut env, result);
+        },
+        ValueType::ObjectType => {
+            if is_error(result) {
+                // Errors returned in different field.
+                av_trace!(env, TraceLevel::Info, "Cell {} error {:X}", cell_id, result);
+                err = repr_error(result);
+            } else {
+                output = repr(&mut env, result);
+            }
+        },
+        ValueType::HashMapType => {}
     }
+    return (output, err)
 }
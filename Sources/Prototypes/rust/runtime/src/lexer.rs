@@ -8,12 +8,69 @@ use avs::constants::*;
 use avs::structs::Atom;
 use avs::runtime::SYMBOL_ID_MAP;
 use avs::environment::Environment;
+use avs::utils::truncate_symbol;
+use avs::numeric::{parse_int_literal, atom_number};
+use avs::units::{parse_unit, Quantity, DIMENSIONLESS};
+use avs::decimal::Decimal;
+use crate::parser::is_dependency_symbol;
 
 
 fn is_digit(ch: char) -> bool {
     return ch >= '0' && ch <= '9';
 }
 
+// Whether the character would continue an identifier. i.e. the "ays" in 5days
+fn is_identifier_char(ch: Option<char>) -> bool {
+    match ch {
+        Some(ch) => ch.is_alphanumeric() || ch == '_',
+        None => false
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    match text.chars().next() {
+        Some(first) if first.is_alphabetic() || first == '_' => {
+            text.chars().all(|ch| is_identifier_char(Some(ch))) && reserved_keyword(text).is_none()
+        },
+        _ => false
+    }
+}
+
+// Record declarations are a "Name:" line followed by indented fields,
+// separated by commas or newlines. i.e. "Point:\n    x, y"
+// Returns None if the input isn't a declaration.
+pub fn lex_declaration(input: &str) -> Option<Result<(String, Vec<String>)>> {
+    let mut lines = input.lines();
+    let header = lines.next()?.trim_end();
+    let name = header.strip_suffix(':')?.trim();
+    if !is_identifier(name) {
+        return None
+    }
+    let mut fields: Vec<String> = vec![];
+    for line in lines {
+        if line.trim().len() == 0 {
+            continue;
+        }
+        if !line.starts_with(|ch: char| ch.is_whitespace()) {
+            return Some(Err(PARSE_ERR_UNEXPECTED_TOKEN))
+        }
+        for field in line.split(',') {
+            let field = field.trim();
+            if !is_identifier(field) {
+                return Some(Err(PARSE_ERR_UNEXPECTED_TOKEN))
+            }
+            if fields.iter().any(|existing| existing.eq_ignore_ascii_case(field)) {
+                return Some(Err(PARSE_ERR_USED_NAME))
+            }
+            fields.push(field.to_string());
+        }
+    }
+    if fields.len() == 0 {
+        return Some(Err(PARSE_ERR_UNEXPECTED_TOKEN))
+    }
+    return Some(Ok((name.to_string(), fields)))
+}
+
 fn gobble_digits(token: &mut String, it: &mut Peekable<std::str::Chars<'_>>) {
     while let Some(&body) = it.peek() {
         if is_digit(body) {
@@ -27,7 +84,7 @@ fn gobble_digits(token: &mut String, it: &mut Peekable<std::str::Chars<'_>>) {
 
 fn parse_number(it: &mut Peekable<std::str::Chars<'_>>, is_negative: bool) -> Result<Atom> {
     let mut token = String::from("");
-    let mut _is_float = false;       // Unused. Could be used for dedicated int type later.
+    let mut is_float = false;
 
     // Leading decimal digits
     gobble_digits(&mut token, it);
@@ -35,7 +92,7 @@ fn parse_number(it: &mut Peekable<std::str::Chars<'_>>, is_negative: bool) -> Re
     // (Optional) decimal
     if let Some(&decimal) = it.peek() {
         if decimal == '.' {
-            _is_float = true;
+            is_float = true;
             token.push(decimal);
             it.next();
 
@@ -47,7 +104,7 @@ fn parse_number(it: &mut Peekable<std::str::Chars<'_>>, is_negative: bool) -> Re
     // (Optional) Exponent
     if let Some(&exp) = it.peek() {
         if exp == 'e' || exp == 'E' {
-            _is_float = true;
+            is_float = true;
             token.push(exp);
             it.next();
 
@@ -70,6 +127,24 @@ fn parse_number(it: &mut Peekable<std::str::Chars<'_>>, is_negative: bool) -> Re
             gobble_digits(&mut token, it);
         }
     }
+    // Decimal suffix. i.e. 19.99d
+    if let Some(&suffix) = it.peek() {
+        if suffix == 'd' && !is_identifier_char(it.clone().nth(1)) {
+            it.next();
+            return match Decimal::parse(&token) {
+                Some(decimal) => Ok(Atom::DecimalValue(if is_negative { decimal.neg() } else { decimal })),
+                None => Err(PARSE_ERR_INVALID_FLOAT)
+            }
+        }
+    }
+
+    // Integers that don't fit in a float are kept exact
+    if !is_float {
+        if let Some(big_int) = parse_int_literal(&token, is_negative) {
+            return Ok(big_int);
+        }
+    }
+
     // Parse should be sufficient since we've validated format already.
     
     let mut val: f64 = lexical::parse(token);
@@ -79,6 +154,75 @@ fn parse_number(it: &mut Peekable<std::str::Chars<'_>>, is_negative: bool) -> Re
     return Ok(Atom::NumericValue(val));
 }
 
+// Whether the character could be part of a unit after a number. i.e. m/s^2 or s^-1
+fn is_unit_char(ch: char, prev: Option<char>) -> bool {
+    return ch.is_alphanumeric() || ch == '_' || ch == '*' || ch == '/' || ch == '^' || (ch == '-' && prev == Some('^'))
+}
+
+// Whether the user named a cell exactly this, including case.
+// Builtins aren't included, so 5 min is always 5 minutes, and a cell named M doesn't hide 5 m.
+fn is_cell_name(context: &Environment, word: &str) -> bool {
+    match context.lookup_by_name(word.to_string()) {
+        Some(&symbol) if truncate_symbol(symbol) as u64 >= APP_SYMBOL_START => {
+            context.lookup(symbol).and_then(|identifier| identifier.name.as_deref()) == Some(word)
+        },
+        _ => false
+    }
+}
+
+// Units directly after a number. i.e. 5 km or 9.8 m/s^2
+// Uses the longest run which is a valid unit, so 5 m*2 is 5 m times 2.
+// After a space, keywords and cell names take precedence. i.e. 2 t is 2 times a cell called t.
+// Numbers without a unit are returned as-is.
+fn parse_quantity(context: &Environment, it: &mut Peekable<std::str::Chars<'_>>, number: Atom) -> Atom {
+    let value = match atom_number(&number) {
+        Some(value) => value.to_f64(),
+        None => return number
+    };
+    let mut lookahead = it.clone();
+    let mut skipped = 0;
+    while let Some(&' ') | Some(&'\t') = lookahead.peek() {
+        lookahead.next();
+        skipped += 1;
+    }
+    match lookahead.peek() {
+        Some(ch) if ch.is_alphabetic() => {},
+        _ => return number
+    }
+    let mut text = String::from("");
+    while let Some(&ch) = lookahead.peek() {
+        if !is_unit_char(ch, text.chars().last()) {
+            break;
+        }
+        text.push(ch);
+        lookahead.next();
+    }
+    if skipped > 0 {
+        let word: String = text.chars().take_while(|&ch| is_identifier_char(Some(ch))).collect();
+        if reserved_keyword(&word).is_some() || is_cell_name(context, &word) {
+            return number
+        }
+    }
+
+    let mut end = text.len();
+    loop {
+        if let Some(unit) = parse_unit(&text[..end]) {
+            for _ in 0..skipped + text[..end].chars().count() {
+                it.next();
+            }
+            // i.e. 5 m/m
+            if unit.dimension == DIMENSIONLESS {
+                return Atom::NumericValue(value * unit.scale)
+            }
+            return Atom::QuantityValue(Quantity { value: value * unit.scale, unit: unit })
+        }
+        match text[..end].rfind(|ch| ch == '*' || ch == '/' || ch == '^') {
+            Some(operator) => end = operator,
+            None => return number
+        }
+    }
+}
+
 fn parse_string(it: &mut Peekable<std::str::Chars<'_>>) -> Result<Atom> {
     let mut token = String::from("");
     
@@ -125,7 +269,7 @@ fn parse_string(it: &mut Peekable<std::str::Chars<'_>>) -> Result<Atom> {
     if ! _terminated {
         return Err(PARSE_ERR_UNTERM_STR);
     }
-    return Ok(Atom::StringValue(token));
+    return Ok(Atom::StringValue(token.into()));
 }
 
 fn is_delimiter(ch: char) -> bool {
@@ -133,7 +277,7 @@ fn is_delimiter(ch: char) -> bool {
     match ch {
         '(' | ')' | '[' | ']' | '{' | '}' | '"' | '\'' | 
         '.' | ',' | ':' | ';' |
-        '-' | '+' | '*' | '/' | '%' |
+        '-' | '+' | '*' | '/' | '%' | '^' |
         ' ' | '\t' | '\n' => true,
         _ => false
     }
@@ -178,8 +322,13 @@ fn reserved_keyword(token: &str) -> Option<Atom> {
     return None
 }
 
+fn ends_value(symbol: u64) -> bool {
+    return symbol == SYMBOL_CLOSE_PAREN.symbol || symbol == SYMBOL_CLOSE_SQBR.symbol ||
+        symbol == SYMBOL_CLOSE_BRACE.symbol || is_dependency_symbol(symbol)
+}
+
 macro_rules! apply_unary_minus {
-    ($it:expr, $tokens:expr) => ({
+    ($context:expr, $it:expr, $tokens:expr) => ({
         if let Some(next) = $it.peek() {
             match next {
                 '(' => {
@@ -188,7 +337,8 @@ macro_rules! apply_unary_minus {
                     Some(Atom::SymbolValue(SYMBOL_MULTIPLY.symbol))
                 },
                 _ => {
-                    Some(parse_number(&mut $it, true)?)
+                    let number = parse_number(&mut $it, true)?;
+                    Some(parse_quantity($context, &mut $it, number))
                 }
             }
         } else {
@@ -212,15 +362,38 @@ pub fn lex(context: &mut Environment, expr: &str) -> Result<Vec<Atom>> {
                 None
             },
             // Digit start
-            '0'..='9' | '.' => Some(parse_number(&mut it, false)? ),
+            '0'..='9' => {
+                let number = parse_number(&mut it, false)?;
+                Some(parse_quantity(context, &mut it, number))
+            },
+            '.' => {
+                match it.clone().nth(1) {
+                    Some(next) if is_digit(next) => {
+                        let number = parse_number(&mut it, false)?;
+                        Some(parse_quantity(context, &mut it, number))
+                    },
+                    _ => {
+                        // Attribute access. i.e. deadline.year
+                        // The attribute name is passed to the dot operator as a string.
+                        it.next();
+                        tokens.push(Atom::SymbolValue(SYMBOL_DOT.symbol));
+                        let attribute = parse_identifier(&mut it);
+                        if attribute.len() == 0 || is_delimiter(attribute.chars().next().unwrap()) {
+                            return Err(PARSE_ERR_UNEXPECTED_TOKEN);
+                        }
+                        Some(Atom::StringValue(attribute.into()))
+                    }
+                }
+            },
             // Special case for minus sign to differentiate subtraction or unary minus
             '-' => {
                 // If the previous char was beginning of string or another operator
                 if let Some(prev) = tokens.last() {
                     match prev {
-                        Atom::SymbolValue(_kw) => {
+                        // Identifiers and closing brackets end a value, so it's subtraction after them.
+                        Atom::SymbolValue(kw) if !ends_value(*kw) => {
                              it.next();
-                             apply_unary_minus!(it, tokens)
+                             apply_unary_minus!(context, it, tokens)
                         },
                         _ => {
                             it.next();
@@ -230,7 +403,7 @@ pub fn lex(context: &mut Environment, expr: &str) -> Result<Vec<Atom>> {
                 } else {
                     // Beginning of string = unary minus
                     it.next();
-                    apply_unary_minus!(it, tokens)
+                    apply_unary_minus!(context, it, tokens)
                 }
             },
             // Interchangeable single/double quoted strings grouped as single token.
@@ -287,9 +460,38 @@ mod tests {
         // Error on undefined exponents.
         assert_eq!(lex(&mut context, "5.1e").unwrap_err(), PARSE_ERR_INVALID_FLOAT);
         assert_eq!(lex(&mut context, "5.1e ").unwrap_err(), PARSE_ERR_INVALID_FLOAT);
+        assert_eq!(lex(&mut context, "1e-9223372036854775808d").unwrap_err(), PARSE_ERR_INVALID_FLOAT);
         // 30_000_000 syntax support? Stick to standard valid floats for now.
     }
 
+    #[test]
+    fn test_lex_attribute() {
+        let mut context = Environment::new(APP_SYMBOL_START);
+        let symbol = context.define_identifier();
+        context.bind_name(symbol, String::from("deadline"));
+        assert_eq!(lex(&mut context, "deadline.year").unwrap(), [
+            Atom::SymbolValue(symbol), Atom::SymbolValue(SYMBOL_DOT.symbol), Atom::StringValue("year".into())
+        ]);
+        assert_eq!(lex(&mut context, "deadline.").unwrap_err(), PARSE_ERR_UNEXPECTED_TOKEN);
+    }
+
+    #[test]
+    fn test_lex_declaration() {
+        assert_eq!(lex_declaration("Point:\n  x, y"), Some(Ok((String::from("Point"), vec![String::from("x"), String::from("y")]))));
+        assert_eq!(lex_declaration("DateString:\n    year\n    month\n\n    day\n").unwrap().unwrap().1.len(), 3);
+        assert_eq!(lex_declaration("Point:\n  x, X"), Some(Err(PARSE_ERR_USED_NAME)));
+        assert_eq!(lex_declaration("Point:\n  x, 1"), Some(Err(PARSE_ERR_UNEXPECTED_TOKEN)));
+        assert_eq!(lex_declaration("Point:\n  x,"), Some(Err(PARSE_ERR_UNEXPECTED_TOKEN)));
+        assert_eq!(lex_declaration("Point:\nx"), Some(Err(PARSE_ERR_UNEXPECTED_TOKEN)));
+        assert_eq!(lex_declaration("Point:"), Some(Err(PARSE_ERR_UNEXPECTED_TOKEN)));
+        // Not declarations
+        assert_eq!(lex_declaration("1 + 2"), None);
+        assert_eq!(lex_declaration("Point: x, y"), None);
+        assert_eq!(lex_declaration("x: x + 1"), None);
+        assert_eq!(lex_declaration("len(\"a:b\")"), None);
+        assert_eq!(lex_declaration("not: x"), None);
+    }
+
     #[test]
     fn test_lex_unary_minus() {
         let mut context = Environment::new(APP_SYMBOL_START);
@@ -302,10 +504,42 @@ mod tests {
         assert_eq!(lex(&mut context, "5 + -.05").unwrap(), [numeric_literal!(5.0), Atom::SymbolValue(SYMBOL_PLUS.symbol), numeric_literal!(-0.05)]);
         assert_eq!(lex(&mut context, "-(4) + 2").unwrap(), [numeric_literal!(-1.0), Atom::SymbolValue(SYMBOL_MULTIPLY.symbol), Atom::SymbolValue(SYMBOL_OPEN_PAREN.symbol), 
          numeric_literal!(4.0), Atom::SymbolValue(SYMBOL_CLOSE_PAREN.symbol), Atom::SymbolValue(SYMBOL_PLUS.symbol), numeric_literal!(2.0)] );
+        assert_eq!(lex(&mut context, "(1) - 2").unwrap(), [Atom::SymbolValue(SYMBOL_OPEN_PAREN.symbol), numeric_literal!(1.0),
+            Atom::SymbolValue(SYMBOL_CLOSE_PAREN.symbol), Atom::SymbolValue(SYMBOL_MINUS.symbol), numeric_literal!(2.0)]);
         assert_eq!(lex(&mut context, "5 * -(2)").unwrap(), [numeric_literal!(5.0), Atom::SymbolValue(SYMBOL_MULTIPLY.symbol), numeric_literal!(-1.0), 
             Atom::SymbolValue(SYMBOL_MULTIPLY.symbol), Atom::SymbolValue(SYMBOL_OPEN_PAREN.symbol), numeric_literal!(2.0), Atom::SymbolValue(SYMBOL_CLOSE_PAREN.symbol) ]);
     }
 
+    #[test]
+    fn test_lex_units() {
+        let mut context = Environment::new(APP_SYMBOL_START);
+        let tokens = lex(&mut context, "9.8 m/s^2 * 2").unwrap();
+        match &tokens[0] {
+            Atom::QuantityValue(quantity) => {
+                assert_eq!(quantity.unit.name.as_ref(), "m/s^2");
+                assert_eq!(quantity.value, 9.8);
+            },
+            _ => panic!("Expected a quantity")
+        }
+        assert_eq!(tokens[1..], [Atom::SymbolValue(SYMBOL_MULTIPLY.symbol), numeric_literal!(2.0)]);
+        // The longest valid unit is used
+        let tokens = lex(&mut context, "-5 km*2").unwrap();
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[0], Atom::QuantityValue(Quantity { value: -5000.0, unit: parse_unit("km").unwrap() }));
+        assert_eq!(lex(&mut context, "5 m/m").unwrap(), [numeric_literal!(5.0)]);
+        // Not a unit
+        assert_eq!(lex(&mut context, "5 parsecs"), Err(PARSE_ERR_UNK_SYMBOL));
+        // Names take precedence over units after a space, but not directly after the number
+        let symbol = context.define_identifier();
+        context.bind_name(symbol, String::from("t"));
+        assert_eq!(lex(&mut context, "2 t").unwrap(), [numeric_literal!(2.0), Atom::SymbolValue(symbol)]);
+        assert_eq!(lex(&mut context, "2t").unwrap(), [Atom::QuantityValue(Quantity { value: 2000.0, unit: parse_unit("t").unwrap() })]);
+        // Only names of cells, matching case
+        let symbol = context.define_identifier();
+        context.bind_name(symbol, String::from("M"));
+        assert_eq!(lex(&mut context, "5 m").unwrap(), [Atom::QuantityValue(Quantity { value: 5.0, unit: parse_unit("m").unwrap() })]);
+    }
+
     #[test]
     fn test_reserved_keyword() {
         assert_eq!(reserved_keyword("not"), Some(Atom::SymbolValue(SYMBOL_NOT.symbol)));
@@ -319,11 +553,11 @@ mod tests {
 
     #[test]
     fn test_lex_string() {
-        assert_eq!(parse_string(&mut r#""hello world""#.chars().peekable()).unwrap(), Atom::StringValue(String::from("hello world")) );
+        assert_eq!(parse_string(&mut r#""hello world""#.chars().peekable()).unwrap(), Atom::StringValue("hello world".into()) );
         // Terminates at end of quote
-        assert_eq!(parse_string(&mut r#""hello world" test"#.chars().peekable()).unwrap(), Atom::StringValue(String::from("hello world")) );
+        assert_eq!(parse_string(&mut r#""hello world" test"#.chars().peekable()).unwrap(), Atom::StringValue("hello world".into()) );
         // Matches quotes
-        assert_eq!(parse_string(&mut r#"'hello " world' test"#.chars().peekable()).unwrap(), Atom::StringValue(String::from("hello \" world")) );
+        assert_eq!(parse_string(&mut r#"'hello " world' test"#.chars().peekable()).unwrap(), Atom::StringValue("hello \" world".into()) );
         // Error on unterminated string
         assert_eq!(parse_string(&mut r#"'hello"#.chars().peekable()).unwrap_err(), PARSE_ERR_UNTERM_STR);
     }
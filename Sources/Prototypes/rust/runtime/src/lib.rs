@@ -1,5 +1,7 @@
 #[macro_use]
 extern crate serde_derive;
+#[macro_use]
+extern crate avs;
 
 use std::result;
 
@@ -12,6 +14,7 @@ pub mod format;
 pub mod dependency;
 pub mod ast;
 pub mod structs;
+pub mod debugger;
 mod tests;
 
 
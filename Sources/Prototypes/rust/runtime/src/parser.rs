@@ -19,9 +19,25 @@ fn get_op_precedence(symbol: u64) -> u8 {
     return 255
 }
 
-pub fn is_operator(symbol: u64) -> bool {
-    // TODO: Verify. 16 or 30?
-    return (symbol & PAYLOAD_MASK) <= 16;
+// Parens, list and map brackets all group a sub-expression
+fn is_open_bracket(symbol: u64) -> bool {
+    return symbol == SYMBOL_OPEN_PAREN.symbol || symbol == SYMBOL_OPEN_SQBR.symbol ||
+        symbol == SYMBOL_OPEN_BRACE.symbol
+}
+
+fn is_close_bracket(symbol: u64) -> bool {
+    return symbol == SYMBOL_CLOSE_PAREN.symbol || symbol == SYMBOL_CLOSE_SQBR.symbol ||
+        symbol == SYMBOL_CLOSE_BRACE.symbol
+}
+
+// Separates arguments, list elements and map entries
+fn is_separator(symbol: u64) -> bool {
+    return symbol == SYMBOL_COMMA.symbol || symbol == SYMBOL_COLON.symbol
+}
+
+// Only identifiers can be called. Other keywords like ( or [ can precede a paren too.
+fn is_callable(symbol: u64) -> bool {
+    return (symbol & PAYLOAD_MASK) >= 255
 }
 
 pub fn is_dependency_symbol(symbol: u64) -> bool {
@@ -39,22 +55,48 @@ pub fn apply_operator_precedence(expression: &mut Expression, infix: &mut Vec<At
     let mut operator_stack: Vec<u64> = Vec::with_capacity(infix.len());
     // The callee will generate used_by from this.
     let mut depends_on: Vec<u64> = Vec::new();
+    // (Separators, Has tokens) for each open bracket. Used to count function arguments.
+    let mut groups: Vec<(usize, bool)> = Vec::new();
 
     for token in infix.drain(..) {
+        if let Atom::SymbolValue(kw) = &token {
+            if is_separator(*kw) {
+                if let Some(group) = groups.last_mut() {
+                    group.0 += 1;
+                }
+            } else if !is_close_bracket(*kw) {
+                if let Some(group) = groups.last_mut() {
+                    group.1 = true;
+                }
+            }
+        } else if let Some(group) = groups.last_mut() {
+            group.1 = true;
+        }
+
         match &token {
             Atom::SymbolValue(kw_addr) => {
                 let kw = *kw_addr;
                 if kw == SYMBOL_OPEN_PAREN.symbol {
+                    groups.push((0, false));
                     operator_stack.push(kw)
-                } else if kw == SYMBOL_COMMA.symbol {
-                    // Denotes end of one sub-expression. i.e. min(1 * 2, 2 + 2). Flush.
+                } else if kw == SYMBOL_OPEN_SQBR.symbol || kw == SYMBOL_OPEN_BRACE.symbol {
+                    // The opening bracket marks where the elements start on the stack.
+                    groups.push((0, false));
+                    postfix.push(Atom::SymbolValue(kw));
+                    operator_stack.push(kw)
+                } else if is_separator(kw) {
+                    // Denotes end of one sub-expression. i.e. min(1 * 2, 2 + 2) or {"a": 1}. Flush.
                     while let Some(op) = operator_stack.last() {
                         if *op == SYMBOL_COMMA.symbol {
                             operator_stack.pop();
-                        } else if *op == SYMBOL_OPEN_PAREN.symbol {
+                        } else if is_open_bracket(*op) {
                             break;
                         } else {
-                            postfix.push(Atom::SymbolValue(operator_stack.pop().unwrap()))
+                            let stack_symbol = operator_stack.pop().unwrap();
+                            if is_dependency_symbol(stack_symbol) {
+                                depends_on.push(stack_symbol);
+                            }
+                            postfix.push(Atom::SymbolValue(stack_symbol))
                         }
                     }
                 } else if kw == SYMBOL_CLOSE_PAREN.symbol {
@@ -65,7 +107,13 @@ pub fn apply_operator_precedence(expression: &mut Expression, infix: &mut Vec<At
                         if op == SYMBOL_OPEN_PAREN.symbol {
                                 found = true;
                                 break;
+                        } else if is_open_bracket(op) {
+                            break;
                         } else {
+                            // Identifiers within function arguments are dependencies as well
+                            if is_dependency_symbol(op) {
+                                depends_on.push(op);
+                            }
                             postfix.push(Atom::SymbolValue(op))
                         }
                     }
@@ -74,31 +122,60 @@ pub fn apply_operator_precedence(expression: &mut Expression, infix: &mut Vec<At
                         expression.set_result(PARSE_ERR_UNMATCHED_PARENS);
                         return;
                     }
-                    
+                    let (separators, has_tokens) = groups.pop().unwrap_or((0, false));
+
                     // Check for function call
                     if let Some(maybe_fn) = operator_stack.last() {
-                        if !is_operator(*maybe_fn) {
+                        if is_callable(*maybe_fn) {
                             // TODO: check if function
                             // TODO: Namespace/module support
+                            // The argument count goes before the function, so calls can check arity.
+                            let arg_count = if has_tokens || separators > 0 { separators + 1 } else { 0 };
+                            postfix.push(Atom::NumericValue(arg_count as f64));
                             postfix.push(Atom::SymbolValue(operator_stack.pop().unwrap()));
                             postfix.push(Atom::SymbolValue(SYMBOL_CALL_FN.symbol));
                         }
                     }
+                } else if kw == SYMBOL_CLOSE_SQBR.symbol || kw == SYMBOL_CLOSE_BRACE.symbol {
+                    let opening = if kw == SYMBOL_CLOSE_SQBR.symbol { SYMBOL_OPEN_SQBR.symbol } else { SYMBOL_OPEN_BRACE.symbol };
+                    let mut found = false;
+                    while let Some(op) = operator_stack.pop() {
+                        if op == opening {
+                            found = true;
+                            break;
+                        } else if is_open_bracket(op) {
+                            break;
+                        } else {
+                            if is_dependency_symbol(op) {
+                                depends_on.push(op);
+                            }
+                            postfix.push(Atom::SymbolValue(op))
+                        }
+                    }
+                    if found == false {
+                        expression.set_result(PARSE_ERR_UNMATCHED_PARENS);
+                        return;
+                    }
+                    groups.pop();
+                    // The interpreter collects everything since the opening bracket
+                    postfix.push(Atom::SymbolValue(kw));
                 } else {
                     // For all other operators, flush higher or equal level operators
-                    // All operators are left associative in our system right now. (else, equals doesn't get pushed)
+                    // Operators are left associative, except for ^. (else, equals doesn't get pushed)
                     let my_precedence = get_op_precedence(kw);
+                    let is_right_assoc = kw == SYMBOL_CARET.symbol;
                     // Is operator. Use precedence.
                     while operator_stack.len() > 0 {
                         let op_peek_last = operator_stack.last().unwrap();
                         // Skip any items that aren't really operators.
-                        if *op_peek_last == SYMBOL_OPEN_PAREN.symbol {
+                        if is_open_bracket(*op_peek_last) {
                             break;
                         }
 
 
                         let other_precedence = get_op_precedence(*op_peek_last);
-                        if other_precedence >= my_precedence {        // output any higher priority operators.
+                        // output any higher priority operators.
+                        if other_precedence > my_precedence || (other_precedence == my_precedence && !is_right_assoc) {
                             let stack_symbol = operator_stack.pop().unwrap();
                             // Dependency is managed at cell/pointer level. Treat built-in symbols as met.
                             if is_dependency_symbol(stack_symbol) {
@@ -120,7 +197,15 @@ pub fn apply_operator_precedence(expression: &mut Expression, infix: &mut Vec<At
                 }
             },
             Atom::NumericValue(_lit) => postfix.push(token),
+            Atom::BigIntValue(_lit) => postfix.push(token),
+            Atom::DecimalValue(_lit) => postfix.push(token),
+            Atom::DateValue(_lit) => postfix.push(token),
+            Atom::DateTimeValue(_lit) => postfix.push(token),
+            Atom::DurationValue(_lit) => postfix.push(token),
+            Atom::QuantityValue(_lit) => postfix.push(token),
             Atom::StringValue(_lit) => postfix.push(token),
+            Atom::ListValue(_lit) => postfix.push(token),
+            Atom::MapValue(_lit) => postfix.push(token),
             Atom::ObjectValue(_lit) => postfix.push(token),     // Should not happen
             Atom::HashMapValue(_lit) => postfix.push(token),     // Should not happen
             Atom::FunctionValue(_lit) => postfix.push(token)
@@ -132,8 +217,7 @@ pub fn apply_operator_precedence(expression: &mut Expression, infix: &mut Vec<At
     operator_stack.reverse();
     for op_kw in operator_stack.drain(..) {
         // All of them should be keywords
-        if op_kw == SYMBOL_OPEN_PAREN.symbol {
-            println!("Invalid paren in drain operator stack");
+        if is_open_bracket(op_kw) {
             expression.set_result(PARSE_ERR_UNMATCHED_PARENS);
             return;
         }
@@ -260,4 +344,42 @@ mod tests {
         apply_operator_precedence(&mut expr2, &mut input2);
         assert_eq!(expr2.parsed, output2);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_list() {
+        // [(1), 2 + 3] = [ 1 2 3 + ]
+        let mut input: Vec<Atom> = vec![
+            Atom::SymbolValue(SYMBOL_OPEN_SQBR.symbol),
+            Atom::SymbolValue(SYMBOL_OPEN_PAREN.symbol),
+            Atom::NumericValue(1.0),
+            Atom::SymbolValue(SYMBOL_CLOSE_PAREN.symbol),
+            Atom::SymbolValue(SYMBOL_COMMA.symbol),
+            Atom::NumericValue(2.0),
+            Atom::SymbolValue(SYMBOL_PLUS.symbol),
+            Atom::NumericValue(3.0),
+            Atom::SymbolValue(SYMBOL_CLOSE_SQBR.symbol)
+        ];
+        // The opening bracket isn't a function, even though it precedes a paren
+        let output: Vec<Atom> = vec![
+            Atom::SymbolValue(SYMBOL_OPEN_SQBR.symbol),
+            Atom::NumericValue(1.0),
+            Atom::NumericValue(2.0),
+            Atom::NumericValue(3.0),
+            Atom::SymbolValue(SYMBOL_PLUS.symbol),
+            Atom::SymbolValue(SYMBOL_CLOSE_SQBR.symbol)
+        ];
+        let mut expr = Expression::new(APP_SYMBOL_START, "".to_string());
+        apply_operator_precedence(&mut expr, &mut input);
+        assert_eq!(expr.parsed, output);
+
+        // Mismatched brackets. [1)
+        let mut input2: Vec<Atom> = vec![
+            Atom::SymbolValue(SYMBOL_OPEN_SQBR.symbol),
+            Atom::NumericValue(1.0),
+            Atom::SymbolValue(SYMBOL_CLOSE_PAREN.symbol)
+        ];
+        let mut expr2 = Expression::new(APP_SYMBOL_START, "".to_string());
+        apply_operator_precedence(&mut expr2, &mut input2);
+        assert_eq!(expr2.result, Some(PARSE_ERR_UNMATCHED_PARENS));
+    }
+}
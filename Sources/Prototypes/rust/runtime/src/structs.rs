@@ -1,13 +1,62 @@
-#[derive(Serialize, PartialEq, Debug, Eq, Ord, PartialOrd)]
+use avs::limits::EvalLimits;
+use avs::random::RandomSource;
+use fnv::FnvHashMap;
+use std::time::Duration;
+
+// Optional evaluation statistics for a cell. Durations are in nanoseconds.
+#[derive(Serialize, PartialEq, Debug, Eq, Ord, PartialOrd, Clone, Default)]
+pub struct CellMetrics {
+    pub lex_ns: u64,
+    pub parse_ns: u64,
+    pub eval_ns: u64,
+    pub operators: u64,
+    pub allocations: u64,
+    // Whether the result came from cache. Always false until results are cached across requests.
+    pub cached: bool
+}
+
+// Request-wide totals across all cells.
+#[derive(Serialize, PartialEq, Debug, Clone, Default)]
+pub struct EvalMetrics {
+    pub cells: u64,
+    pub cached_cells: u64,
+    pub lex_ns: u64,
+    pub parse_ns: u64,
+    pub eval_ns: u64,
+    pub operators: u64,
+    pub allocations: u64,
+    // Wall-clock time for the whole request, including dependency ordering.
+    pub total_ns: u64
+}
+
+impl EvalMetrics {
+    pub fn add_cell(&mut self, cell: &CellMetrics) {
+        self.cells += 1;
+        if cell.cached {
+            self.cached_cells += 1;
+        }
+        self.lex_ns += cell.lex_ns;
+        self.parse_ns += cell.parse_ns;
+        self.eval_ns += cell.eval_ns;
+        self.operators += cell.operators;
+        self.allocations += cell.allocations;
+    }
+}
+
+#[derive(Serialize, PartialEq, Debug, Eq, Ord, PartialOrd, Default)]
 pub struct CellResponse {
     pub id: u64,
     pub output: String,
-    pub error: String
+    pub error: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<CellMetrics>
 }
 
 #[derive(Serialize, PartialEq, Debug)]
 pub struct EvalResponse {
-    pub results: Vec<CellResponse>
+    pub results: Vec<CellResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<EvalMetrics>
 }
 
 #[derive(Deserialize,Debug)]
@@ -26,8 +75,63 @@ pub struct AvHttpRequest {
 }
 
 
-#[derive(Deserialize,Debug)]
+// Optional resource limits applied to each cell. Unset fields are unlimited.
+#[derive(Deserialize,Debug,Default)]
+pub struct LimitsRequest {
+    pub max_steps: Option<u64>,
+    pub max_depth: Option<u32>,
+    pub max_allocations: Option<u64>,
+    // Checked every 256 steps rather than continuously.
+    pub timeout_ms: Option<u64>,
+    pub solver_tolerance: Option<f64>,
+    pub solver_iterations: Option<u32>
+}
+
+impl LimitsRequest {
+    pub fn to_limits(&self) -> EvalLimits {
+        let mut limits = EvalLimits::default();
+        limits.max_steps = self.max_steps;
+        if let Some(max_depth) = self.max_depth {
+            limits.max_depth = max_depth;
+        }
+        limits.max_allocations = self.max_allocations;
+        limits.timeout = self.timeout_ms.map(Duration::from_millis);
+        if let Some(tolerance) = self.solver_tolerance {
+            limits.solver_tolerance = tolerance;
+        }
+        if let Some(iterations) = self.solver_iterations {
+            limits.solver_iterations = iterations;
+        }
+        return limits
+    }
+}
+
+// Seed for the random builtins. A cell's random values only change when the seed or its run count does.
+#[derive(Deserialize,Debug,Default)]
+pub struct RandomRequest {
+    pub seed: u64,
+    // Cell ID -> Number of times the user explicitly re-ran that cell
+    #[serde(default)]
+    pub runs: FnvHashMap<u64, u64>
+}
+
+impl RandomRequest {
+    pub fn to_source(&self) -> RandomSource {
+        return RandomSource::new(self.seed, self.runs.clone())
+    }
+}
+
+
+#[derive(Deserialize,Debug,Default)]
 pub struct EvalRequest {
     pub body: Vec<CellRequest>,
-    pub input: Option<AvHttpRequest>
+    pub input: Option<AvHttpRequest>,
+    #[serde(default)]
+    pub limits: Option<LimitsRequest>,
+    // Unset = seed 0
+    #[serde(default)]
+    pub random: Option<RandomRequest>,
+    // Include timing and evaluation statistics in the response.
+    #[serde(default)]
+    pub collect_metrics: bool
 }
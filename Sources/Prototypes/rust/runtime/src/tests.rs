@@ -10,6 +10,7 @@ use crate::lexer::lex;
     use avs::constants::{SYMBOL_TRUE, SYMBOL_FALSE};
     use avs::environment::Environment;
     use avs::expression::Expression;
+    use avs::gc::DEFAULT_GC_THRESHOLD;
 
     use crate::interpreter;
     use crate::structs::*;
@@ -129,6 +130,26 @@ use crate::lexer::lex;
     }
 
 
+    #[test]
+    fn test_reval_equality() {
+        read_eval_check!("1 == 1", SYMBOL_TRUE.symbol);
+        read_eval_check!("1 != 1", SYMBOL_FALSE.symbol);
+        read_eval_check!("\"abc\" == \"abc\"", SYMBOL_TRUE.symbol);
+        read_eval_check!("\"abc\" == \"abd\"", SYMBOL_FALSE.symbol);
+        read_eval_check!("\"Hi\" + \"!\" == \"Hi!\"", SYMBOL_TRUE.symbol);
+        read_eval_check!("\"Hello \" + \"World\" == \"Hello World\"", SYMBOL_TRUE.symbol);
+        read_eval_check!("\"Hello World\" != \"Hello\"", SYMBOL_TRUE.symbol);
+    }
+
+    #[test]
+    fn test_reval_string_comparison() {
+        read_eval_check!("\"abc\" < \"abd\"", SYMBOL_TRUE.symbol);
+        read_eval_check!("\"ab\" < \"abc\"", SYMBOL_TRUE.symbol);
+        read_eval_check!("\"banana split\" > \"apple pie\"", SYMBOL_TRUE.symbol);
+        read_eval_check!("\"b\" >= \"apple pie\"", SYMBOL_TRUE.symbol);
+        read_eval_check!("\"\" < \"a\"", SYMBOL_TRUE.symbol);
+    }
+
     #[test]
     fn test_program_eval() {
         let cell_a = CellRequest {id: 1, name: Some(String::from("one")), input: String::from("1 + 1")};
@@ -137,7 +158,8 @@ use crate::lexer::lex;
         // Can't just have single value inputs anymore, need cells as inputs
         let mut program = EvalRequest {
             body: Vec::new(),
-            input: None
+            input: None,
+            ..Default::default()
         };
         program.body.push(cell_a);
         program.body.push(cell_b);
@@ -147,13 +169,15 @@ use crate::lexer::lex;
         let expected_a = CellResponse {
             id: 1, 
             output: String::from("2"),
-            error: String::from("")
+            error: String::from(""),
+            ..Default::default()
         };
 
         let expected_b = CellResponse {
             id: 2, 
             output: String::from("3"),
-            error: String::from("")
+            error: String::from(""),
+            ..Default::default()
         };
 
         let mut expected_results = Vec::new();
@@ -172,7 +196,8 @@ use crate::lexer::lex;
         // Can't just have single value inputs anymore, need cells as inputs
         let mut program = EvalRequest {
             body: Vec::new(),
-            input: None
+            input: None,
+            ..Default::default()
         };
         program.body.push(cell_a);
         program.body.push(cell_b);
@@ -183,13 +208,15 @@ use crate::lexer::lex;
         let expected_a = CellResponse {
             id: 1, 
             output: String::from("2"),
-            error: String::from("")
+            error: String::from(""),
+            ..Default::default()
         };
 
         let expected_b = CellResponse {
             id: 2, 
             output: String::from("5"),
-            error: String::from("")
+            error: String::from(""),
+            ..Default::default()
         };
 
         let mut expected_results = Vec::new();
@@ -209,7 +236,8 @@ use crate::lexer::lex;
         // Can't just have single value inputs anymore, need cells as inputs
         let mut program = EvalRequest {
             body: Vec::new(),
-            input: None
+            input: None,
+            ..Default::default()
         };
         program.body.push(cell_a);
         program.body.push(cell_b);
@@ -220,19 +248,22 @@ use crate::lexer::lex;
         let expected_a = CellResponse {
             id: 1, 
             output: String::from("2"),
-            error: String::from("")
+            error: String::from(""),
+            ..Default::default()
         };
 
         let expected_b = CellResponse {
             id: 2, 
             output: String::from("2"),
-            error: String::from("")
+            error: String::from(""),
+            ..Default::default()
         };
 
         let expected_c = CellResponse {
             id: 3, 
             output: String::from("2"),
-            error: String::from("")
+            error: String::from(""),
+            ..Default::default()
         };        
 
         let mut expected_results = Vec::new();
@@ -254,7 +285,8 @@ use crate::lexer::lex;
         // Can't just have single value inputs anymore, need cells as inputs
         let mut program = EvalRequest {
             body: Vec::new(),
-            input: None
+            input: None,
+            ..Default::default()
         };
         program.body.push(cell_a);
         program.body.push(cell_b);
@@ -265,19 +297,22 @@ use crate::lexer::lex;
         let expected_a = CellResponse {
             id: 1, 
             output: String::from("2"),
-            error: String::from("")
+            error: String::from(""),
+            ..Default::default()
         };
 
         let expected_b = CellResponse {
             id: 2, 
             output: String::from("2"),
-            error: String::from("")
+            error: String::from(""),
+            ..Default::default()
         };
 
         let expected_c = CellResponse {
             id: 3, 
             output: String::from("2"),
-            error: String::from("")
+            error: String::from(""),
+            ..Default::default()
         };        
 
         let mut expected_results = Vec::new();
@@ -296,7 +331,8 @@ use crate::lexer::lex;
         // Can't just have single value inputs anymore, need cells as inputs
         let mut program = EvalRequest {
             body: vec![cell_a],
-            input: None
+            input: None,
+            ..Default::default()
         };
         let i_result = interpreter::interpret_all(program);
         println!("{:?}", i_result);
@@ -311,13 +347,14 @@ use crate::lexer::lex;
                 CellRequest {id: 1, name: Some(String::from("one")), input: String::from("True")},
                 CellRequest {id: 2, name: Some(String::from("two")), input: String::from("one")}
             ],
-            input: None
+            input: None,
+            ..Default::default()
         };
         let i_result = interpreter::interpret_all(program);
         println!("{:?}", i_result);
         let expected_results = vec![
-            CellResponse { id: 1, output: String::from("True"), error: String::from("") },
-            CellResponse { id: 2, output: String::from("True"), error: String::from("") },
+            CellResponse { id: 1, output: String::from("True"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 2, output: String::from("True"), error: String::from(""), ..Default::default() },
         ];
         assert_eq!(i_result.results, expected_results);
     }
@@ -330,12 +367,13 @@ use crate::lexer::lex;
                 CellRequest {id: 1, name: Some(String::from("one")), input: String::from("min(4, 3)")},
                 CellRequest {id: 2, name: Some(String::from("two")), input: String::from("min(3, 4)")}
             ],
-            input: None
+            input: None,
+            ..Default::default()
         };
         let i_result = interpreter::interpret_all(program);
         let expected_results = vec![
-            CellResponse { id: 1, output: String::from("3"), error: String::from("") },
-            CellResponse { id: 2, output: String::from("3"), error: String::from("") },
+            CellResponse { id: 1, output: String::from("3"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 2, output: String::from("3"), error: String::from(""), ..Default::default() },
         ];
         assert_eq!(i_result.results, expected_results);
     }
@@ -348,16 +386,691 @@ use crate::lexer::lex;
                 CellRequest {id: 1, name: Some(String::from("one")), input: String::from("min(2 * 2, 2 + 1)")},
                 CellRequest {id: 2, name: Some(String::from("two")), input: String::from("min(2, max(1, 4))")}
             ],
-            input: None
+            input: None,
+            ..Default::default()
         };
         let i_result = interpreter::interpret_all(program);
         let expected_results = vec![
-            CellResponse { id: 1, output: String::from("3"), error: String::from("") },
-            CellResponse { id: 2, output: String::from("2"), error: String::from("") },
+            CellResponse { id: 1, output: String::from("3"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 2, output: String::from("2"), error: String::from(""), ..Default::default() },
+        ];
+        assert_eq!(i_result.results, expected_results);
+    }
+
+
+    #[test]
+    fn test_builtin_len() {
+        let program = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: None, input: String::from("len(\"\")")},
+                CellRequest {id: 2, name: None, input: String::from("len(\"Hello\")")},
+                CellRequest {id: 3, name: Some(String::from("greeting")), input: String::from("\"Hello \" + \"World\"")},
+                CellRequest {id: 4, name: None, input: String::from("len(greeting)")},
+                CellRequest {id: 5, name: None, input: String::from("len(\"caf\u{e9} au lait\")")},
+                CellRequest {id: 6, name: None, input: String::from("len(1)")},
+            ],
+            input: None,
+            ..Default::default()
+        };
+        let mut i_result = interpreter::interpret_all(program);
+        let expected_results = vec![
+            CellResponse { id: 1, output: String::from("0"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 2, output: String::from("5"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 3, output: String::from("\"Hello World\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 4, output: String::from("11"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 5, output: String::from("12"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 6, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_EXPECTED_STR), ..Default::default() },
+        ];
+        i_result.results.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(i_result.results, expected_results);
+    }
+
+
+    #[test]
+    fn test_big_integers() {
+        let program = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: None, input: String::from("9007199254740991 + 2")},
+                CellRequest {id: 2, name: Some(String::from("big")), input: String::from("123456789012345678901234567890")},
+                CellRequest {id: 3, name: None, input: String::from("big * big")},
+                CellRequest {id: 4, name: None, input: String::from("big + -123456789012345678901234567000")},
+                CellRequest {id: 5, name: None, input: String::from("big / -10")},
+                CellRequest {id: 6, name: None, input: String::from("big > 9007199254740991")},
+                CellRequest {id: 7, name: None, input: String::from("big == big + 0")},
+                CellRequest {id: 8, name: None, input: String::from("1e20")},
+            ],
+            input: None,
+            ..Default::default()
+        };
+        let mut i_result = interpreter::interpret_all(program);
+        let expected_results = vec![
+            CellResponse { id: 1, output: String::from("9007199254740993"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 2, output: String::from("123456789012345678901234567890"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 3, output: String::from("15241578753238836750495351562536198787501905199875019052100"), error: String::from(""), ..Default::default() },
+            // Demoted back to a regular number
+            CellResponse { id: 4, output: String::from("890"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 5, output: String::from("-12345678901234567890123456789"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 6, output: String::from("True"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 7, output: String::from("True"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 8, output: String::from("100000000000000000000"), error: String::from(""), ..Default::default() },
+        ];
+        i_result.results.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(i_result.results, expected_results);
+    }
+
+
+    #[test]
+    fn test_decimals() {
+        let program = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: None, input: String::from("0.1d + 0.2d")},
+                CellRequest {id: 2, name: Some(String::from("price")), input: String::from("19.99d")},
+                CellRequest {id: 3, name: Some(String::from("total")), input: String::from("price * 3 + 0.5")},
+                CellRequest {id: 4, name: None, input: String::from("decimal(total / 7, 2)")},
+                CellRequest {id: 5, name: None, input: String::from("decimal(0.125, 2)")},
+                CellRequest {id: 6, name: None, input: String::from("decimal_round(0.125, 2, \"half_up\")")},
+                CellRequest {id: 7, name: None, input: String::from("-1.5e-8d")},
+                CellRequest {id: 8, name: None, input: String::from("price > 19.98 and price == 19.990")},
+                CellRequest {id: 9, name: None, input: String::from("decimal(1, -1)")},
+                CellRequest {id: 10, name: None, input: String::from("decimal_round(1, 2, \"up\")")},
+                CellRequest {id: 11, name: None, input: String::from("price / 0")},
+            ],
+            input: None,
+            ..Default::default()
+        };
+        let mut i_result = interpreter::interpret_all(program);
+        let expected_results = vec![
+            CellResponse { id: 1, output: String::from("0.3"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 2, output: String::from("19.99"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 3, output: String::from("60.47"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 4, output: String::from("8.64"), error: String::from(""), ..Default::default() },
+            // Banker's rounding by default
+            CellResponse { id: 5, output: String::from("0.12"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 6, output: String::from("0.13"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 7, output: String::from("-0.000000015"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 8, output: String::from("True"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 9, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_INVALID_ARG), ..Default::default() },
+            CellResponse { id: 10, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_INVALID_ARG), ..Default::default() },
+            CellResponse { id: 11, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_DIV_Z), ..Default::default() },
+        ];
+        i_result.results.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(i_result.results, expected_results);
+    }
+
+
+    #[test]
+    fn test_dates() {
+        let program = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: None, input: String::from("date(\"2020-03-01\") - date(\"2020-02-01\")")},
+                CellRequest {id: 2, name: Some(String::from("start")), input: String::from("date(\"2021-12-20\")")},
+                CellRequest {id: 3, name: Some(String::from("deadline")), input: String::from("date(\"2022-01-10\")")},
+                CellRequest {id: 4, name: None, input: String::from("(deadline - start).days")},
+                CellRequest {id: 5, name: None, input: String::from("deadline.year")},
+                CellRequest {id: 6, name: None, input: String::from("deadline + days(7)")},
+                CellRequest {id: 7, name: None, input: String::from("datetime(\"2022-01-10T09:30:00+05:30\") + hours(2.5)")},
+                CellRequest {id: 8, name: None, input: String::from("datetime(\"2022-01-10T04:00:00Z\") == datetime(\"2022-01-10T09:30:00+05:30\")")},
+                CellRequest {id: 9, name: None, input: String::from("deadline > start")},
+                CellRequest {id: 10, name: None, input: String::from("date(\"bad\")")},
+                CellRequest {id: 11, name: None, input: String::from("deadline.hour")},
+                CellRequest {id: 12, name: None, input: String::from("start - minutes(90)")},
+            ],
+            input: None,
+            ..Default::default()
+        };
+        let mut i_result = interpreter::interpret_all(program);
+        let expected_results = vec![
+            CellResponse { id: 1, output: String::from("29 days"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 2, output: String::from("2021-12-20"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 3, output: String::from("2022-01-10"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 4, output: String::from("21"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 5, output: String::from("2022"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 6, output: String::from("2022-01-17"), error: String::from(""), ..Default::default() },
+            // Keeps the offset it was written in
+            CellResponse { id: 7, output: String::from("2022-01-10T12:00:00+05:30"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 8, output: String::from("True"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 9, output: String::from("True"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 10, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_INVALID_ARG), ..Default::default() },
+            CellResponse { id: 11, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_UNK_FIELD), ..Default::default() },
+            // Partial days turn a date into a datetime
+            CellResponse { id: 12, output: String::from("2021-12-19T22:30:00+00:00"), error: String::from(""), ..Default::default() },
+        ];
+        i_result.results.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(i_result.results, expected_results);
+    }
+
+    #[test]
+    fn test_records() {
+        let program = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: None, input: String::from("Point:\n    x, y")},
+                CellRequest {id: 2, name: None, input: String::from("DateString:\n    year\n    month\n    day")},
+                CellRequest {id: 3, name: Some(String::from("d")), input: String::from("DateString(2023, \"03\", \"05\")")},
+                CellRequest {id: 4, name: None, input: String::from("d.year + 1")},
+                CellRequest {id: 5, name: None, input: String::from("d.month + \"-\" + d.day")},
+                CellRequest {id: 6, name: None, input: String::from("d == DateString(2023, \"03\", \"05\")")},
+                CellRequest {id: 7, name: None, input: String::from("d == DateString(2023, \"03\", \"06\")")},
+                CellRequest {id: 8, name: None, input: String::from("Point(1, 2).y")},
+                CellRequest {id: 9, name: None, input: String::from("d.hour")},
+                CellRequest {id: 10, name: None, input: String::from("Point(1)")},
+                CellRequest {id: 11, name: None, input: String::from("Bad:\n    x, 1")},
+                // Arguments can't be taken from the surrounding expression
+                CellRequest {id: 12, name: None, input: String::from("2 + Point(1)")},
+            ],
+            input: None,
+            ..Default::default()
+        };
+        let mut i_result = interpreter::interpret_all(program);
+        let expected_results = vec![
+            CellResponse { id: 1, output: String::from("Point: x, y"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 2, output: String::from("DateString: year, month, day"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 3, output: String::from("DateString(year: 2023, month: \"03\", day: \"05\")"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 4, output: String::from("2024"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 5, output: String::from("\"03-05\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 6, output: String::from("True"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 7, output: String::from("False"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 8, output: String::from("2"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 9, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_UNK_FIELD), ..Default::default() },
+            CellResponse { id: 10, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_FN_ARITY), ..Default::default() },
+            CellResponse { id: 11, output: String::from(""), error: format::repr_error(avs::constants::PARSE_ERR_UNEXPECTED_TOKEN), ..Default::default() },
+            CellResponse { id: 12, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_FN_ARITY), ..Default::default() },
+        ];
+        i_result.results.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(i_result.results, expected_results);
+    }
+
+    #[test]
+    fn test_call_malformed_stack() {
+        let mut env = Environment::new(APP_SYMBOL_START);
+        assert_eq!(interpreter::call_function(&mut env, &mut vec![]), avs::constants::RUNTIME_ERR_FN_ARITY);
+        // Function symbol without an argument count
+        assert_eq!(interpreter::call_function(&mut env, &mut vec![avs::constants::AV_FN_SORT.symbol]), avs::constants::RUNTIME_ERR_FN_ARITY);
+    }
+
+    #[test]
+    fn test_lists_maps() {
+        let program = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: Some(String::from("xs")), input: String::from("[1, 2 * 3, \"three\"]")},
+                CellRequest {id: 2, name: Some(String::from("ys")), input: String::from("push(xs, [4, -5])")},
+                CellRequest {id: 3, name: None, input: String::from("len(xs) + len(ys)")},
+                CellRequest {id: 4, name: None, input: String::from("get(ys, -1)")},
+                CellRequest {id: 5, name: None, input: String::from("set(xs, 0, (10 + 1))")},
+                CellRequest {id: 6, name: Some(String::from("m")), input: String::from("{\"a\": 1, \"b\": [2]}")},
+                CellRequest {id: 7, name: None, input: String::from("get(set(m, \"a\", 5), \"a\") + get(m, \"a\")")},
+                CellRequest {id: 8, name: None, input: String::from("[1, [2]] == [1, [1 + 1]]")},
+                CellRequest {id: 9, name: None, input: String::from("get(xs, 3)")},
+                CellRequest {id: 10, name: None, input: String::from("get(m, \"c\")")},
+                CellRequest {id: 11, name: None, input: String::from("[1, 2")},
+                CellRequest {id: 12, name: None, input: String::from("[]")},
+            ],
+            input: None,
+            ..Default::default()
+        };
+        let mut i_result = interpreter::interpret_all(program);
+        let expected_results = vec![
+            CellResponse { id: 1, output: String::from("[1, 6, \"three\"]"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 2, output: String::from("[1, 6, \"three\", [4, -5]]"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 3, output: String::from("7"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 4, output: String::from("[4, -5]"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 5, output: String::from("[11, 6, \"three\"]"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 6, output: String::from("{a: 1, b: [2]}"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 7, output: String::from("6"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 8, output: String::from("True"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 9, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_INVALID_ARG), ..Default::default() },
+            CellResponse { id: 10, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_UNK_FIELD), ..Default::default() },
+            CellResponse { id: 11, output: String::from(""), error: format::repr_error(avs::constants::PARSE_ERR_UNMATCHED_PARENS), ..Default::default() },
+            CellResponse { id: 12, output: String::from("[]"), error: String::from(""), ..Default::default() },
+        ];
+        i_result.results.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(i_result.results, expected_results);
+    }
+
+    #[test]
+    fn test_math() {
+        let program = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: None, input: String::from("2 ^ 3 ^ 2")},
+                CellRequest {id: 2, name: None, input: String::from("2 * (1 + 2) ^ 2")},
+                CellRequest {id: 3, name: None, input: String::from("pow(2, 10) - 2 ^ 10")},
+                CellRequest {id: 4, name: None, input: String::from("round(2.675, 2)")},
+                CellRequest {id: 5, name: None, input: String::from("round(1250, -2) + round(2.5)")},
+                CellRequest {id: 6, name: None, input: String::from("ln(0)")},
+                CellRequest {id: 7, name: None, input: String::from("sqrt(-4)")},
+                CellRequest {id: 8, name: None, input: String::from("gcd(12, 18) + lcm(4, 6)")},
+                CellRequest {id: 9, name: None, input: String::from("clamp(15, 0, 10)")},
+                CellRequest {id: 10, name: None, input: String::from("round(pi, 4)")},
+                CellRequest {id: 11, name: None, input: String::from("tau / pi")},
+                CellRequest {id: 12, name: None, input: String::from("log10(1000) + log2(8)")},
+                CellRequest {id: 13, name: None, input: String::from("sign(-3) * hypot(3, 4)")},
+                CellRequest {id: 14, name: None, input: String::from("round(atan2(1, 1) * 4, 5)")},
+                CellRequest {id: 15, name: None, input: String::from("gcd(1)")},
+                CellRequest {id: 16, name: None, input: String::from("round()")},
+            ],
+            input: None,
+            ..Default::default()
+        };
+        let mut i_result = interpreter::interpret_all(program);
+        let expected_results = vec![
+            CellResponse { id: 1, output: String::from("512"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 2, output: String::from("18"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 3, output: String::from("0"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 4, output: String::from("2.68"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 5, output: String::from("1303"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 6, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_DOMAIN), ..Default::default() },
+            CellResponse { id: 7, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_DOMAIN), ..Default::default() },
+            CellResponse { id: 8, output: String::from("18"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 9, output: String::from("10"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 10, output: String::from("3.1416"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 11, output: String::from("2"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 12, output: String::from("6"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 13, output: String::from("-5"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 14, output: String::from("3.14159"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 15, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_FN_ARITY), ..Default::default() },
+            CellResponse { id: 16, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_FN_ARITY), ..Default::default() },
+        ];
+        i_result.results.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(i_result.results, expected_results);
+    }
+
+    #[test]
+    fn test_strings() {
+        let program = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: None, input: String::from("upper(\"abc\") + lower(\" Long Mixed Case\")")},
+                CellRequest {id: 2, name: None, input: String::from("trim(\"   padded text   \")")},
+                CellRequest {id: 3, name: None, input: String::from("split(\"a,b,,c\", \",\")")},
+                CellRequest {id: 4, name: None, input: String::from("join(split(\"2023-03-05\", \"-\"), \"/\")")},
+                CellRequest {id: 5, name: None, input: String::from("join([1, 2.5, \"x\"], \" \")")},
+                CellRequest {id: 6, name: None, input: String::from("replace(\"a-b-c\", \"-\", \" + \")")},
+                CellRequest {id: 7, name: None, input: String::from("contains(\"notebook\", \"book\") and starts_with(\"notebook\", \"note\")")},
+                CellRequest {id: 8, name: None, input: String::from("ends_with(\"notebook\", \"note\")")},
+                CellRequest {id: 9, name: None, input: String::from("slice(\"Hello World\", 6) + slice(\"Hello\", 0, -2)")},
+                CellRequest {id: 10, name: None, input: String::from("repeat(\"ab\", 3)")},
+                CellRequest {id: 11, name: None, input: String::from("pad_left(\"7\", 3, \"0\") + pad_right(\"x\", 3) + \"|\"")},
+                CellRequest {id: 12, name: None, input: String::from("char(codepoint(\"A\") + 1)")},
+                CellRequest {id: 13, name: None, input: String::from("len(\"héllo\") + len(\"a longer string\")")},
+                CellRequest {id: 14, name: None, input: String::from("upper(1)")},
+                CellRequest {id: 15, name: None, input: String::from("replace(\"abc\", \"\", \"x\")")},
+            ],
+            input: None,
+            ..Default::default()
+        };
+        let mut i_result = interpreter::interpret_all(program);
+        let expected_results = vec![
+            CellResponse { id: 1, output: String::from("\"ABC long mixed case\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 2, output: String::from("\"padded text\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 3, output: String::from("[\"a\", \"b\", \"\", \"c\"]"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 4, output: String::from("\"2023/03/05\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 5, output: String::from("\"1 2.5 x\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 6, output: String::from("\"a + b + c\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 7, output: String::from("True"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 8, output: String::from("False"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 9, output: String::from("\"WorldHel\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 10, output: String::from("\"ababab\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 11, output: String::from("\"007x  |\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 12, output: String::from("\"B\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 13, output: String::from("20"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 14, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_EXPECTED_STR), ..Default::default() },
+            CellResponse { id: 15, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_INVALID_ARG), ..Default::default() },
+        ];
+        i_result.results.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(i_result.results, expected_results);
+    }
+
+    #[test]
+    fn test_statistics() {
+        let program = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: None, input: String::from("sum([1, 2, 3.5])")},
+                CellRequest {id: 2, name: None, input: String::from("mean([2, 4, 4, 4, 5, 5, 7, 9]) + stdev_p([2, 4, 4, 4, 5, 5, 7, 9])")},
+                CellRequest {id: 3, name: None, input: String::from("median([5, 1, 3, 2])")},
+                CellRequest {id: 4, name: None, input: String::from("mode([1, 2, 2, 3, 3])")},
+                CellRequest {id: 5, name: None, input: String::from("variance([1, 2, 3, 4])")},
+                CellRequest {id: 6, name: None, input: String::from("percentile([1, 2, 3, 4, 5], 0.9)")},
+                CellRequest {id: 7, name: None, input: String::from("min([4, 2, 8]) + max(1, 7, 3)")},
+                CellRequest {id: 8, name: None, input: String::from("count([1, \"a\", 2])")},
+                CellRequest {id: 9, name: None, input: String::from("count_if([10, 50, 75, 90], \">=50\")")},
+                CellRequest {id: 10, name: None, input: String::from("cumsum([1, 2, 3])")},
+                CellRequest {id: 11, name: None, input: String::from("[1, 1 / 0]")},
+                CellRequest {id: 12, name: None, input: String::from("sum([1, 1 / 0, 2])")},
+                CellRequest {id: 13, name: None, input: String::from("sum([1, 1 / 0, \"n/a\", 2], \"skip\")")},
+                CellRequest {id: 14, name: None, input: String::from("mean([1, \"a\"])")},
+                CellRequest {id: 15, name: None, input: String::from("stdev([1])")},
+            ],
+            input: None,
+            ..Default::default()
+        };
+        let mut i_result = interpreter::interpret_all(program);
+        let expected_results = vec![
+            CellResponse { id: 1, output: String::from("6.5"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 2, output: String::from("7"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 3, output: String::from("2.5"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 4, output: String::from("2"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 5, output: String::from("1.6666666666666667"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 6, output: String::from("4.6"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 7, output: String::from("9"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 8, output: String::from("2"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 9, output: String::from("3"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 10, output: String::from("[1, 3, 6]"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 11, output: String::from("[1, #Error]"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 12, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_DIV_Z), ..Default::default() },
+            CellResponse { id: 13, output: String::from("3"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 14, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_EXPECTED_NUM), ..Default::default() },
+            CellResponse { id: 15, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_DIV_Z), ..Default::default() },
+        ];
+        i_result.results.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(i_result.results, expected_results);
+    }
+
+    #[test]
+    fn test_finance() {
+        let program = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: None, input: String::from("round(pmt(0.08 / 12, 10, 10000), 2)")},
+                CellRequest {id: 2, name: None, input: String::from("round(pv(0.08 / 12, 240, 500), 2)")},
+                CellRequest {id: 3, name: None, input: String::from("round(fv(0.06 / 12, 10, -200, -500, 1), 2)")},
+                CellRequest {id: 4, name: None, input: String::from("round(nper(0.01, -100, -1000, 10000, 1), 4)")},
+                CellRequest {id: 5, name: None, input: String::from("round(rate(48, -200, 8000), 6)")},
+                CellRequest {id: 6, name: None, input: String::from("round(npv(0.1, -10000, 3000, 4200, 6800), 2)")},
+                CellRequest {id: 7, name: None, input: String::from("round(irr([-70000, 12000, 15000, 18000, 21000, 26000]), 4)")},
+                CellRequest {id: 8, name: None, input: String::from("round(xirr([-10000, 2750, 4250, 3250, 2750], [date(\"2008-01-01\"), date(\"2008-03-01\"), date(\"2008-10-30\"), date(\"2009-02-15\"), date(\"2009-04-01\")]), 4)")},
+                CellRequest {id: 9, name: None, input: String::from("irr([100, 200])")},
+                CellRequest {id: 10, name: None, input: String::from("pmt(0.1, 10)")},
+            ],
+            input: None,
+            ..Default::default()
+        };
+        let mut i_result = interpreter::interpret_all(program);
+        let expected_results = vec![
+            CellResponse { id: 1, output: String::from("-1037.03"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 2, output: String::from("-59777.15"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 3, output: String::from("2581.4"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 4, output: String::from("59.6739"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 5, output: String::from("0.007701"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 6, output: String::from("1188.44"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 7, output: String::from("0.0866"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 8, output: String::from("0.3734"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 9, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_DOMAIN), ..Default::default() },
+            CellResponse { id: 10, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_FN_ARITY), ..Default::default() },
+        ];
+        i_result.results.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(i_result.results, expected_results);
+    }
+
+    fn random_results(other_cell: &str, runs: &[(u64, u64)]) -> Vec<CellResponse> {
+        let program = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: None, input: String::from("random()")},
+                CellRequest {id: 2, name: None, input: String::from("random_int(1, 1000000)")},
+                CellRequest {id: 3, name: None, input: String::from("shuffle([1, 2, 3, 4, 5, 6, 7, 8])")},
+                CellRequest {id: 4, name: None, input: String::from(other_cell)},
+            ],
+            input: None,
+            random: Some(RandomRequest { seed: 7, runs: runs.iter().cloned().collect() }),
+            ..Default::default()
+        };
+        let mut results = interpreter::interpret_all(program).results;
+        results.sort_by(|a, b| a.id.cmp(&b.id));
+        return results
+    }
+
+    #[test]
+    fn test_random() {
+        let first = random_results("1 + 1", &[]);
+        assert!(first.iter().all(|cell| cell.error == ""));
+        // Editing an unrelated cell doesn't change the random values
+        let edited = random_results("choice([1, 2]) + 1", &[]);
+        assert_eq!(first[0..3], edited[0..3]);
+        // Re-running a cell only changes that cell
+        let rerun = random_results("1 + 1", &[(1, 1)]);
+        assert_ne!(first[0], rerun[0]);
+        assert_eq!(first[1..], rerun[1..]);
+
+        let program = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: None, input: String::from("random_int(6, 1)")},
+                CellRequest {id: 2, name: None, input: String::from("sample([1, 2], 3)")},
+                CellRequest {id: 3, name: None, input: String::from("len(sample([1, 2, 3], 2))")},
+                CellRequest {id: 4, name: None, input: String::from("random(1)")},
+            ],
+            input: None,
+            ..Default::default()
+        };
+        let mut i_result = interpreter::interpret_all(program);
+        let expected_results = vec![
+            CellResponse { id: 1, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_INVALID_ARG), ..Default::default() },
+            CellResponse { id: 2, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_INVALID_ARG), ..Default::default() },
+            CellResponse { id: 3, output: String::from("2"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 4, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_FN_ARITY), ..Default::default() },
+        ];
+        i_result.results.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(i_result.results, expected_results);
+    }
+
+    #[test]
+    fn test_regex() {
+        let program = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: None, input: String::from("matches(\"order 66\", \"[0-9]+$\")")},
+                CellRequest {id: 2, name: None, input: String::from("find(\"GET /home 404\", \"[0-9]{3}\")")},
+                CellRequest {id: 3, name: None, input: String::from("find_all(\"a1 b22 c333\", \"[0-9]+\")")},
+                CellRequest {id: 4, name: None, input: String::from("get(captures(\"user=ada id=7\", \"user=(?P<name>[a-z]+) id=([0-9]+)\"), \"name\")")},
+                CellRequest {id: 5, name: None, input: String::from("get(captures(\"user=ada id=7\", \"user=(?P<name>[a-z]+) id=([0-9]+)\"), 2)")},
+                CellRequest {id: 6, name: None, input: String::from("replace_regex(\"2020-01-31\", \"([0-9]+)-([0-9]+)-([0-9]+)\", \"$3/$2/$1\")")},
+                CellRequest {id: 7, name: None, input: String::from("find(\"abc\", \"[\")")},
+            ],
+            input: None,
+            ..Default::default()
+        };
+        let mut i_result = interpreter::interpret_all(program);
+        let expected_results = vec![
+            CellResponse { id: 1, output: String::from("True"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 2, output: String::from("\"404\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 3, output: String::from("[\"1\", \"22\", \"333\"]"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 4, output: String::from("\"ada\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 5, output: String::from("\"7\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 6, output: String::from("\"31/01/2020\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 7, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_INVALID_ARG), ..Default::default() },
+        ];
+        i_result.results.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(i_result.results, expected_results);
+    }
+
+    #[test]
+    fn test_units() {
+        let program = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: None, input: String::from("5 km + 300 m")},
+                CellRequest {id: 2, name: None, input: String::from("(10 kg) * (9.8 m/s^2)")},
+                CellRequest {id: 3, name: None, input: String::from("convert(26.2 mi, \"km\")")},
+                CellRequest {id: 4, name: None, input: String::from("convert(60 mph, \"km/h\")")},
+                CellRequest {id: 5, name: None, input: String::from("5 km + 3 kg")},
+                CellRequest {id: 6, name: None, input: String::from("2 h * 3")},
+                CellRequest {id: 7, name: None, input: String::from("(3 m)^2")},
+                CellRequest {id: 8, name: None, input: String::from("100 m / 10 s")},
+                CellRequest {id: 9, name: None, input: String::from("1 km == 1000 m")},
+                CellRequest {id: 10, name: None, input: String::from("1 km > 900 m")},
+                CellRequest {id: 11, name: None, input: String::from("(1 km) / (250 m)")},
+                CellRequest {id: 12, name: None, input: String::from("convert(3 kg, \"mi\")")},
+                CellRequest {id: 13, name: None, input: String::from("5 km + 1")},
+                CellRequest {id: 14, name: None, input: String::from("(1 m^100) * (1 m^100)")},
+                CellRequest {id: 15, name: None, input: String::from("1 m^-128")},
+                // min is a builtin, so it doesn't hide the unit
+                CellRequest {id: 16, name: None, input: String::from("5 min + 30 s")},
+            ],
+            input: None,
+            ..Default::default()
+        };
+        let mut i_result = interpreter::interpret_all(program);
+        let expected_results = vec![
+            CellResponse { id: 1, output: String::from("5.3 km"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 2, output: String::from("98 N"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 3, output: String::from("42.1648128 km"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 4, output: String::from("96.56064 km/h"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 5, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_DIMENSION), ..Default::default() },
+            CellResponse { id: 6, output: String::from("6 h"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 7, output: String::from("9 m^2"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 8, output: String::from("10 m/s"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 9, output: String::from("True"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 10, output: String::from("True"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 11, output: String::from("4"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 12, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_DIMENSION), ..Default::default() },
+            CellResponse { id: 13, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_DIMENSION), ..Default::default() },
+            CellResponse { id: 14, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_INVALID_ARG), ..Default::default() },
+            CellResponse { id: 15, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_INVALID_ARG), ..Default::default() },
+            CellResponse { id: 16, output: String::from("5.5 min"), error: String::from(""), ..Default::default() },
+        ];
+        i_result.results.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(i_result.results, expected_results);
+    }
+
+    #[test]
+    fn test_format() {
+        let program = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: None, input: String::from("format(1234567.891, \"#,##0.00\")")},
+                CellRequest {id: 2, name: None, input: String::from("format(1234567.891, \"#,##0.00 ¤\", \"de\")")},
+                CellRequest {id: 3, name: None, input: String::from("format(0.0725, \"0.0%\")")},
+                CellRequest {id: 4, name: None, input: String::from("format(-42.5d, \"¤#,##0.00;(¤#,##0.00)\")")},
+                CellRequest {id: 5, name: None, input: String::from("format(6.02e23, \"0.00E+00\")")},
+                CellRequest {id: 6, name: None, input: String::from("format(date(\"2020-01-31\"), \"EEEE, d MMMM yyyy\")")},
+                CellRequest {id: 7, name: None, input: String::from("format(date(\"2020-01-31\"), \"d MMMM yyyy\", \"fr\")")},
+                CellRequest {id: 8, name: None, input: String::from("format(1, \"0.00\", \"klingon\")")},
+                CellRequest {id: 9, name: None, input: String::from("format(\"text\", \"0.00\")")},
+            ],
+            input: None,
+            ..Default::default()
+        };
+        let mut i_result = interpreter::interpret_all(program);
+        let expected_results = vec![
+            CellResponse { id: 1, output: String::from("\"1,234,567.89\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 2, output: String::from("\"1.234.567,89 €\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 3, output: String::from("\"7.3%\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 4, output: String::from("\"($42.50)\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 5, output: String::from("\"6.02E+23\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 6, output: String::from("\"Friday, 31 January 2020\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 7, output: String::from("\"31 janvier 2020\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 8, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_INVALID_ARG), ..Default::default() },
+            CellResponse { id: 9, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_INVALID_TYPE), ..Default::default() },
+        ];
+        i_result.results.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(i_result.results, expected_results);
+    }
+
+    #[test]
+    fn test_host_functions() {
+        use avs::registry::{FunctionRegistry, HostFunction, Param};
+        use avs::strings::text_arg;
+        use fnv::FnvHashMap;
+        use std::rc::Rc;
+
+        // Stands in for a lookup against the host's own data
+        let prices: Rc<FnvHashMap<String, f64>> = Rc::new(vec![(String::from("ACME"), 12.5)].into_iter().collect());
+        let mut functions = FunctionRegistry::new();
+        functions.register(HostFunction {
+            name: String::from("price"),
+            params: vec![Param::required("symbol"), Param::optional("date")],
+            docs: String::from("Closing price for a ticker symbol"),
+            pure: true,
+            func: Rc::new(move |env: &mut Environment, args: &[u64]| {
+                let symbol = match text_arg(env, args[0]) {
+                    Ok(symbol) => symbol,
+                    Err(err) => return err
+                };
+                match prices.get(&*symbol) {
+                    // Older prices are a flat discount in this example
+                    Some(price) if args.len() == 2 => (price - 1.5).to_bits(),
+                    Some(price) => price.to_bits(),
+                    None => avs::constants::RUNTIME_ERR_INVALID_ARG
+                }
+            })
+        }).unwrap();
+
+        let program = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: None, input: String::from("price(\"ACME\")")},
+                CellRequest {id: 2, name: None, input: String::from("PRICE(\"ACME\") * 2")},
+                CellRequest {id: 3, name: None, input: String::from("price(\"ACME\", date(\"2020-01-31\"))")},
+                CellRequest {id: 4, name: None, input: String::from("price()")},
+                CellRequest {id: 5, name: None, input: String::from("price(\"WIDGET\")")},
+                CellRequest {id: 6, name: None, input: String::from("default(price(\"WIDGET\"), 0)")},
+                // Functions are values, so they can be bound to another name
+                CellRequest {id: 7, name: Some(String::from("lookup")), input: String::from("price")},
+                CellRequest {id: 8, name: None, input: String::from("lookup(\"ACME\")")},
+            ],
+            input: None,
+            ..Default::default()
+        };
+        let mut i_result = interpreter::interpret_all_with_functions(program, &functions);
+        let expected_results = vec![
+            CellResponse { id: 1, output: String::from("12.5"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 2, output: String::from("25"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 3, output: String::from("11"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 4, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_FN_ARITY), ..Default::default() },
+            CellResponse { id: 5, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_INVALID_ARG), ..Default::default() },
+            CellResponse { id: 6, output: String::from("0"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 7, output: String::from("<Function>"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 8, output: String::from("12.5"), error: String::from(""), ..Default::default() },
+        ];
+        i_result.results.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(i_result.results, expected_results);
+    }
+
+    #[test]
+    fn test_sorting() {
+        let program = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: None, input: String::from("sort([3, 1, 2])")},
+                CellRequest {id: 2, name: None, input: String::from("sort_desc([3, 1, 2])")},
+                CellRequest {id: 3, name: None, input: String::from("sort([\"pear\", true, 1 / 0, 10, \"apple\", 2])")},
+                CellRequest {id: 4, name: None, input: String::from("sort_by([\"ccc\", \"a\", \"bb\"], len)")},
+                CellRequest {id: 5, name: Some(String::from("people")), input: String::from("[{\"name\": \"ada\", \"team\": 2}, {\"name\": \"grace\", \"team\": 1}, {\"name\": \"alan\", \"team\": 2}]")},
+                CellRequest {id: 6, name: None, input: String::from("get(sort_by(people, \"team\"), 0)")},
+                CellRequest {id: 7, name: None, input: String::from("get(sort_by(people, [\"team\", \"name\"], [\"desc\", \"desc\"]), 0)")},
+                CellRequest {id: 8, name: None, input: String::from("sort_by(people, \"team\", \"sideways\")")},
+                CellRequest {id: 9, name: None, input: String::from("sort(3)")},
+            ],
+            input: None,
+            ..Default::default()
+        };
+        let mut i_result = interpreter::interpret_all(program);
+        let expected_results = vec![
+            CellResponse { id: 1, output: String::from("[1, 2, 3]"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 2, output: String::from("[3, 2, 1]"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 3, output: String::from("[2, 10, \"apple\", \"pear\", True, #Error]"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 4, output: String::from("[\"a\", \"bb\", \"ccc\"]"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 5, output: String::from("[{name: \"ada\", team: 2}, {name: \"grace\", team: 1}, {name: \"alan\", team: 2}]"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 6, output: String::from("{name: \"grace\", team: 1}"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 7, output: String::from("{name: \"alan\", team: 2}"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 8, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_INVALID_ARG), ..Default::default() },
+            CellResponse { id: 9, output: String::from(""), error: format::repr_error(avs::constants::RUNTIME_ERR_INVALID_TYPE), ..Default::default() },
         ];
+        i_result.results.sort_by(|a, b| a.id.cmp(&b.id));
         assert_eq!(i_result.results, expected_results);
     }
 
+    #[test]
+    fn test_garbage_collection() {
+        // Enough cells to trigger a collection partway through evaluation
+        let cell_count = DEFAULT_GC_THRESHOLD / 2;
+        let mut body: Vec<CellRequest> = (0..cell_count).map(|i| CellRequest {
+            id: i,
+            name: Some(format!("s{}", i)),
+            input: format!("\"Intermediate value \" + \"number {}\"", i)
+        }).collect();
+        body.push(CellRequest {id: cell_count, name: None, input: String::from("s1 + \" and \" + s2000")});
+        let program = EvalRequest {
+            body: body,
+            input: None,
+            ..Default::default()
+        };
+        let mut i_result = interpreter::interpret_all(program);
+        i_result.results.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(i_result.results[1].output, "\"Intermediate value number 1\"");
+        assert_eq!(i_result.results[cell_count as usize].output,
+            "\"Intermediate value number 1 and Intermediate value number 2000\"");
+    }
 
     #[test]
     fn test_builtin_math_fn() {
@@ -371,17 +1084,18 @@ use crate::lexer::lex;
                 CellRequest {id: 6, name: None, input: String::from("truncate(-2.5)")},
                 CellRequest {id: 7, name: None, input: String::from("sqrt(25)")},
             ],
-            input: None
+            input: None,
+            ..Default::default()
         };
         let mut i_result = interpreter::interpret_all(program);
         let expected_results = vec![
-            CellResponse { id: 1, output: String::from("23"), error: String::from("") },
-            CellResponse { id: 2, output: String::from("3"), error: String::from("") },
-            CellResponse { id: 3, output: String::from("2"), error: String::from("") },
-            CellResponse { id: 4, output: String::from("3"), error: String::from("") },
-            CellResponse { id: 5, output: String::from("-3"), error: String::from("") },
-            CellResponse { id: 6, output: String::from("-2"), error: String::from("") },
-            CellResponse { id: 7, output: String::from("5"), error: String::from("") },
+            CellResponse { id: 1, output: String::from("23"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 2, output: String::from("3"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 3, output: String::from("2"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 4, output: String::from("3"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 5, output: String::from("-3"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 6, output: String::from("-2"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 7, output: String::from("5"), error: String::from(""), ..Default::default() },
         ];
 
         i_result.results.sort_by(|a, b| a.id.cmp(&b.id));
@@ -399,19 +1113,144 @@ use crate::lexer::lex;
         // Can't just have single value inputs anymore, need cells as inputs
         let mut program = EvalRequest {
             body: vec![cell_a, cell_b, cell_c],
-            input: None
+            input: None,
+            ..Default::default()
         };
         
         let i_result = interpreter::interpret_all(program);
 
         let expected_results = vec![
-            CellResponse { id: 1, output: String::from("\"Hello\""), error: String::from("") },
-            CellResponse { id: 2, output: String::from("\"Hello Arevel\""), error: String::from("") },
-            CellResponse { id: 3, output: String::from("\"Arevel Hello\""), error: String::from("") },
+            CellResponse { id: 1, output: String::from("\"Hello\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 2, output: String::from("\"Hello Arevel\""), error: String::from(""), ..Default::default() },
+            CellResponse { id: 3, output: String::from("\"Arevel Hello\""), error: String::from(""), ..Default::default() },
         ];
 
         println!("{:?}", i_result);
         assert_eq!(i_result.results, expected_results);
     }
 
+
+    #[test]
+    fn test_error_recovery() {
+        let program = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: Some(String::from("one")), input: String::from("1 / 0")},
+                CellRequest {id: 2, name: None, input: String::from("default(one, 5)")},
+                CellRequest {id: 3, name: None, input: String::from("is_error(one)")},
+                CellRequest {id: 4, name: None, input: String::from("default(2 + 1, 5)")},
+                CellRequest {id: 5, name: None, input: String::from("is_error(2)")},
+                CellRequest {id: 6, name: None, input: String::from("one + 1")},
+            ],
+            input: None,
+            ..Default::default()
+        };
+        let mut i_result = interpreter::interpret_all(program);
+        let div_z_err = format::repr_error(avs::constants::RUNTIME_ERR_DIV_Z);
+        let expected_results = vec![
+            CellResponse { id: 1, output: String::from(""), error: div_z_err.clone(), ..Default::default() },
+            CellResponse { id: 2, output: String::from("5"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 3, output: String::from("True"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 4, output: String::from("3"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 5, output: String::from("False"), error: String::from(""), ..Default::default() },
+            // Errors propagate as-is to dependent cells
+            CellResponse { id: 6, output: String::from(""), error: div_z_err.clone(), ..Default::default() },
+        ];
+
+        i_result.results.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(i_result.results, expected_results);
+    }
+
+
+    #[test]
+    fn test_eval_limits() {
+        let program = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: None, input: String::from("1 + 2 * 3 + 4")},
+                CellRequest {id: 2, name: None, input: String::from("1 + 2")},
+                CellRequest {id: 3, name: None, input: String::from("\"alpha \" + \"bravo \" + \"charlie\"")},
+            ],
+            input: None,
+            limits: Some(LimitsRequest {
+                max_steps: Some(5),
+                max_depth: None,
+                max_allocations: Some(2),
+                timeout_ms: Some(10_000),
+                solver_tolerance: None,
+                solver_iterations: None
+            }),
+            ..Default::default()
+        };
+        let mut i_result = interpreter::interpret_all(program);
+        let expected_results = vec![
+            CellResponse { id: 1, output: String::from(""), error: format::repr_error(avs::constants::INTERPRETER_ERR_LIMIT_STEPS), ..Default::default() },
+            // The limit applies per cell, so other cells still finish.
+            CellResponse { id: 2, output: String::from("3"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 3, output: String::from(""), error: format::repr_error(avs::constants::INTERPRETER_ERR_LIMIT_MEMORY), ..Default::default() },
+        ];
+
+        i_result.results.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(i_result.results, expected_results);
+    }
+
+
+    #[test]
+    fn test_eval_limits_zero_depth() {
+        // Zero depth allows no pointer hops, but plain values still resolve.
+        let program = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: Some(String::from("a")), input: String::from("1 + 2")},
+                CellRequest {id: 2, name: None, input: String::from("a")},
+            ],
+            input: None,
+            limits: Some(LimitsRequest {
+                max_depth: Some(0),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut i_result = interpreter::interpret_all(program);
+        let expected_results = vec![
+            CellResponse { id: 1, output: String::from("3"), error: String::from(""), ..Default::default() },
+            CellResponse { id: 2, output: String::from("3"), error: String::from(""), ..Default::default() },
+        ];
+
+        i_result.results.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(i_result.results, expected_results);
+    }
+
+
+    #[test]
+    fn test_eval_metrics() {
+        let program = EvalRequest {
+            body: vec![
+                CellRequest {id: 1, name: None, input: String::from("1 + 2 * 3")},
+                CellRequest {id: 2, name: None, input: String::from("\"Hello \" + \"World\"")},
+            ],
+            input: None,
+            collect_metrics: true,
+            ..Default::default()
+        };
+        let mut i_result = interpreter::interpret_all(program);
+        i_result.results.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let metrics_a = i_result.results[0].metrics.as_ref().unwrap();
+        assert_eq!(metrics_a.operators, 2);
+        assert_eq!(metrics_a.allocations, 0);
+        assert_eq!(metrics_a.cached, false);
+
+        // "Hello " and "World" are short enough to be stored inline.
+        // Only the concatenated result is allocated.
+        let metrics_b = i_result.results[1].metrics.as_ref().unwrap();
+        assert_eq!(metrics_b.operators, 1);
+        assert_eq!(metrics_b.allocations, 1);
+
+        let totals = i_result.metrics.unwrap();
+        assert_eq!(totals.cells, 2);
+        assert_eq!(totals.cached_cells, 0);
+        assert_eq!(totals.operators, 3);
+        assert_eq!(totals.allocations, 1);
+        assert_eq!(totals.eval_ns, metrics_a.eval_ns + metrics_b.eval_ns);
+        assert!(totals.total_ns >= totals.eval_ns);
+    }
+
 }
\ No newline at end of file